@@ -0,0 +1,19 @@
+use crate::backend::abc::{AbcBackend, AbcSettings};
+use crate::backend::{process_input_file, INPUT_TABS};
+
+#[test]
+fn test_abc() {
+    for name in INPUT_TABS {
+        let out = process_input_file::<AbcBackend>(name, AbcSettings::default());
+        insta::assert_snapshot!(name, out);
+    }
+}
+
+#[test]
+fn test_abc_title() {
+    let settings = AbcSettings { title: Some("Riff".to_string()), ..Default::default() };
+    let out = process_input_file::<AbcBackend>("multi_track", settings);
+    assert!(out.starts_with("X:1\nT:Riff\n"), "{out}");
+    let out = process_input_file::<AbcBackend>("multi_track", AbcSettings::default());
+    assert!(out.starts_with("X:1\nT:Guitar 1\n"), "{out}");
+}
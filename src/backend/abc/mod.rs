@@ -0,0 +1,296 @@
+//! Writes a tune in ABC notation, with an eighth as the unit note length.
+//!
+//! The rhythm is read like in [super::lilypond]: a note rings through the `~` and technique ticks
+//! after it. Notes are written at the usual written pitch of the guitar, an octave above the
+//! sounding one. Every line of the tab becomes a line of the tune, preceded by a `%%tab` line
+//! which gives the string and fret of every note, as ABC has no tablature of its own.
+
+#[cfg(test)]
+mod abc_tests;
+
+use std::{fmt::Write, time::Instant};
+
+use super::{
+    lilypond::{articulations, note_lengths, track_events},
+    muxml::{
+        common_measure_len, detect_key,
+        key::{AccidentalState, Key, Spelling},
+        settings::Meter,
+    },
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    fingering::{refret::part_tunings, Tuning},
+    parser::{parser::parse, parser::ParseResult, tab_element::TabElement},
+    rlen, time,
+};
+
+pub struct AbcBackend();
+
+#[derive(Clone, Debug, Default)]
+pub struct AbcSettings {
+    /// The `T:` header. The name of the first track is used if not set.
+    pub title: Option<String>,
+    /// Detected from the notes if not set
+    pub key: Option<Key>,
+    /// Detected as the most common measure length if not set
+    pub meter: Option<Meter>,
+    /// Write time signatures like 4/4 instead of 8/8
+    pub simplify_time_signature: bool,
+}
+
+impl Backend for AbcBackend {
+    type BackendSettings = AbcSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut tune = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        if let Err(e) = write_abc(&parsed, &mut tune, &settings) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(tune.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("title", "The title of the tune"),
+        ("key", "Key signature, like `Bb` or `-1`. Detected if not given."),
+        ("meter", "The time signature, like 3/4. Detected if not given."),
+        ("simplify-time-signature", "Simplify time signatures, e.g. 8/8 -> 4/4"),
+    ];
+    registry.register(SelectorBackend::new(
+        "abc",
+        "Writes a tune in ABC notation",
+        &["abc"],
+        options,
+        |o| {
+            Ok(BackendSelector::Abc(AbcSettings {
+                title: o.get("title")?,
+                key: o.get("key")?,
+                meter: o.get("meter")?,
+                simplify_time_signature: o.flag("simplify-time-signature")?,
+            }))
+        },
+    ));
+}
+
+/// A note name with its accidental and octave marks, like `^F` or `c'`
+fn note_name(spelling: Spelling, accidental: Option<&str>) -> String {
+    let mut name = match accidental {
+        Some("sharp") => "^",
+        Some("double-sharp") => "^^",
+        Some("flat") => "_",
+        Some("flat-flat") => "__",
+        Some(_) => "=",
+        None => "",
+    }
+    .to_string();
+    // `C` is middle C, `c` the octave above it
+    match spelling.octave {
+        0..=4 => {
+            name.push(spelling.step);
+            name.push_str(&",".repeat(4usize.saturating_sub(spelling.octave as usize)));
+        }
+        octave => {
+            name.push(spelling.step.to_ascii_lowercase());
+            name.push_str(&"'".repeat(octave as usize - 5));
+        }
+    }
+    name
+}
+
+/// The name ABC gives the major key, like `Bb`
+fn key_name(key: &Key) -> String {
+    let (tonic, alter) = key.tonic();
+    let accidental = match alter {
+        1 => "#",
+        -1 => "b",
+        _ => "",
+    };
+    format!("{}{accidental}", "CDEFGAB".as_bytes()[tonic] as char)
+}
+
+/// The most common length of the measures with notes, in eighths, like the meter of
+/// [super::muxml]
+fn detect_meter_len(parsed: &ParseResult) -> u32 {
+    common_measure_len(parsed.measures.iter().map(|measure| {
+        let range = &measure.data_range;
+        let silent = !parsed.tick_stream[*range.start() as usize..=*range.end() as usize]
+            .iter()
+            .any(|x| matches!(x, TabElement::Fret(_) | TabElement::DeadNote));
+        (rlen(range) / 6, silent)
+    }))
+}
+
+/// The `%%tab` token of the chord at `tick`, like `5:3` or `6:0+4:2h`
+fn tab_token(parsed: &ParseResult, tick: usize, measure_end: usize) -> String {
+    let notes = (0..6).rev().filter_map(|string| {
+        let note = match parsed.tick_stream[tick + string] {
+            TabElement::Fret(fret) => fret.to_string(),
+            TabElement::DeadNote => "x".to_string(),
+            _ => return None,
+        };
+        let technique =
+            match parsed.tick_stream.get(tick + 6 + string).filter(|_| tick + 6 < measure_end) {
+                Some(TabElement::HammerOn) => "h",
+                Some(TabElement::Pull) => "p",
+                Some(TabElement::Bend) => "b",
+                Some(TabElement::Release) => "r",
+                Some(TabElement::Slide) => "/",
+                _ => "",
+            };
+        Some(format!("{}:{note}{technique}", string + 1))
+    });
+    notes.collect::<Vec<_>>().join("+")
+}
+
+/// Writes the whole tune into `buf`
+pub fn write_abc(
+    parsed: &ParseResult, buf: &mut String, settings: &AbcSettings,
+) -> std::fmt::Result {
+    let key = settings.key.unwrap_or_else(|| detect_key(parsed));
+    let meter = settings.meter.unwrap_or_else(|| {
+        Meter::from_eighths(detect_meter_len(parsed), settings.simplify_time_signature)
+    });
+    let tunings = part_tunings(parsed);
+    let part_of = |stream_idx: usize| {
+        parsed.offsets.partition_point(|x| x.1 as usize <= stream_idx).saturating_sub(1)
+    };
+    let title = settings.title.as_deref();
+    let title = title.or(parsed.tracks.first().map(|x| x.name.as_str())).unwrap_or("Untitled");
+    writeln!(buf, "X:1")?;
+    writeln!(buf, "T:{title}")?;
+    writeln!(buf, "M:{}/{}", meter.beats, meter.beat_type)?;
+    writeln!(buf, "L:1/8")?;
+    let voices = parsed.tracks.len() > 1;
+    if voices {
+        for (track_idx, track) in parsed.tracks.iter().enumerate() {
+            writeln!(buf, "V:{} name=\"{}\"", track_idx + 1, track.name.replace('"', "'"))?;
+        }
+    }
+    writeln!(buf, "K:{}", key_name(&key))?;
+
+    let mut accidentals = AccidentalState::new(key);
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        if voices {
+            writeln!(buf, "V:{}", track_idx + 1)?;
+        }
+        let mut current_meter = meter;
+        let mut slur_open = false;
+        let mut music = String::new();
+        let mut tab = String::from("%%tab");
+        let measures = track_events(parsed, track);
+        for (measure_number, events) in measures.iter().enumerate() {
+            let range = &parsed.measures[track.measures[measure_number] as usize].data_range;
+            let measure_start = *range.start() as usize;
+            let measure_end = *range.end() as usize + 1;
+            let tuning: Tuning = tunings.get(part_of(measure_start)).copied().unwrap_or_default();
+            let len = rlen(range) / 6;
+            if len != current_meter.eighths() {
+                current_meter = Meter::from_eighths(len, settings.simplify_time_signature);
+                write!(music, "[M:{}/{}] ", current_meter.beats, current_meter.beat_type)?;
+            }
+            accidentals.reset();
+            for (event_idx, event) in events.iter().enumerate() {
+                let Some(tick) = event.tick else {
+                    write!(music, "z{} ", length_mark(event.len))?;
+                    tab.push_str(" z");
+                    continue;
+                };
+                let tied_over = match events.get(event_idx + 1) {
+                    Some(_) => false,
+                    None => measures
+                        .get(measure_number + 1)
+                        .is_some_and(|x| x.first().is_some_and(|x| x.tied && x.tick == Some(tick))),
+                };
+                let a = match event.tied {
+                    true => Default::default(),
+                    false => articulations(parsed, tick, measure_end),
+                };
+                tab.push(' ');
+                match event.tied {
+                    true => tab.push('-'),
+                    false => tab.push_str(&tab_token(parsed, tick, measure_end)),
+                }
+                let notes =
+                    (0..6).rev().filter_map(|string| match parsed.tick_stream[tick + string] {
+                        TabElement::Fret(fret) => Some((tuning.strings[string] + fret, false)),
+                        TabElement::DeadNote => Some((tuning.strings[string], true)),
+                        _ => None,
+                    });
+                // the octave of a spelling is the written one, see [super::muxml::fretboard]
+                let notes = notes.map(|(pitch, dead)| (key.spell(pitch), dead)).collect::<Vec<_>>();
+                let lengths = note_lengths(event.len);
+                for (idx, len) in lengths.iter().enumerate() {
+                    let last = idx == lengths.len() - 1;
+                    if last && a.slur_start && !slur_open {
+                        music.push('(');
+                        slur_open = true;
+                    }
+                    if idx == 0 && a.glissando_end {
+                        music.push_str("!slide!");
+                    }
+                    if idx == 0 && notes.iter().any(|x| x.1) {
+                        music.push_str("\"^x\"");
+                    }
+                    if notes.len() > 1 {
+                        music.push('[');
+                    }
+                    for (spelling, _) in &notes {
+                        // a tied note keeps the accidental of the note it is tied to
+                        let accidental = accidentals.accidental(spelling);
+                        music.push_str(&note_name(*spelling, accidental));
+                    }
+                    if notes.len() > 1 {
+                        music.push(']');
+                    }
+                    music.push_str(&length_mark(*len));
+                    if !last || tied_over {
+                        music.push('-');
+                    }
+                    if idx == 0 && a.slur_end && slur_open {
+                        music.push(')');
+                        slur_open = false;
+                    }
+                    music.push(' ');
+                }
+            }
+            music.push_str("| ");
+            tab.push_str(" |");
+            // a line of the tune for every line of the tab
+            let next_part = track
+                .measures
+                .get(measure_number + 1)
+                .map(|x| part_of(*parsed.measures[*x as usize].data_range.start() as usize));
+            if next_part != Some(part_of(measure_start)) {
+                writeln!(buf, "{tab}")?;
+                writeln!(buf, "{}", music.trim_end())?;
+                music.clear();
+                tab = String::from("%%tab");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The length of a note in eighths, as written after it: nothing for an eighth
+fn length_mark(eighths: u32) -> String {
+    match eighths {
+        1 => String::new(),
+        x => x.to_string(),
+    }
+}
@@ -0,0 +1,17 @@
+use crate::backend::alphatex::{AlphaTexBackend, AlphaTexSettings};
+use crate::backend::{process_input_file, INPUT_TABS};
+
+#[test]
+fn test_alphatex() {
+    for name in INPUT_TABS {
+        let out = process_input_file::<AlphaTexBackend>(name, AlphaTexSettings::default());
+        insta::assert_snapshot!(name, out);
+    }
+}
+
+#[test]
+fn test_alphatex_title() {
+    let settings = AlphaTexSettings { title: Some("Riff \"1\"".to_string()) };
+    let out = process_input_file::<AlphaTexBackend>("c_major", settings);
+    assert!(out.starts_with("\\title \"Riff \\\"1\\\"\"\n.\n"), "{out}");
+}
@@ -0,0 +1,189 @@
+//! Writes alphaTex, the text format of [alphaTab](https://alphatab.net), which renders a tab and
+//! its standard notation in the browser.
+//!
+//! The rhythm is read like in [super::lilypond]. alphaTex has no dotted durations without beat
+//! effects, so longer notes are split into tied ones, like `3.5.4 -.5.8`.
+
+#[cfg(test)]
+mod alphatex_tests;
+
+use std::{fmt::Write, time::Instant};
+
+use super::{
+    lilypond::{track_events, Event},
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    fingering::refret::part_tunings,
+    parser::{parser::parse, parser::ParseResult, tab_element::TabElement},
+    rlen, time,
+};
+
+pub struct AlphaTexBackend();
+
+#[derive(Clone, Debug, Default)]
+pub struct AlphaTexSettings {
+    /// The `\title` of the song. Left out if not set.
+    pub title: Option<String>,
+}
+
+impl Backend for AlphaTexBackend {
+    type BackendSettings = AlphaTexSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut tex = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        if let Err(e) = write_alphatex(&parsed, &mut tex, &settings) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(tex.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    registry.register(SelectorBackend::new(
+        "alphatex",
+        "Writes alphaTex, for rendering with alphaTab",
+        &["alphatex"],
+        &[("title", "The title of the song")],
+        |o| Ok(BackendSelector::AlphaTex(AlphaTexSettings { title: o.get("title")? })),
+    ));
+}
+
+/// Splits a number of eighths into plain durations, longest first
+fn plain_lengths(mut len: u32) -> Vec<u32> {
+    let mut result = vec![];
+    while len > 0 {
+        let eighths = [8, 4, 2, 1].into_iter().find(|x| *x <= len).unwrap();
+        result.push(eighths);
+        len -= eighths;
+    }
+    result
+}
+
+/// A string escaped for a quoted alphaTex value
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The effect of the note at `idx`, which leads to the next note on its string, like `{h}`
+fn note_effect(parsed: &ParseResult, idx: usize, measure_end: usize) -> String {
+    let TabElement::Fret(fret) = parsed.tick_stream[idx] else {
+        return String::new();
+    };
+    let at = |idx: usize| parsed.tick_stream.get(idx).filter(|_| idx < measure_end);
+    match at(idx + 6) {
+        Some(TabElement::HammerOn | TabElement::Pull) => "{h}".to_string(),
+        Some(TabElement::Slide) => "{sl}".to_string(),
+        Some(elem @ (TabElement::Bend | TabElement::Release)) => {
+            // in quarter tones, a whole tone if there is no note to bend to
+            let amount = match at(idx + 12) {
+                Some(TabElement::Fret(x)) => (*x as i16 - fret as i16).unsigned_abs() * 2,
+                Some(_) | None => 4,
+            };
+            // a release comes down from a pre-bend, so that no point is negative
+            match elem {
+                TabElement::Release => format!("{{b (0 {amount}) (60 0)}}"),
+                _ => format!("{{b (0 {amount})}}"),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Writes the whole document into `buf`
+pub fn write_alphatex(
+    parsed: &ParseResult, buf: &mut String, settings: &AlphaTexSettings,
+) -> std::fmt::Result {
+    if let Some(title) = &settings.title {
+        writeln!(buf, "\\title {}", quoted(title))?;
+    }
+    writeln!(buf, ".")?;
+    let tunings = part_tunings(parsed);
+    let part_of = |stream_idx: usize| {
+        parsed.offsets.partition_point(|x| x.1 as usize <= stream_idx).saturating_sub(1)
+    };
+    for track in &parsed.tracks {
+        writeln!(buf, "\\track {}", quoted(&track.name))?;
+        writeln!(buf, "\\staff {{tabs}}")?;
+        let first =
+            track.measures.first().map(|x| *parsed.measures[*x as usize].data_range.start());
+        let tuning = tunings.get(part_of(first.unwrap_or(0) as usize)).copied().unwrap_or_default();
+        // scientific pitch names, like `e4 b3 g3 d3 a2 e2`
+        let names = tuning.strings.iter().map(|x| {
+            let name = ["c", "c#", "d", "d#", "e", "f", "f#", "g", "g#", "a", "a#", "b"];
+            format!("{}{}", name[*x as usize % 12], x / 12 - 1)
+        });
+        writeln!(buf, "\\tuning {}", names.collect::<Vec<_>>().join(" "))?;
+        writeln!(buf, "\\instrument {}", track.midi_program())?;
+
+        let mut last_len = None;
+        let mut last_time = None;
+        let measures = track_events(parsed, track);
+        for (measure_number, events) in measures.iter().enumerate() {
+            let range = &parsed.measures[track.measures[measure_number] as usize].data_range;
+            let measure_end = *range.end() as usize + 1;
+            let time = rlen(range) / 6;
+            if last_time != Some(time) {
+                write!(buf, "\\ts {time} 8 ")?;
+                last_time = Some(time);
+            }
+            for Event { tick, len, tied } in events {
+                let notes = tick.map(|tick| {
+                    (0..6)
+                        .map(|string| (string, tick + string))
+                        .filter(|x| {
+                            matches!(
+                                parsed.tick_stream[x.1],
+                                TabElement::Fret(_) | TabElement::DeadNote
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                });
+                for (idx, len) in plain_lengths(*len).into_iter().enumerate() {
+                    if last_len != Some(len) {
+                        write!(buf, ":{} ", 8 / len)?;
+                        last_len = Some(len);
+                    }
+                    let Some(notes) = &notes else {
+                        buf.push_str("r ");
+                        continue;
+                    };
+                    let beat = notes.iter().map(|(string, elem_idx)| {
+                        let string = string + 1;
+                        match (&parsed.tick_stream[*elem_idx], idx > 0 || *tied) {
+                            (_, true) => format!("-.{string}"),
+                            (TabElement::Fret(fret), false) => {
+                                format!(
+                                    "{fret}.{string}{}",
+                                    note_effect(parsed, *elem_idx, measure_end)
+                                )
+                            }
+                            (_, false) => format!("x.{string}"),
+                        }
+                    });
+                    let beat = beat.collect::<Vec<_>>();
+                    match beat.as_slice() {
+                        [note] => write!(buf, "{note} ")?,
+                        notes => write!(buf, "({}) ", notes.join(" "))?,
+                    }
+                }
+            }
+            buf.push_str("|\n");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+//! Renders a tab to a WAV file with a plucked string synth, so it can be listened to without a
+//! MIDI player.
+//!
+//! Every tick is an eighth at the given tempo, and every string of every track is a voice of its
+//! own, tuned by the string names of its part. A note rings until the next note or rest on its
+//! string, like in [super::midi]. Hammer-ons and pull-offs change the pitch without picking the
+//! string again, slides and bends glide to the next fret during their tick, and `~` adds vibrato.
+
+pub mod synth;
+pub mod wav;
+
+use std::{ops::RangeInclusive, time::Instant};
+
+use synth::{Glide, Voice};
+
+use super::{
+    check_tempo,
+    errors::backend_error::BackendError,
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    parser::{parser::parse, parser::ParseResult},
+    time,
+};
+
+pub struct AudioBackend();
+
+#[derive(Clone, Debug)]
+pub struct AudioSettings {
+    /// In quarter notes per minute
+    pub tempo: u32,
+    /// In samples per second
+    pub sample_rate: u32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self { tempo: 80, sample_rate: 44100 }
+    }
+}
+
+impl AudioSettings {
+    /// Below this, the highest notes of a guitar can't be rendered
+    pub const SAMPLE_RATES: RangeInclusive<u32> = 8000..=192_000;
+
+    pub fn validate(&self) -> Result<(), BackendError> {
+        check_tempo(self.tempo)?;
+        if !Self::SAMPLE_RATES.contains(&self.sample_rate) {
+            let (min, max) = Self::SAMPLE_RATES.into_inner();
+            let message = format!("sample-rate should be from {min} to {max}");
+            return Err(BackendError::invalid_option(message));
+        }
+        Ok(())
+    }
+}
+
+impl Backend for AudioBackend {
+    type BackendSettings = AudioSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        if let Err(e) = settings.validate() {
+            return BackendResult::new(vec![], Some(e), None, None);
+        }
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let (voices, len) = voices(&parsed, &settings);
+        let samples = mix(&voices, len, settings.sample_rate);
+        let bytes = wav::write_wav(&samples, settings.sample_rate);
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), Some(gen_start.elapsed()));
+        if let Err(e) = out.write_all(&bytes) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("tempo", "In quarter notes per minute, from 1 to 1000, 80 by default"),
+        ("sample-rate", "In samples per second, from 8000 to 192000, 44100 by default"),
+    ];
+    registry.register(SelectorBackend::new(
+        "audio",
+        "Renders the tab to a WAV file",
+        &["wav"],
+        options,
+        |o| {
+            let default = AudioSettings::default();
+            let settings = AudioSettings {
+                tempo: o.get_or("tempo", default.tempo)?,
+                sample_rate: o.get_or("sample-rate", default.sample_rate)?,
+            };
+            settings.validate()?;
+            Ok(BackendSelector::Audio(settings))
+        },
+    ));
+}
+
+/// How long a dead note sounds, in seconds
+const DEAD_NOTE_LEN: f32 = 0.06;
+/// How long the pitch change of a hammer-on or pull-off takes, in seconds
+const HAMMER_LEN: f32 = 0.01;
+/// How long the last notes ring after the end of the tab, in seconds
+const TAIL: f32 = 1.0;
+/// The volume of a single voice, which leaves room for chords
+const GAIN: f32 = 0.25;
+
+/// The notes of every string of every track, and the length of the whole recording in samples
+pub fn voices(parsed: &ParseResult, settings: &AudioSettings) -> (Vec<Voice>, usize) {
+    let sample_rate = settings.sample_rate as u64;
+    let tick_start =
+        |tick: usize| (tick as u64 * sample_rate * 30 / settings.tempo as u64) as usize;
+    let seconds = |x: f32| (x * settings.sample_rate as f32) as usize;
+    let score = parsed.score();
+    let len = tick_start(score.tracks().map(|x| x.tick_count()).max().unwrap_or(0)) + seconds(TAIL);
+
+    let mut voices = vec![];
+    for track in score.tracks() {
+        let track_len = track.tick_count();
+        for note in track.sounding_notes() {
+            let start = tick_start(note.tick);
+            let mut voice = Voice::new(start, note.pitch as f32);
+            voice.len = match note.tick + note.len {
+                _ if note.dead => seconds(DEAD_NOTE_LEN).min(len - start),
+                // the last notes ring into the tail
+                end if end == track_len => len - start,
+                end => tick_start(end) - start,
+            };
+            voice.glides = note
+                .glides
+                .iter()
+                .map(|glide| Glide {
+                    start: tick_start(glide.tick) - start,
+                    len: match glide.len {
+                        0 => seconds(HAMMER_LEN),
+                        len => tick_start(glide.tick + len) - tick_start(glide.tick),
+                    },
+                    to: glide.to as f32,
+                })
+                .collect();
+            voice.vibrato = note.vibrato.map(|x| tick_start(x) - start);
+            voice.dead = note.dead;
+            voices.push(voice);
+        }
+    }
+    (voices, len)
+}
+
+/// Renders the voices and adds them up into `len` samples
+pub fn mix(voices: &[Voice], len: usize, sample_rate: u32) -> Vec<i16> {
+    let mut mix = vec![0f32; len];
+    for voice in voices {
+        for (sample, rendered) in mix[voice.start..].iter_mut().zip(voice.render(sample_rate)) {
+            *sample += rendered * GAIN;
+        }
+    }
+    mix.into_iter().map(|x| (x.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).collect()
+}
+
+#[test]
+fn test_audio() {
+    use super::errors::backend_error_kind::BackendErrorKind;
+    let input = std::fs::read_to_string("input/ties.tab").unwrap();
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let settings = AudioSettings { tempo: 120, sample_rate: 8000 };
+    let mut out = vec![];
+    let res = AudioBackend::process(&lines, &mut out, settings.clone());
+    assert!(res.err.is_none(), "{:?}", res.err);
+    // 16 ticks of 2000 samples and the tail, in 16 bits
+    let len = 16 * 2000 + 8000;
+    assert_eq!(&out[..4], b"RIFF");
+    assert_eq!(u32::from_le_bytes(out[4..8].try_into().unwrap()), 36 + len * 2);
+    assert_eq!(&out[8..16], b"WAVEfmt ");
+    // PCM, mono
+    assert_eq!(&out[20..24], &[1, 0, 1, 0]);
+    assert_eq!(u32::from_le_bytes(out[24..28].try_into().unwrap()), 8000);
+    assert_eq!(&out[36..40], b"data");
+    assert_eq!(u32::from_le_bytes(out[40..44].try_into().unwrap()), len * 2);
+    assert_eq!(out.len(), 44 + len as usize * 2);
+
+    for (tempo, sample_rate) in [(120, 0), (0, 8000), (1001, 8000)] {
+        let settings = AudioSettings { tempo, sample_rate };
+        let res = AudioBackend::process(&lines, &mut vec![], settings);
+        assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidOption(_)));
+    }
+}
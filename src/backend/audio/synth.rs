@@ -0,0 +1,140 @@
+//! A Karplus–Strong plucked string: a delay line of one period, filled with noise, whose samples
+//! are averaged with their neighbours on every pass. The delay is read with linear interpolation,
+//! so it can change while the string rings, which is how bends and slides glide.
+
+use std::f32::consts::TAU;
+
+/// How much of the string's energy is left after every period
+const DECAY: f32 = 0.996;
+/// How much is left for a dead note, which is muted right after the pick hits it
+const DEAD_DECAY: f32 = 0.5;
+/// The vibrato, in Hz and semitones
+const VIBRATO_RATE: f32 = 5.5;
+const VIBRATO_DEPTH: f32 = 0.3;
+/// The length of the fade at the end of a note, in seconds, so that it doesn't click
+const RELEASE: f32 = 0.02;
+
+/// A change of pitch while the string rings, in samples from the start of the note
+#[derive(Clone, Debug, PartialEq)]
+pub struct Glide {
+    pub start: usize,
+    pub len: usize,
+    /// In semitones, like MIDI keys
+    pub to: f32,
+}
+
+/// A note played on one string
+#[derive(Clone, Debug, PartialEq)]
+pub struct Voice {
+    /// The first sample of the note in the output
+    pub start: usize,
+    /// The number of samples the note sounds
+    pub len: usize,
+    /// In semitones, like MIDI keys
+    pub pitch: f32,
+    /// Sorted by their start
+    pub glides: Vec<Glide>,
+    /// The sample from the start of the note where the vibrato begins
+    pub vibrato: Option<usize>,
+    pub dead: bool,
+}
+
+impl Voice {
+    pub fn new(start: usize, pitch: f32) -> Self {
+        Self { start, len: 0, pitch, glides: vec![], vibrato: None, dead: false }
+    }
+
+    /// The pitch without vibrato at `n` samples from the start of the note
+    pub fn pitch_at(&self, n: usize) -> f32 {
+        let mut pitch = self.pitch;
+        for glide in self.glides.iter().take_while(|x| x.start <= n) {
+            let progress = match glide.len {
+                0 => 1.0,
+                len => ((n - glide.start) as f32 / len as f32).min(1.0),
+            };
+            pitch += (glide.to - pitch) * progress;
+        }
+        pitch
+    }
+
+    /// Renders the samples of the note. The noise that excites the string is seeded with the
+    /// start and pitch of the note, so the same tab always sounds the same.
+    pub fn render(&self, sample_rate: u32) -> Vec<f32> {
+        let sample_rate = sample_rate as f32;
+        let decay = if self.dead { DEAD_DECAY } else { DECAY };
+        let mut rng = Rng((self.start as u32 ^ self.pitch.to_bits()).wrapping_mul(2654435761) | 1);
+        let mut out: Vec<f32> = Vec::with_capacity(self.len);
+        for n in 0..self.len {
+            let mut pitch = self.pitch_at(n);
+            if let Some(from) = self.vibrato.filter(|x| *x <= n) {
+                let t = (n - from) as f32 / sample_rate;
+                pitch += VIBRATO_DEPTH * (TAU * VIBRATO_RATE * t).sin();
+            }
+            let frequency = 440.0 * 2f32.powf((pitch - 69.0) / 12.0);
+            // averaging two samples delays by another half sample. [interpolate] reads the sample
+            // after the position, so the period can't be shorter than 2 samples.
+            let period = (sample_rate / frequency - 0.5).max(2.0);
+            let pos = n as f32 - period;
+            let sample = match pos < 1.0 {
+                true => rng.next(),
+                false => decay * 0.5 * (interpolate(&out, pos) + interpolate(&out, pos - 1.0)),
+            };
+            out.push(sample);
+        }
+        let release = ((RELEASE * sample_rate) as usize).min(out.len());
+        let fade_start = out.len() - release;
+        for (idx, sample) in out[fade_start..].iter_mut().enumerate() {
+            *sample *= 1.0 - idx as f32 / release as f32;
+        }
+        out
+    }
+}
+
+/// The sample at a fractional position
+fn interpolate(samples: &[f32], pos: f32) -> f32 {
+    let idx = pos.floor() as usize;
+    let frac = pos - pos.floor();
+    samples[idx] * (1.0 - frac) + samples[idx + 1] * frac
+}
+
+/// A xorshift generator, which gives white noise between -1 and 1
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+#[test]
+fn test_voice() {
+    // the frequency of the samples, from the lag where they are most like themselves, between
+    // 98 and 147 Hz
+    let frequency = |samples: &[f32]| {
+        let correlation =
+            |lag: usize| samples.iter().zip(&samples[lag..]).map(|(a, b)| a * b).sum::<f32>();
+        let lag = (300..450).max_by(|a, b| correlation(*a).total_cmp(&correlation(*b))).unwrap();
+        44100.0 / lag as f32
+    };
+    let mut voice = Voice::new(0, 45.0);
+    voice.len = 44100;
+    let samples = voice.render(44100);
+    // the noise dies down into the fundamental of the A string, 110 Hz
+    let a = frequency(&samples[22050..]);
+    assert!((a - 110.0).abs() < 1.0, "{a}");
+    assert_eq!(voice.render(44100), samples);
+
+    // bent up a whole step
+    voice.glides.push(Glide { start: 4410, len: 4410, to: 47.0 });
+    assert_eq!(voice.pitch_at(6615), 46.0);
+    let b = frequency(&voice.render(44100)[22050..]);
+    assert!((b - 123.47).abs() < 1.2, "{b}");
+
+    // the 24th fret of the high e, with a period of less than a sample
+    let mut high = Voice::new(0, 88.0);
+    high.len = 1000;
+    assert_eq!(high.render(1000).len(), 1000);
+}
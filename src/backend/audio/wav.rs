@@ -0,0 +1,28 @@
+//! The RIFF container of an uncompressed WAV file
+
+/// Writes mono 16-bit PCM samples as the bytes of a .wav file
+pub fn write_wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend(b"RIFF");
+    bytes.extend((36 + data_len).to_le_bytes());
+    bytes.extend(b"WAVE");
+
+    bytes.extend(b"fmt ");
+    bytes.extend(16u32.to_le_bytes());
+    // PCM, in one channel
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(sample_rate.to_le_bytes());
+    // the bytes per second and per sample, and the bits per sample
+    bytes.extend((sample_rate * 2).to_le_bytes());
+    bytes.extend(2u16.to_le_bytes());
+    bytes.extend(16u16.to_le_bytes());
+
+    bytes.extend(b"data");
+    bytes.extend(data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend(sample.to_le_bytes());
+    }
+    bytes
+}
@@ -88,6 +88,48 @@ impl BackendError {
             kind: BackendErrorKind::FretTooLarge,
         }
     }
+    pub fn invalid_xml(line: usize, message: String) -> Self {
+        Self {
+            main_location: ErrorLocation::LineOnly(line),
+            relevant_lines: line..=line,
+            kind: BackendErrorKind::InvalidXml(message),
+        }
+    }
+    pub fn invalid_json(line: usize, message: String) -> Self {
+        Self {
+            main_location: ErrorLocation::LineOnly(line),
+            relevant_lines: line..=line,
+            kind: BackendErrorKind::InvalidJson(message),
+        }
+    }
+    pub fn not_formatted(line: usize) -> Self {
+        Self {
+            main_location: ErrorLocation::LineOnly(line),
+            relevant_lines: line..=line,
+            kind: BackendErrorKind::NotFormatted,
+        }
+    }
+    pub fn invalid_option(message: String) -> Self {
+        Self {
+            main_location: ErrorLocation::NoLocation,
+            relevant_lines: 0..=0,
+            kind: BackendErrorKind::InvalidOption(message),
+        }
+    }
+    pub fn unsupported(message: String) -> Self {
+        Self {
+            main_location: ErrorLocation::NoLocation,
+            relevant_lines: 0..=0,
+            kind: BackendErrorKind::Unsupported(message),
+        }
+    }
+    pub fn invalid_midi(message: String) -> Self {
+        Self {
+            main_location: ErrorLocation::NoLocation,
+            relevant_lines: 0..=0,
+            kind: BackendErrorKind::InvalidMidi(message),
+        }
+    }
 }
 
 impl From<std::io::Error> for BackendError {
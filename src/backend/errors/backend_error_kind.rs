@@ -11,6 +11,13 @@ pub enum BackendErrorKind {
     BothSlotsMultiChar,
     MultiBothSlotsFilled,
     FretTooLarge,
+    InvalidXml(String),
+    InvalidMidi(String),
+    InvalidJson(String),
+    InvalidOption(String),
+    /// The output format can't hold something in the tab
+    Unsupported(String),
+    NotFormatted,
 }
 
 impl BackendErrorKind {
@@ -32,7 +39,7 @@ impl BackendErrorKind {
             BackendErrorKind::Parse3InvalidCharacter(c) => {
                 ("Invalid character".into(), match c {
                     Some(c) => format!("The character {c} is not valid here."),
-                    None => format!("This character is not valid here."),
+                    None => "This character is not valid here.".to_string(),
                 })
             }
             BackendErrorKind::FixupFailed => (
@@ -62,6 +69,12 @@ In this position, you may have:
  - another multichar element of the same cardinality"#.into()
             ), 
             BackendErrorKind::FretTooLarge => ("Too large fret".to_string(), "The maximum allowed fret is 99.".to_string()),
+            BackendErrorKind::InvalidXml(message) => ("Invalid XML".into(), format!("This file can't be read as MusicXML: {message}")),
+            BackendErrorKind::InvalidMidi(message) => ("Invalid MIDI".into(), format!("This file can't be read as a MIDI file: {message}")),
+            BackendErrorKind::InvalidJson(message) => ("Invalid JSON".into(), format!("This file can't be read as a scoreman JSON file: {message}")),
+            BackendErrorKind::InvalidOption(message) => ("Invalid option".into(), format!("The options given to the backend can't be used: {message}")),
+            BackendErrorKind::Unsupported(message) => ("Unsupported by the output format".into(), format!("The output format can't hold this tab: {message}")),
+            BackendErrorKind::NotFormatted => ("Not formatted".into(), "This line differs from the formatted file. Run the fmt backend without --check to fix it.".into()),
         }
     }
 }
@@ -6,6 +6,11 @@ pub enum DiagnosticKind {
     CommentInPart,
     FormatAddedBarline,
     FormatReplacedInvalid,
+    MeasureLongerThanMeter,
+    MeasurePaddedToMeter,
+    NoStringForNote,
+    NoteQuantized,
+    TuningChangedInTrack,
 }
 
 impl Display for DiagnosticKind {
@@ -23,6 +28,30 @@ impl Display for DiagnosticKind {
             DiagnosticKind::FormatReplacedInvalid => {
                 write!(f, "This character is invalid, so I replaced it with a rest (`-`).")
             }
+            DiagnosticKind::MeasureLongerThanMeter => {
+                write!(
+                    f,
+                    "This measure is longer than the meter, so it gets its own time signature."
+                )
+            }
+            DiagnosticKind::MeasurePaddedToMeter => {
+                write!(f, "This measure is shorter than the meter, so I filled it up with rests.")
+            }
+            DiagnosticKind::NoStringForNote => {
+                write!(f, "There is no free string this note could be played on, so I left it out.")
+            }
+            DiagnosticKind::NoteQuantized => {
+                write!(
+                    f,
+                    "This note doesn't start on an eighth note, so I moved it to the closest one."
+                )
+            }
+            DiagnosticKind::TuningChangedInTrack => {
+                write!(
+                    f,
+                    "This Part is tuned differently from the first one of its track, which is the only tuning I can write."
+                )
+            }
         }
     }
 }
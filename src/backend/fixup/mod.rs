@@ -1,8 +1,11 @@
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use clap::ValueEnum;
 
-use super::BackendResult;
+use super::{
+    registry::{Registry, SelectorBackend},
+    BackendResult, BackendSelector,
+};
 use crate::{
     backend::{
         errors::{
@@ -70,7 +73,7 @@ impl Backend for FixupBackend {
         let mut diagnostics = vec![];
         // TODO: figure out a way not to clone these
         let mut parser_input = parser_input.to_owned();
-        let mut parse_time = Duration::from_secs(0);
+        let mut parse_time;
         let fixup_start = Instant::now();
         let mut location_tracker = LocationTracker::new();
         loop {
@@ -92,7 +95,14 @@ impl Backend for FixupBackend {
                         );
                     }
                     match err.kind {
-                        BackendErrorKind::IOError(_) | BackendErrorKind::FmtError(_) => {
+                        BackendErrorKind::IOError(_)
+                        | BackendErrorKind::FmtError(_)
+                        | BackendErrorKind::InvalidXml(_)
+                        | BackendErrorKind::InvalidMidi(_)
+                        | BackendErrorKind::InvalidJson(_)
+                        | BackendErrorKind::InvalidOption(_)
+                        | BackendErrorKind::Unsupported(_)
+                        | BackendErrorKind::NotFormatted => {
                             return BackendResult::new(
                                 diagnostics,
                                 parsed.error,
@@ -134,7 +144,7 @@ impl Backend for FixupBackend {
                             ))
                         }
                         BackendErrorKind::FixupFailed => unreachable!(),
-                        BackendErrorKind::Parse3InvalidCharacter(x) => {
+                        BackendErrorKind::Parse3InvalidCharacter(_) => {
                             let Some((line_idx, char_idx)) = err
                                 .main_location
                                 .get_line_idx()
@@ -158,3 +168,13 @@ impl Backend for FixupBackend {
         BackendResult::new(diagnostics, maybe_io_err, Some(parse_time), Some(gen_time))
     }
 }
+
+pub(crate) fn register(registry: &mut Registry) {
+    registry.register(SelectorBackend::new(
+        "fixup",
+        "Tries to fix errors in a tab, until it can be parsed",
+        &[],
+        &[("dump", "Dump the parse tree: tick-stream or pretty-tracks")],
+        |o| Ok(BackendSelector::Fixup(FixupBackendSettings { dump: o.choice("dump")? })),
+    ));
+}
@@ -0,0 +1,123 @@
+use crate::backend::errors::backend_error_kind::BackendErrorKind;
+use crate::backend::format::{FormatBackend, FormatSettings};
+use crate::backend::Backend;
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(|x| x.to_string()).collect()
+}
+
+fn format(input: &str, settings: FormatSettings) -> String {
+    let mut out = vec![];
+    let res = FormatBackend::process(&lines(input), &mut out, settings);
+    assert!(res.err.is_none(), "{:?}", res.err);
+    String::from_utf8(out).unwrap()
+}
+
+const MESSY: &str = "
+
+My Song   
+  // intro, played twice
+e|--12---|-------|
+B|---3---|-0-----|   
+G|-------|-------|
+D|-------|-------|
+A|3------|-------|
+E|-------|-------|
+
+
+
+e|-0-|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+// outro
+e|-5-|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+
+";
+
+#[test]
+fn test_format() {
+    let formatted = format(MESSY, FormatSettings::default());
+    // the 3 which was merged into the multichar tick is moved into its column
+    assert_eq!(
+        formatted,
+        "My Song
+  // intro, played twice
+e|--12---|-------|
+B|--3----|-0-----|
+G|-------|-------|
+D|-------|-------|
+A|3------|-------|
+E|-------|-------|
+
+e|-0-|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+// outro
+e|-5-|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+"
+    );
+    assert_eq!(format(&formatted, FormatSettings::default()), formatted);
+
+    for path in ["input/c_major.tab", "input/multi_track.tab", "input/ties.tab", "input/bends.tab"]
+    {
+        let tab = std::fs::read_to_string(path).unwrap();
+        assert_eq!(format(&tab, FormatSettings::default()), tab, "{path}");
+    }
+}
+
+#[test]
+fn test_format_rewrap() {
+    let settings = FormatSettings { measures_per_line: Some(3), ..Default::default() };
+    let formatted = format(MESSY, settings);
+    // the Part after the comment is not joined with the others
+    assert_eq!(
+        formatted.lines().filter(|x| x.starts_with("e|")).collect::<Vec<_>>(),
+        ["e|--12---|-------|-0-|", "e|-5-|"]
+    );
+
+    let settings = FormatSettings { max_width: Some(18), ..Default::default() };
+    let formatted = format(MESSY, settings);
+    assert_eq!(
+        formatted.lines().filter(|x| x.starts_with("e|")).collect::<Vec<_>>(),
+        ["e|--12---|-------|", "e|-0-|", "e|-5-|"]
+    );
+    // a measure that doesn't fit on its own is not split
+    let settings =
+        FormatSettings { max_width: Some(4), measures_per_line: Some(2), ..Default::default() };
+    let formatted = format(MESSY, settings);
+    assert_eq!(
+        formatted.lines().filter(|x| x.starts_with("e|")).collect::<Vec<_>>(),
+        ["e|--12---|", "e|-------|", "e|-0-|", "e|-5-|"]
+    );
+}
+
+#[test]
+fn test_format_check() {
+    let settings = FormatSettings { check: true, ..Default::default() };
+    let mut out = vec![];
+    let res = FormatBackend::process(&lines(MESSY), &mut out, settings.clone());
+    let err = res.err.unwrap();
+    assert!(matches!(err.kind, BackendErrorKind::NotFormatted));
+    assert_eq!(err.main_location.get_line_idx(), Some(0));
+    assert!(out.is_empty());
+
+    let formatted = format(MESSY, FormatSettings::default());
+    let res = FormatBackend::process(&lines(&formatted), &mut out, settings);
+    assert!(res.err.is_none());
+}
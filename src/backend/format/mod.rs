@@ -0,0 +1,214 @@
+//! Rewrites a tab in a canonical layout, like rustfmt does for Rust code.
+//!
+//! The ticks are read with [parse] and written back with [crate::tab_writer], so every column is
+//! as wide as its widest element. Everything that is not a Part, like comments, headers and
+//! `Track:` lines, is kept where it was, with trailing whitespace removed and runs of blank
+//! lines collapsed. Parts which are only separated by blank lines can be re-wrapped to a fixed
+//! number of measures or characters per line.
+
+#[cfg(test)]
+mod format_tests;
+
+use std::time::Instant;
+
+use super::{
+    errors::{backend_error::BackendError, diagnostic::Diagnostic},
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    parser::parser::{parse, ParseResult},
+    tab_writer::{wrap, write_part, TabWriterSettings},
+    time, traceln,
+};
+
+pub struct FormatBackend();
+
+#[derive(Clone, Debug, Default)]
+pub struct FormatSettings {
+    /// Re-wrap the measures into Parts of this many measures. Parts are kept as they are if not
+    /// set.
+    pub measures_per_line: Option<usize>,
+    /// Re-wrap the measures into Parts of at most this many characters per line. Can be combined
+    /// with [FormatSettings::measures_per_line].
+    pub max_width: Option<usize>,
+    /// Don't write anything, only fail with [super::errors::backend_error_kind::BackendErrorKind::NotFormatted]
+    /// on the first line which would change
+    pub check: bool,
+}
+
+impl Backend for FormatBackend {
+    type BackendSettings = FormatSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let diagnostics = vec![];
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(diagnostics, Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut formatted = String::new();
+        let mut r = BackendResult::new(diagnostics, None, Some(parse_time), None);
+        if let Err(e) = format(input, &parsed, &mut formatted, &settings) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if settings.check {
+            let mut formatted_lines = formatted.lines();
+            let first_diff = (0..input.len().max(formatted.lines().count()))
+                .find(|idx| input.get(*idx).map(|x| x.as_str()) != formatted_lines.next());
+            if let Some(line) = first_diff {
+                // a missing line at the end is reported on the last one
+                r.err = Some(BackendError::not_formatted(line.min(input.len().saturating_sub(1))));
+            }
+            return r;
+        }
+        if let Err(e) = out.write_all(formatted.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("measures-per-line", "Re-wrap the measures into Parts of this many measures"),
+        ("width", "Re-wrap the measures into Parts of at most this many characters per line"),
+        ("check", "Only fail if the tab differs from the formatted one"),
+    ];
+    registry.register(SelectorBackend::new(
+        "fmt",
+        "Rewrites a tab in a canonical layout",
+        &[],
+        options,
+        |o| {
+            Ok(BackendSelector::Format(FormatSettings {
+                measures_per_line: o.get("measures-per-line")?,
+                max_width: o.get("width")?,
+                check: o.flag("check")?,
+            }))
+        },
+    ));
+}
+
+/// A Part of the source, with the measures it contains
+struct SourcePart {
+    first_line: usize,
+    measures: std::ops::Range<u32>,
+    names: [char; 6],
+}
+
+fn source_parts(parsed: &ParseResult) -> Vec<SourcePart> {
+    let mut measure_start = 0;
+    parsed
+        .offsets
+        .iter()
+        .enumerate()
+        .map(|(part_idx, (first_line, _))| {
+            // the measures of a Part are the ones before the tick stream of the next Part
+            let stream_end = parsed.offsets.get(part_idx + 1).map_or(u32::MAX, |x| x.1);
+            let measure_end =
+                parsed.measures.partition_point(|x| *x.data_range.start() < stream_end) as u32;
+            let measures = measure_start..measure_end;
+            measure_start = measures.end;
+            let names = std::array::from_fn(|s| parsed.base_notes[part_idx * 6 + s]);
+            SourcePart { first_line: *first_line as usize, measures, names }
+        })
+        .collect()
+}
+
+/// Writes the lines which are not part of a Part, like comments
+fn write_verbatim(lines: &[String], buf: &mut String) {
+    let mut last_blank = buf.is_empty() || buf.ends_with("\n\n");
+    for line in lines.iter().map(|x| x.trim_end()) {
+        if line.is_empty() && last_blank {
+            continue;
+        }
+        last_blank = line.is_empty();
+        buf.push_str(line);
+        buf.push('\n');
+    }
+}
+
+/// Writes the formatted version of `input`, which was parsed into `parsed`, into `buf`
+pub fn format(
+    input: &[String], parsed: &ParseResult, buf: &mut String, settings: &FormatSettings,
+) -> std::fmt::Result {
+    let parts = source_parts(parsed);
+    let mut next_line = 0;
+    let mut part_idx = 0;
+    while part_idx < parts.len() {
+        write_verbatim(&input[next_line..parts[part_idx].first_line], buf);
+        // a run of Parts that are only separated by blank lines, and can be re-wrapped together
+        let mut run_end = part_idx + 1;
+        while run_end < parts.len()
+            && parts[run_end].names == parts[part_idx].names
+            && input[parts[run_end - 1].first_line + 6..parts[run_end].first_line]
+                .iter()
+                .all(|x| x.trim().is_empty())
+        {
+            run_end += 1;
+        }
+        traceln!("fmt: writing Parts {part_idx}..{run_end} together");
+        let run = &parts[part_idx..run_end];
+        let measures = run.iter().flat_map(|x| x.measures.clone()).collect::<Vec<_>>();
+        let lines = match (settings.measures_per_line, settings.max_width) {
+            (None, None) => {
+                let mut rest = measures.as_slice();
+                let lines = run.iter().map(|x| {
+                    let (line, after) = rest.split_at(x.measures.len());
+                    rest = after;
+                    line
+                });
+                lines.collect()
+            }
+            (measures_per_line, max_width) => {
+                let measures_per_line = measures_per_line.unwrap_or(usize::MAX);
+                wrap(parsed, &measures, &TabWriterSettings { measures_per_line, max_width })
+            }
+        };
+        for (line_idx, measures) in lines.iter().enumerate() {
+            if line_idx > 0 {
+                buf.push('\n');
+            }
+            write_part(parsed, buf, measures, run[0].names)?;
+        }
+        next_line = run[run.len() - 1].first_line + 6;
+        part_idx = run_end;
+    }
+    write_verbatim(&input[next_line..], buf);
+    while buf.ends_with("\n\n") {
+        buf.pop();
+    }
+    Ok(())
+}
+
+/// Parses the tab, changes it with `transform`, and writes it with the layout and the comments
+/// of the input. `header` is written before everything else.
+pub(crate) fn process_rewrite<Out: std::io::Write>(
+    input: &[String], out: &mut Out, header: Option<&str>,
+    transform: impl FnOnce(&mut ParseResult, &mut Vec<Diagnostic>),
+) -> BackendResult {
+    let mut diagnostics = vec![];
+    let (parse_time, mut parsed) = time(|| parse(input));
+    if let Some(e) = parsed.error.take() {
+        return BackendResult::new(diagnostics, Some(e), Some(parse_time), None);
+    }
+    let gen_start = Instant::now();
+    transform(&mut parsed, &mut diagnostics);
+    let mut tab = String::new();
+    let mut r = BackendResult::new(diagnostics, None, Some(parse_time), None);
+    if let Err(e) = format(input, &parsed, &mut tab, &FormatSettings::default()) {
+        r.err = Some(e.into());
+        return r;
+    }
+    r.timing_gen = Some(gen_start.elapsed());
+    let header = header.unwrap_or_default().as_bytes();
+    if let Err(e) = out.write_all(header).and_then(|_| out.write_all(tab.as_bytes())) {
+        r.err = Some(e.into());
+    }
+    r
+}
@@ -0,0 +1,226 @@
+use crate::backend::gp5::{
+    reader::read_song, song, writer::write_song, Beat, Gp5Backend, Gp5Settings, Note, NoteEffects,
+    NoteKind,
+};
+use crate::backend::{
+    errors::{backend_error_kind::BackendErrorKind, error_location::ErrorLocation},
+    Backend,
+};
+use crate::fingering::Tuning;
+use crate::parser::parser::parse;
+
+fn lines(input: &str) -> Vec<String> {
+    input.lines().map(|x| x.to_string()).collect()
+}
+
+fn note(string: u8, fret: u8, kind: NoteKind, effects: NoteEffects) -> Note {
+    Note { string, fret, kind, effects }
+}
+
+fn rest(len: u32) -> Beat {
+    Beat { len, notes: vec![] }
+}
+
+#[test]
+fn test_gp5_backend() {
+    let input = lines(
+        "
+e|-----------|--------|
+B|-----------|--------|
+G|---------7~|~~------|
+D|-5h7-5/7---|--------|
+A|-----------|-x------|
+E|-0---------|--------|",
+    );
+    let mut out = vec![];
+    let settings = Gp5Settings { title: Some("Riff".to_string()), tempo: 120 };
+    let res = Gp5Backend::process(&input, &mut out, settings);
+    assert!(res.err.is_none(), "{:?}", res.err);
+    assert_eq!(&out[1..25], b"FICHIER GUITAR PRO v5.00");
+
+    let song = read_song(&out).unwrap();
+    assert_eq!(song.title, "Riff");
+    assert_eq!(song.tempo, 120);
+    assert_eq!(song.measures, vec![11, 8]);
+    assert_eq!(song.tracks.len(), 1);
+    let track = &song.tracks[0];
+    assert_eq!(track.name, "Guitar1");
+    assert_eq!(track.tuning, Tuning::STANDARD);
+    assert_eq!(track.program, 25);
+
+    let plain = NoteEffects::default;
+    let hammer = NoteEffects { hammer: true, ..Default::default() };
+    let slide = NoteEffects { slide: true, ..Default::default() };
+    let vibrato = NoteEffects { vibrato: true, ..Default::default() };
+    assert_eq!(
+        track.measures[0],
+        vec![
+            rest(1),
+            Beat {
+                len: 2,
+                notes: vec![
+                    note(4, 5, NoteKind::Normal, hammer),
+                    note(6, 0, NoteKind::Normal, plain()),
+                ],
+            },
+            Beat { len: 1, notes: vec![note(4, 7, NoteKind::Normal, plain())] },
+            rest(1),
+            Beat { len: 2, notes: vec![note(4, 5, NoteKind::Normal, slide)] },
+            Beat { len: 1, notes: vec![note(4, 7, NoteKind::Normal, plain())] },
+            rest(1),
+            Beat { len: 2, notes: vec![note(3, 7, NoteKind::Normal, vibrato.clone())] },
+        ]
+    );
+    // the held note is tied over the barline
+    assert_eq!(
+        track.measures[1],
+        vec![
+            Beat { len: 1, notes: vec![note(3, 7, NoteKind::Tie, vibrato)] },
+            Beat { len: 1, notes: vec![note(5, 0, NoteKind::Dead, plain())] },
+            rest(6),
+        ]
+    );
+}
+
+#[test]
+fn test_gp5_round_trip() {
+    let input = lines(
+        "
+Track: Lead
+e|-------------|
+B|-5b7--8r6----|
+G|-------------|
+D|-------------|
+A|-------------|
+E|-------------|
+
+Track: Bass program=33
+D|-0-----------|-3--|
+A|-------------|----|
+F|-------------|----|
+C|-3~~~~~~~~~~~|----|
+G|-------------|----|
+D|-------------|----|",
+    );
+    let parsed = parse(&input);
+    assert!(parsed.error.is_none(), "{:?}", parsed.error);
+    let song = song(&parsed, &Gp5Settings::default(), &mut vec![]);
+    assert_eq!(song.tempo, 80);
+    assert_eq!(song.measures, vec![13, 4]);
+    let lead = &song.tracks[0];
+    let bend = |x| NoteEffects { bend: Some(x), ..Default::default() };
+    assert_eq!(lead.measures[0][1].notes, vec![note(2, 5, NoteKind::Normal, bend(2))]);
+    assert_eq!(lead.measures[0][4].notes, vec![note(2, 8, NoteKind::Normal, bend(-2))]);
+    // the lead is filled up with a rest where the bass goes on
+    assert_eq!(lead.measures[1], vec![rest(4)]);
+    let bass = &song.tracks[1];
+    assert_eq!(bass.program, 33);
+    assert_eq!(bass.tuning.strings, [62, 57, 53, 48, 43, 38]);
+    // the held chord is split into a whole and a tied half
+    assert_eq!(bass.measures[0][1].len, 8);
+    assert_eq!(bass.measures[0][2].len, 4);
+    assert!(bass.measures[0][2].notes.iter().all(|x| x.kind == NoteKind::Tie));
+
+    let bytes = write_song(&song).unwrap();
+    // a bend goes up to a whole tone, and a release comes down from it, without negative points
+    let bend_bytes = |kind: u8, points: [i32; 3]| {
+        let mut expected = vec![kind];
+        expected.extend(100i32.to_le_bytes());
+        expected.extend(3i32.to_le_bytes());
+        for (position, value) in [0i32, 30, 60].into_iter().zip(points) {
+            expected.extend(position.to_le_bytes());
+            expected.extend(value.to_le_bytes());
+            expected.push(0);
+        }
+        expected
+    };
+    let contains = |expected: Vec<u8>| bytes.windows(expected.len()).any(|x| x == expected);
+    assert!(contains(bend_bytes(1, [0, 100, 100])));
+    assert!(contains(bend_bytes(5, [100, 0, 0])));
+    assert_eq!(read_song(&bytes), Ok(song));
+}
+
+#[test]
+fn test_gp5_measure_lengths() {
+    let input = lines(
+        "
+Track: Lead
+e|-0-|-------|
+B|---|-------|
+G|---|-------|
+D|---|-------|
+A|---|-------|
+E|---|-------|
+
+Track: Rhythm
+e|-----|---|-0-|
+B|-----|---|---|
+G|-----|---|---|
+D|-----|---|---|
+A|-----|---|---|
+E|---3-|-0-|---|
+
+Track: Lead
+D|-0-|
+A|---|
+F|---|
+C|---|
+G|---|
+D|---|",
+    );
+    let mut out = vec![];
+    let res = Gp5Backend::process(&input, &mut out, Gp5Settings::default());
+    assert!(res.err.is_none(), "{:?}", res.err);
+    let song = read_song(&out).unwrap();
+    // every measure is as long as the longest track at its index
+    assert_eq!(song.measures, vec![5, 7, 3]);
+    for track in &song.tracks {
+        let lens = track.measures.iter().map(|x| x.iter().map(|x| x.len).sum::<u32>());
+        assert_eq!(lens.collect::<Vec<_>>(), song.measures, "{}", track.name);
+    }
+    let lead = &song.tracks[0];
+    let open = Beat { len: 1, notes: vec![note(1, 0, NoteKind::Normal, NoteEffects::default())] };
+    assert_eq!(lead.measures[0], vec![rest(1), open.clone(), rest(1), rest(2)]);
+    assert_eq!(lead.measures[2], vec![rest(1), open, rest(1)]);
+
+    // the lead keeps the tuning of its first Part, and its last Part is reported
+    assert_eq!(lead.tuning, Tuning::STANDARD);
+    assert_eq!(res.diagnostics.len(), 1);
+    assert_eq!(res.diagnostics[0].location, ErrorLocation::LineOnly(18));
+}
+
+#[test]
+fn test_gp5_long_measure() {
+    let input = ["e|", "B|", "G|", "D|", "A|", "E|"].map(|x| format!("{x}-{}|", "-".repeat(127)));
+    let res = Gp5Backend::process(&input, &mut vec![], Gp5Settings::default());
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::Unsupported(_)));
+}
+
+#[test]
+fn test_gp5_tempo_out_of_range() {
+    let input = lines("e|-0-|");
+    for tempo in [0, 1001, u32::MAX] {
+        let settings = Gp5Settings { tempo, ..Default::default() };
+        let res = Gp5Backend::process(&input, &mut vec![], settings);
+        assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidOption(_)));
+    }
+}
+
+#[test]
+fn test_gp5_reader_errors() {
+    let input = lines(
+        "
+e|---|
+B|---|
+G|---|
+D|-5-|
+A|---|
+E|---|",
+    );
+    let song = song(&parse(&input), &Gp5Settings::default(), &mut vec![]);
+    let bytes = write_song(&song).unwrap();
+    assert!(read_song(&bytes[..bytes.len() - 3]).unwrap_err().contains("unexpected end of file"));
+    let mut other_version = bytes.clone();
+    other_version[24] = b'1';
+    assert!(read_song(&other_version).unwrap_err().contains("unsupported version"));
+}
@@ -0,0 +1,282 @@
+//! Writes a Guitar Pro 5 (.gp5) file, which Guitar Pro and TuxGuitar can open.
+//!
+//! The tab is first turned into a [Song], which holds only what a tab can express, and then
+//! written by [writer::write_song]. [reader::read_song] reads the same subset of the format back.
+//!
+//! The rhythm is read like in [super::lilypond]: every tick is an eighth and a note rings through
+//! the `~` and technique ticks after it. A note held with `~` gets a vibrato. Longer notes are
+//! split into tied ones, and hammer-ons, pull-offs, slides and bends are put on the last of them.
+//! The tab format has no harmonics, so none are written.
+
+pub mod reader;
+pub mod writer;
+
+#[cfg(test)]
+mod gp5_tests;
+
+use std::{ops::Range, time::Instant};
+
+use itertools::Itertools;
+
+use super::{
+    check_tempo,
+    errors::{
+        backend_error::BackendError, diagnostic::Diagnostic, diagnostic_kind::DiagnosticKind,
+        error_location::ErrorLocation,
+    },
+    lilypond::{note_lengths, track_events},
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    fingering::Tuning,
+    parser::{parser::parse, parser::ParseResult, score, tab_element::TabElement},
+    time,
+};
+
+pub struct Gp5Backend();
+
+#[derive(Clone, Debug)]
+pub struct Gp5Settings {
+    /// The title of the song. Left empty if not set.
+    pub title: Option<String>,
+    /// In quarter notes per minute
+    pub tempo: u32,
+}
+
+impl Default for Gp5Settings {
+    fn default() -> Self {
+        Self { title: None, tempo: 80 }
+    }
+}
+
+impl Gp5Settings {
+    pub fn validate(&self) -> Result<(), BackendError> {
+        check_tempo(self.tempo)
+    }
+}
+
+impl Backend for Gp5Backend {
+    type BackendSettings = Gp5Settings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        if let Err(e) = settings.validate() {
+            return BackendResult::new(vec![], Some(e), None, None);
+        }
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut diagnostics = vec![];
+        let bytes = match writer::write_song(&song(&parsed, &settings, &mut diagnostics)) {
+            Ok(x) => x,
+            Err(message) => {
+                let err = BackendError::unsupported(message);
+                return BackendResult::new(diagnostics, Some(err), Some(parse_time), None);
+            }
+        };
+        let gen_time = Some(gen_start.elapsed());
+        let mut r = BackendResult::new(diagnostics, None, Some(parse_time), gen_time);
+        if let Err(e) = out.write_all(&bytes) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("title", "The title of the song"),
+        ("tempo", "In quarter notes per minute, from 1 to 1000, 80 by default"),
+    ];
+    registry.register(SelectorBackend::new(
+        "gp5",
+        "Writes a Guitar Pro 5 file",
+        &["gp5"],
+        options,
+        |o| {
+            let default = Gp5Settings::default();
+            let settings =
+                Gp5Settings { title: o.get("title")?, tempo: o.get_or("tempo", default.tempo)? };
+            settings.validate()?;
+            Ok(BackendSelector::Gp5(settings))
+        },
+    ));
+}
+
+/// A song as it is stored in a GP5 file, reduced to what a tab can express
+#[derive(Clone, Debug, PartialEq)]
+pub struct Song {
+    pub title: String,
+    /// In quarter notes per minute
+    pub tempo: u32,
+    /// The length of every measure in eighths, which all tracks share
+    pub measures: Vec<u32>,
+    pub tracks: Vec<GpTrack>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpTrack {
+    pub name: String,
+    pub tuning: Tuning,
+    /// The General MIDI program
+    pub program: u8,
+    /// The beats of every measure
+    pub measures: Vec<Vec<Beat>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Beat {
+    /// In eighths, one of the [note_lengths]
+    pub len: u32,
+    /// Empty for a rest
+    pub notes: Vec<Note>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Note {
+    /// Counting from 1, the highest string first
+    pub string: u8,
+    pub fret: u8,
+    pub kind: NoteKind,
+    pub effects: NoteEffects,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteKind {
+    Normal = 1,
+    /// Continues the note on the same string in the previous beat
+    Tie = 2,
+    Dead = 3,
+}
+
+/// The effects of a note. Hammer-ons, slides and bends lead to the next note on its string.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NoteEffects {
+    /// In semitones, negative for a release
+    pub bend: Option<i16>,
+    /// A hammer-on or a pull-off, which are the same in Guitar Pro
+    pub hammer: bool,
+    pub slide: bool,
+    pub vibrato: bool,
+}
+
+/// The note on `string` of the tick at `tick`, in a beat of the event whose ticks are `span`.
+/// `techniques` is set for the last beat of an event, which leads to the next note.
+fn note(
+    parsed: &ParseResult, tick: usize, string: usize, span: &Range<usize>, measure_end: usize,
+    tie: bool, techniques: bool,
+) -> Option<Note> {
+    let idx = tick + string;
+    let (fret, kind) = match parsed.tick_stream[idx] {
+        TabElement::Fret(fret) if tie => (fret, NoteKind::Tie),
+        TabElement::Fret(fret) => (fret, NoteKind::Normal),
+        TabElement::DeadNote => (0, NoteKind::Dead),
+        _ => return None,
+    };
+    let vibrato =
+        span.clone().step_by(6).any(|x| parsed.tick_stream[x + string] == TabElement::Vibrato);
+    let mut effects = NoteEffects { vibrato, ..Default::default() };
+    if techniques && kind != NoteKind::Dead {
+        let at = |idx: usize| parsed.tick_stream.get(idx).filter(|_| idx < measure_end);
+        let target = match at(idx + 12) {
+            Some(TabElement::Fret(x)) => Some(*x as i16 - fret as i16),
+            _ => None,
+        };
+        match at(idx + 6) {
+            Some(TabElement::HammerOn | TabElement::Pull) => effects.hammer = target.is_some(),
+            Some(TabElement::Slide) => effects.slide = true,
+            Some(TabElement::Bend) => effects.bend = Some(target.unwrap_or(2)),
+            Some(TabElement::Release) => effects.bend = Some(target.unwrap_or(-2)),
+            _ => (),
+        }
+    }
+    Some(Note { string: string as u8 + 1, fret, kind, effects })
+}
+
+/// The tuning of the first Part of `track`. A GP5 track has only one tuning, so the later Parts
+/// which are tuned differently get a diagnostic.
+fn track_tuning(track: score::Track, diagnostics: &mut Vec<Diagnostic>) -> Tuning {
+    let mut parts = track.measures().map(|x| x.part()).dedup_by(|a, b| a.index() == b.index());
+    let Some(first) = parts.next() else {
+        return Tuning::default();
+    };
+    let tuning = first.tuning();
+    for part in parts.filter(|x| x.tuning() != tuning) {
+        let location = ErrorLocation::LineOnly(part.line());
+        diagnostics.push(Diagnostic::warn(location, DiagnosticKind::TuningChangedInTrack));
+    }
+    tuning
+}
+
+/// Converts a parsed tab into a [Song]. Every measure is as long as the longest of the tracks
+/// at its index, and the shorter ones, as well as tracks with fewer measures than the others,
+/// are filled up with rests.
+pub fn song(
+    parsed: &ParseResult, settings: &Gp5Settings, diagnostics: &mut Vec<Diagnostic>,
+) -> Song {
+    let score = parsed.score();
+    let mut measures: Vec<u32> = vec![];
+    for track in score.tracks() {
+        for (measure_number, measure) in track.measures().enumerate() {
+            let len = measure.tick_count() as u32;
+            match measures.get_mut(measure_number) {
+                Some(x) => *x = (*x).max(len),
+                None => measures.push(len),
+            }
+        }
+    }
+    let mut tracks = vec![];
+    for (track, view) in parsed.tracks.iter().zip(score.tracks()) {
+        let tuning = track_tuning(view, diagnostics);
+        let mut beats = vec![];
+        for (measure_number, events) in track_events(parsed, track).iter().enumerate() {
+            let range = &parsed.measures[track.measures[measure_number] as usize].data_range;
+            let measure_start = *range.start() as usize;
+            let measure_end = *range.end() as usize + 1;
+            let mut measure_beats = vec![];
+            let mut start = measure_start;
+            for event in events {
+                let span = start..start + 6 * event.len as usize;
+                start = span.end;
+                let lengths = note_lengths(event.len);
+                let count = lengths.len();
+                for (idx, len) in lengths.into_iter().enumerate() {
+                    let notes = event.tick.map(|tick| {
+                        let tie = idx > 0 || event.tied;
+                        let techniques = idx + 1 == count && !event.tied;
+                        (0..6)
+                            .filter_map(|string| {
+                                note(parsed, tick, string, &span, measure_end, tie, techniques)
+                            })
+                            .collect()
+                    });
+                    measure_beats.push(Beat { len, notes: notes.unwrap_or_default() });
+                }
+            }
+            beats.push(measure_beats);
+        }
+        tracks.push(GpTrack {
+            name: track.name.clone(),
+            tuning,
+            program: track.midi_program(),
+            measures: beats,
+        });
+    }
+    for track in &mut tracks {
+        track.measures.resize(measures.len(), vec![]);
+        for (beats, len) in track.measures.iter_mut().zip(&measures) {
+            let missing = len - beats.iter().map(|x| x.len).sum::<u32>();
+            beats.extend(note_lengths(missing).into_iter().map(|len| Beat { len, notes: vec![] }));
+        }
+    }
+    Song {
+        title: settings.title.clone().unwrap_or_default(),
+        tempo: settings.tempo,
+        measures,
+        tracks,
+    }
+}
@@ -0,0 +1,332 @@
+//! Reads the subset of version 5.00 files that [super::writer] writes. Things a tab can't express,
+//! like chord diagrams, tuplets or mix table changes, are rejected, and most of the settings which
+//! don't change the notes are skipped.
+
+use super::{
+    writer::{BEND, CHANNEL_COUNT, PREBEND_RELEASE, VERSION},
+    Beat, GpTrack, Note, NoteEffects, NoteKind, Song,
+};
+use crate::fingering::Tuning;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + count)
+            .ok_or_else(|| format!("unexpected end of file at byte {}", self.pos))?;
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        self.take(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i8(&mut self) -> Result<i8, String> {
+        Ok(self.u8()? as i8)
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A count, which can't be negative
+    fn count(&mut self) -> Result<usize, String> {
+        let pos = self.pos;
+        usize::try_from(self.i32()?).map_err(|_| format!("negative count at byte {pos}"))
+    }
+
+    fn latin1(&mut self, len: usize) -> Result<String, String> {
+        Ok(self.take(len)?.iter().map(|x| *x as char).collect())
+    }
+
+    /// A string with its length in a byte, padded to `size` bytes
+    fn byte_size_string(&mut self, size: usize) -> Result<String, String> {
+        let len = self.u8()? as usize;
+        let s = self.latin1(len.min(size))?;
+        self.skip(size - len.min(size))?;
+        Ok(s)
+    }
+
+    /// A string with its length in a byte, preceded by an int with that length plus one
+    fn int_byte_size_string(&mut self) -> Result<String, String> {
+        self.i32()?;
+        let len = self.u8()? as usize;
+        self.latin1(len)
+    }
+
+    /// A string with its length in an int
+    fn int_size_string(&mut self) -> Result<String, String> {
+        let len = self.count()?;
+        self.latin1(len)
+    }
+}
+
+/// The length of a beat in eighths, the inverse of [super::writer::duration]
+fn eighths(value: i8, dotted: bool) -> Result<u32, String> {
+    let len = match value {
+        -2 => 8,
+        -1 => 4,
+        0 => 2,
+        1 => 1,
+        _ => return Err(format!("unsupported duration {value}")),
+    };
+    match dotted {
+        true if len == 1 => Err("unsupported dotted eighth".to_string()),
+        true => Ok(len / 2 * 3),
+        false => Ok(len),
+    }
+}
+
+/// Reads the bytes of a .gp5 file
+pub fn read_song(bytes: &[u8]) -> Result<Song, String> {
+    let mut r = Reader { bytes, pos: 0 };
+    let version = r.byte_size_string(30)?;
+    if version != VERSION {
+        return Err(format!("unsupported version {version:?}"));
+    }
+
+    let title = r.int_byte_size_string()?;
+    for _ in 0..8 {
+        r.int_byte_size_string()?;
+    }
+    for _ in 0..r.count()? {
+        r.int_byte_size_string()?;
+    }
+
+    r.i32()?;
+    for _ in 0..5 {
+        r.i32()?;
+        r.int_size_string()?;
+    }
+
+    r.skip(7 * 4 + 2)?;
+    for _ in 0..10 {
+        r.int_byte_size_string()?;
+    }
+
+    r.int_byte_size_string()?;
+    let tempo = r.i32()?;
+    let tempo = u32::try_from(tempo).map_err(|_| format!("invalid tempo {tempo}"))?;
+    r.skip(1 + 4)?;
+
+    let mut programs = vec![];
+    for _ in 0..CHANNEL_COUNT {
+        programs.push(r.i32()?);
+        r.skip(6 + 2)?;
+    }
+
+    r.skip(19 * 2 + 4)?;
+
+    let measure_count = r.count()?;
+    let track_count = r.count()?;
+    let mut measures = vec![];
+    // 4/4 until the first time signature
+    let mut time = (4, 4);
+    for measure_idx in 0..measure_count {
+        if measure_idx > 0 {
+            r.skip(1)?;
+        }
+        let flags = r.u8()?;
+        if flags & 0x01 != 0 {
+            time.0 = r.i8()? as i32;
+        }
+        if flags & 0x02 != 0 {
+            time.1 = r.i8()? as i32;
+        }
+        if flags & 0x08 != 0 {
+            // the number of repeats
+            r.skip(1)?;
+        }
+        if flags & 0x20 != 0 {
+            // a marker with its colour
+            r.int_byte_size_string()?;
+            r.skip(4)?;
+        }
+        if flags & 0x40 != 0 {
+            r.skip(2)?;
+        }
+        if flags & 0x10 != 0 {
+            r.skip(1)?;
+        }
+        if flags & 0x03 != 0 {
+            r.skip(4)?;
+        }
+        if flags & 0x10 == 0 {
+            r.skip(1)?;
+        }
+        r.skip(1)?;
+        match time {
+            (numerator @ 1.., 1 | 2 | 4 | 8) => measures.push((numerator * 8 / time.1) as u32),
+            _ => return Err(format!("unsupported time signature {}/{}", time.0, time.1)),
+        }
+    }
+
+    let mut tracks = vec![];
+    for _ in 0..track_count {
+        r.skip(2)?;
+        let name = r.byte_size_string(40)?;
+        let string_count = r.count()?;
+        if string_count != 6 {
+            return Err(format!("unsupported track with {string_count} strings"));
+        }
+        let mut tuning = Tuning::STANDARD;
+        for string in &mut tuning.strings {
+            let pitch = r.i32()?;
+            *string = u8::try_from(pitch).map_err(|_| format!("invalid string pitch {pitch}"))?;
+        }
+        r.skip(4)?;
+        let port = r.count()?;
+        let channel = r.count()?;
+        let program = (port * 16 + channel)
+            .checked_sub(17)
+            .and_then(|x| programs.get(x))
+            .ok_or_else(|| format!("invalid channel {channel} on port {port}"))?;
+        r.skip(4 + 4 + 4 + 4 + 2 + 1 + 1)?;
+        r.skip(1 + 24 + 12 + 3)?;
+        tracks.push(GpTrack { name, tuning, program: *program as u8, measures: vec![] });
+    }
+    r.skip(2)?;
+
+    for _ in 0..measure_count {
+        for track in &mut tracks {
+            let mut beats = vec![];
+            for _ in 0..r.count()? {
+                beats.push(read_beat(&mut r)?);
+            }
+            if r.count()? > 0 {
+                return Err("unsupported second voice".to_string());
+            }
+            r.skip(1)?;
+            track.measures.push(beats);
+        }
+    }
+    Ok(Song { title, tempo, measures, tracks })
+}
+
+fn read_beat(r: &mut Reader) -> Result<Beat, String> {
+    let flags = r.u8()?;
+    if flags & 0x40 != 0 {
+        // empty or rest, which is told apart by the notes
+        r.skip(1)?;
+    }
+    let len = eighths(r.i8()?, flags & 0x01 != 0)?;
+    if flags & 0x20 != 0 {
+        return Err("unsupported tuplet".to_string());
+    }
+    if flags & 0x02 != 0 {
+        return Err("unsupported chord diagram".to_string());
+    }
+    if flags & 0x04 != 0 {
+        r.int_byte_size_string()?;
+    }
+    if flags & 0x08 != 0 {
+        return Err("unsupported beat effect".to_string());
+    }
+    if flags & 0x10 != 0 {
+        return Err("unsupported mix table change".to_string());
+    }
+    let strings = r.u8()?;
+    let mut notes = vec![];
+    for string in 1..=6u8 {
+        if strings & 1 << (7 - string) != 0 {
+            notes.push(read_note(r, string)?);
+        }
+    }
+    if r.i16()? & 0x0800 != 0 {
+        r.skip(1)?;
+    }
+    Ok(Beat { len, notes })
+}
+
+fn read_note(r: &mut Reader, string: u8) -> Result<Note, String> {
+    let flags = r.u8()?;
+    let mut kind = NoteKind::Normal;
+    let mut fret = 0;
+    if flags & 0x20 != 0 {
+        kind = match r.u8()? {
+            1 => NoteKind::Normal,
+            2 => NoteKind::Tie,
+            3 => NoteKind::Dead,
+            x => return Err(format!("invalid note type {x}")),
+        };
+    }
+    if flags & 0x10 != 0 {
+        // the dynamic
+        r.skip(1)?;
+    }
+    if flags & 0x20 != 0 {
+        let value = r.i8()?;
+        fret = u8::try_from(value).map_err(|_| format!("invalid fret {value}"))?;
+    }
+    if flags & 0x80 != 0 {
+        // the fingering of both hands
+        r.skip(2)?;
+    }
+    if flags & 0x01 != 0 {
+        // the duration in percent
+        r.skip(8)?;
+    }
+    r.skip(1)?;
+    let mut effects = NoteEffects::default();
+    if flags & 0x08 != 0 {
+        let flags1 = r.i8()?;
+        let flags2 = r.i8()?;
+        effects.hammer = flags1 & 0x02 != 0;
+        effects.vibrato = flags2 & 0x40 != 0;
+        if flags1 & 0x01 != 0 {
+            let pos = r.pos;
+            let kind = r.u8()?;
+            r.skip(4)?;
+            let mut peak: i32 = 0;
+            for _ in 0..r.count()? {
+                r.skip(4)?;
+                peak = peak.max(r.i32()?);
+                r.skip(1)?;
+            }
+            effects.bend = Some(match kind {
+                BEND => (peak / 50) as i16,
+                PREBEND_RELEASE => -(peak / 50) as i16,
+                _ => return Err(format!("unsupported bend type {kind} at byte {pos}")),
+            });
+        }
+        if flags1 & 0x10 != 0 {
+            // a grace note
+            r.skip(5)?;
+        }
+        if flags2 & 0x04 != 0 {
+            // tremolo picking
+            r.skip(1)?;
+        }
+        if flags2 & 0x08 != 0 {
+            r.skip(1)?;
+            effects.slide = true;
+        }
+        if flags2 & 0x10 != 0 {
+            // a harmonic, with the pitch of an artificial one or the fret of a tapped one
+            match r.i8()? {
+                2 => r.skip(3)?,
+                3 => r.skip(1)?,
+                _ => (),
+            }
+        }
+        if flags2 & 0x20 != 0 {
+            // a trill
+            r.skip(2)?;
+        }
+    }
+    Ok(Note { string, fret, kind, effects })
+}
@@ -0,0 +1,340 @@
+//! The binary layout of a version 5.00 file. All numbers are little endian, and strings are
+//! Latin-1, in one of the length-prefixed forms below.
+
+use super::{Beat, Note, NoteKind, Song};
+
+pub const VERSION: &str = "FICHIER GUITAR PRO v5.00";
+
+/// The number of MIDI ports times the channels of a port
+pub const CHANNEL_COUNT: usize = 64;
+/// The bend types used for [super::NoteEffects::bend]. The points of a bend are never negative, a
+/// release starts bent instead.
+pub const BEND: u8 = 1;
+pub const PREBEND_RELEASE: u8 = 5;
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, x: u8) {
+        self.bytes.push(x);
+    }
+
+    fn i8(&mut self, x: i8) {
+        self.bytes.push(x as u8);
+    }
+
+    fn i16(&mut self, x: i16) {
+        self.bytes.extend(x.to_le_bytes());
+    }
+
+    fn i32(&mut self, x: i32) {
+        self.bytes.extend(x.to_le_bytes());
+    }
+
+    /// Bytes that readers skip
+    fn placeholder(&mut self, count: usize) {
+        self.bytes.resize(self.bytes.len() + count, 0);
+    }
+
+    /// A string with its length in a byte, padded with zeros to `size` bytes
+    fn byte_size_string(&mut self, s: &str, size: usize) {
+        let s = latin1(s, size);
+        self.u8(s.len() as u8);
+        self.bytes.extend(&s);
+        self.placeholder(size - s.len());
+    }
+
+    /// A string with its length in a byte, preceded by an int with that length plus one
+    fn int_byte_size_string(&mut self, s: &str) {
+        let s = latin1(s, u8::MAX as usize);
+        self.i32(s.len() as i32 + 1);
+        self.u8(s.len() as u8);
+        self.bytes.extend(s);
+    }
+
+    /// A string with its length in an int
+    fn int_size_string(&mut self, s: &str) {
+        let s = latin1(s, i32::MAX as usize);
+        self.i32(s.len() as i32);
+        self.bytes.extend(s);
+    }
+}
+
+/// The first `max` characters of `s` in Latin-1, with `?` for the ones it doesn't have
+fn latin1(s: &str, max: usize) -> Vec<u8> {
+    s.chars().take(max).map(|c| u8::try_from(c as u32).unwrap_or(b'?')).collect()
+}
+
+/// The MIDI channel of a track, skipping the percussion channel like [crate::backend::midi]
+pub fn track_channel(track_idx: usize) -> usize {
+    let channel = if track_idx >= 9 { track_idx + 1 } else { track_idx };
+    channel % 16
+}
+
+/// The duration of a beat and whether it is dotted. Guitar Pro stores a whole note as -2, a half
+/// note as -1 and so on.
+pub fn duration(eighths: u32) -> (i8, bool) {
+    match eighths {
+        8 => (-2, false),
+        6 => (-1, true),
+        4 => (-1, false),
+        3 => (0, true),
+        2 => (0, false),
+        _ => (1, false),
+    }
+}
+
+/// Writes a whole song as the bytes of a .gp5 file. Fails for measures which are too long for
+/// a time signature.
+pub fn write_song(song: &Song) -> Result<Vec<u8>, String> {
+    let mut w = Writer::default();
+    w.byte_size_string(VERSION, 30);
+
+    // title, subtitle, artist, album, words, music, copyright, tab author and instructions
+    w.int_byte_size_string(&song.title);
+    for _ in 0..8 {
+        w.int_byte_size_string("");
+    }
+    // the lines of the notice
+    w.i32(0);
+
+    // the track the lyrics belong to, and five lines of lyrics with their first measure
+    w.i32(0);
+    for _ in 0..5 {
+        w.i32(1);
+        w.int_size_string("");
+    }
+
+    // the page size and margins in millimetres, and the size of the score in percent
+    for x in [210, 297, 10, 10, 15, 10, 100] {
+        w.i32(x);
+    }
+    // show every header and footer
+    w.i16(0x01ff);
+    for s in [
+        "%TITLE%",
+        "%SUBTITLE%",
+        "%ARTIST%",
+        "%ALBUM%",
+        "Words by %WORDS%",
+        "Music by %MUSIC%",
+        "Words & Music by %WORDSMUSIC%",
+        "Copyright %COPYRIGHT%",
+        "All Rights Reserved - International Copyright Secured",
+        "Page %N%/%P%",
+    ] {
+        w.int_byte_size_string(s);
+    }
+
+    w.int_byte_size_string("Moderate");
+    w.i32(song.tempo as i32);
+    // the key signature and its octave
+    w.i8(0);
+    w.i32(0);
+
+    let mut programs = [25; CHANNEL_COUNT];
+    for (track_idx, track) in song.tracks.iter().enumerate() {
+        programs[track_channel(track_idx)] = track.program as i32;
+    }
+    for program in programs {
+        w.i32(program);
+        // volume, balance, chorus, reverb, phaser and tremolo, in sixteenths of their range
+        for x in [13, 8, 0, 0, 0, 0] {
+            w.i8(x);
+        }
+        w.placeholder(2);
+    }
+
+    // the measures of the 19 directions, like segno and coda, which are all unused
+    for _ in 0..19 {
+        w.i16(-1);
+    }
+    // the master reverb
+    w.i32(0);
+
+    w.i32(song.measures.len() as i32);
+    w.i32(song.tracks.len() as i32);
+    write_measure_headers(&mut w, &song.measures)?;
+    for (track_idx, track) in song.tracks.iter().enumerate() {
+        w.placeholder(1);
+        // visible
+        w.u8(0x08);
+        w.byte_size_string(&track.name, 40);
+        w.i32(6);
+        for string in track.tuning.strings {
+            w.i32(string as i32);
+        }
+        // the unused seventh string
+        w.i32(0);
+        // the port, the channel and the effect channel, counting from 1
+        w.i32(1);
+        w.i32(track_channel(track_idx) as i32 + 1);
+        w.i32(track_channel(track_idx) as i32 + 1);
+        // the number of frets and the capo
+        w.i32(24);
+        w.i32(0);
+        // the colour, as red, green, blue and a padding byte
+        for x in [255, 0, 0, 0] {
+            w.u8(x);
+        }
+        // show the tab and the standard notation
+        w.i16(0x0003);
+        // auto accentuation and the MIDI bank
+        w.u8(0);
+        w.u8(0);
+        // the settings of the Realistic Sound Engine: humanize, some unknown ints, the
+        // instrument, its variation and sound bank, and its effect
+        w.u8(0);
+        w.placeholder(24);
+        for _ in 0..3 {
+            w.i32(-1);
+        }
+        w.i16(-1);
+        w.placeholder(1);
+    }
+    w.placeholder(2);
+
+    for measure_idx in 0..song.measures.len() {
+        for track in &song.tracks {
+            let beats = track.measures.get(measure_idx).map(|x| x.as_slice()).unwrap_or(&[]);
+            w.i32(beats.len() as i32);
+            for beat in beats {
+                write_beat(&mut w, beat);
+            }
+            // the second voice is always empty
+            w.i32(0);
+            // no line break
+            w.u8(0);
+        }
+    }
+    Ok(w.bytes)
+}
+
+/// Writes a time signature of the length in eighths at the start of every measure whose length
+/// differs from the one before
+fn write_measure_headers(w: &mut Writer, measures: &[u32]) -> Result<(), String> {
+    let mut last = None;
+    for (measure_idx, eighths) in measures.iter().enumerate() {
+        if measure_idx > 0 {
+            w.placeholder(1);
+        }
+        let changed = last != Some(*eighths);
+        last = Some(*eighths);
+        // the numerator and denominator of the time signature follow
+        w.u8(if changed { 0x03 } else { 0x00 });
+        if changed {
+            let numerator = i8::try_from(*eighths).map_err(|_| {
+                format!(
+                    "measure {} has {eighths} eighths, but a time signature can have at most {}",
+                    measure_idx + 1,
+                    i8::MAX
+                )
+            })?;
+            w.i8(numerator);
+            w.i8(8);
+            // beam the eighths in pairs, with the rest in the last group
+            let pairs = (eighths / 2).clamp(1, 4);
+            for group in 0..4 {
+                let x = match group + 1 {
+                    g if g < pairs => 2,
+                    g if g == pairs => eighths - 2 * (pairs - 1),
+                    _ => 0,
+                };
+                w.u8(x as u8);
+            }
+        }
+        // no alternate ending
+        w.placeholder(1);
+        // the triplet feel
+        w.u8(0);
+    }
+    Ok(())
+}
+
+fn write_beat(w: &mut Writer, beat: &Beat) {
+    let (value, dotted) = duration(beat.len);
+    let rest = beat.notes.is_empty();
+    let mut flags = 0;
+    if dotted {
+        flags |= 0x01;
+    }
+    if rest {
+        flags |= 0x40;
+    }
+    w.u8(flags);
+    if rest {
+        w.u8(0x02);
+    }
+    w.i8(value);
+    let strings = beat.notes.iter().fold(0, |acc, x| acc | 1 << (7 - x.string));
+    w.u8(strings);
+    let mut notes = beat.notes.iter().collect::<Vec<_>>();
+    notes.sort_by_key(|x| x.string);
+    for note in notes {
+        write_note(w, note);
+    }
+    // the display flags
+    w.i16(0);
+}
+
+fn write_note(w: &mut Writer, note: &Note) {
+    let e = &note.effects;
+    let has_effects = e.bend.is_some() || e.hammer || e.slide || e.vibrato;
+    // the note type and fret follow
+    let mut flags = 0x20;
+    if has_effects {
+        flags |= 0x08;
+    }
+    w.u8(flags);
+    w.u8(note.kind as u8);
+    w.i8(match note.kind {
+        NoteKind::Dead => 0,
+        _ => note.fret as i8,
+    });
+    // don't swap the accidentals
+    w.u8(0);
+    if !has_effects {
+        return;
+    }
+    let mut flags1 = 0;
+    if e.bend.is_some() {
+        flags1 |= 0x01;
+    }
+    if e.hammer {
+        flags1 |= 0x02;
+    }
+    let mut flags2 = 0;
+    if e.slide {
+        flags2 |= 0x08;
+    }
+    if e.vibrato {
+        flags2 |= 0x40;
+    }
+    w.i8(flags1);
+    w.i8(flags2);
+    if let Some(bend) = e.bend {
+        // in hundredths of a whole tone, reached halfway through the note and held. A release
+        // goes down from the bent pitch instead.
+        let value = bend.unsigned_abs() as i32 * 50;
+        let (kind, points) = match bend {
+            0.. => (BEND, [(0, 0), (30, value), (60, value)]),
+            _ => (PREBEND_RELEASE, [(0, value), (30, 0), (60, 0)]),
+        };
+        w.u8(kind);
+        w.i32(value);
+        w.i32(points.len() as i32);
+        for (position, value) in points {
+            w.i32(position);
+            w.i32(value);
+            // no vibrato
+            w.u8(0);
+        }
+    }
+    if e.slide {
+        // a legato slide
+        w.i8(0x02);
+    }
+}
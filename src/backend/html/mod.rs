@@ -0,0 +1,351 @@
+//! Writes a single self-contained HTML page with the tab and a player, to send a tab to someone
+//! who has nothing but a browser.
+//!
+//! The tab is drawn column by column, so the player can highlight the tick it is playing. The
+//! player is a small WebAudio synth which reads the notes from an inline JSON list. Notes are read
+//! with [crate::parser::score::Track::sounding_notes], like in [super::audio].
+
+use std::{fmt::Write, time::Instant};
+
+use super::{
+    check_tempo,
+    errors::backend_error::BackendError,
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector, TEMPOS,
+};
+use crate::{
+    parser::{
+        parser::{parse, ParseResult},
+        score::Track,
+        tab_element::TabElement,
+    },
+    time,
+};
+
+pub struct HtmlBackend();
+
+#[derive(Clone, Debug)]
+pub struct HtmlSettings {
+    /// The title of the page. The name of the first track is used if not set.
+    pub title: Option<String>,
+    /// The tempo the player starts with, in quarter notes per minute
+    pub tempo: u32,
+}
+
+impl Default for HtmlSettings {
+    fn default() -> Self {
+        Self { title: None, tempo: 80 }
+    }
+}
+
+impl HtmlSettings {
+    pub fn validate(&self) -> Result<(), BackendError> {
+        check_tempo(self.tempo)
+    }
+}
+
+impl Backend for HtmlBackend {
+    type BackendSettings = HtmlSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        if let Err(e) = settings.validate() {
+            return BackendResult::new(vec![], Some(e), None, None);
+        }
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut page = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        if let Err(e) = write_html(&parsed, &mut page, &settings) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(page.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("title", "The title of the page"),
+        ("tempo", "The tempo the player starts with, from 1 to 1000, 80 by default"),
+    ];
+    registry.register(SelectorBackend::new(
+        "html",
+        "Writes an HTML page with the tab and a player",
+        &["html", "htm"],
+        options,
+        |o| {
+            let default = HtmlSettings::default();
+            let settings =
+                HtmlSettings { title: o.get("title")?, tempo: o.get_or("tempo", default.tempo)? };
+            settings.validate()?;
+            Ok(BackendSelector::Html(settings))
+        },
+    ));
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em}
+.part{display:flex;font-family:monospace;font-size:16px;line-height:1.2;margin:1.5em 0}
+.col{display:flex;flex-direction:column;white-space:pre}
+.col .n{font-size:11px;color:#777}
+.col.on{background:#ffe27a}
+button{font-size:1em;min-width:5em}
+#tempo{width:4em}";
+
+const SCRIPT: &str = r#"const data = JSON.parse(document.getElementById("notes").textContent);
+const button = document.getElementById("play");
+let ctx = null;
+let playing = null;
+
+function frequency(pitch) {
+  return 440 * Math.pow(2, (pitch - 69) / 12);
+}
+
+function highlight(tick) {
+  document.querySelectorAll(".col.on").forEach((x) => x.classList.remove("on"));
+  data.tracks.forEach((_, track) => {
+    const col = document.getElementById(`c${track}_${tick}`);
+    if (col) {
+      col.classList.add("on");
+      col.scrollIntoView({ block: "nearest", inline: "nearest" });
+    }
+  });
+}
+
+function stop() {
+  if (playing) {
+    playing.output.disconnect();
+    cancelAnimationFrame(playing.frame);
+    playing = null;
+  }
+  highlight(-1);
+  button.textContent = "Play";
+}
+
+function play() {
+  ctx = ctx || new AudioContext();
+  const tick = 30 / Math.max(1, Number(document.getElementById("tempo").value));
+  const start = ctx.currentTime + 0.1;
+  const output = ctx.createGain();
+  output.gain.value = 0.15;
+  output.connect(ctx.destination);
+  for (const track of data.tracks) {
+    for (const note of track.notes) {
+      const from = start + note.t * tick;
+      const to = from + (note.x ? 0.06 : note.d * tick);
+      const osc = ctx.createOscillator();
+      osc.type = "triangle";
+      osc.frequency.setValueAtTime(frequency(note.p), from);
+      let pitch = note.p;
+      for (const [at, len, target] of note.g || []) {
+        const glideStart = start + at * tick;
+        osc.frequency.setValueAtTime(frequency(pitch), glideStart);
+        osc.frequency.linearRampToValueAtTime(frequency(target), glideStart + Math.max(len * tick, 0.01));
+        pitch = target;
+      }
+      if (note.v !== undefined) {
+        const lfo = ctx.createOscillator();
+        const depth = ctx.createGain();
+        lfo.frequency.value = 5.5;
+        depth.gain.setValueAtTime(0, from);
+        depth.gain.setValueAtTime(30, start + note.v * tick);
+        lfo.connect(depth).connect(osc.detune);
+        lfo.start(from);
+        lfo.stop(to);
+      }
+      const envelope = ctx.createGain();
+      envelope.gain.setValueAtTime(0.0001, from);
+      envelope.gain.exponentialRampToValueAtTime(1, from + 0.005);
+      envelope.gain.exponentialRampToValueAtTime(0.01, to);
+      osc.connect(envelope).connect(output);
+      osc.start(from);
+      osc.stop(to + 0.05);
+    }
+  }
+  const length = Math.max(...data.tracks.map((x) => x.ticks));
+  playing = { output, frame: 0 };
+  const frame = () => {
+    const current = Math.floor((ctx.currentTime - start) / tick);
+    if (current >= length) {
+      stop();
+      return;
+    }
+    highlight(current);
+    playing.frame = requestAnimationFrame(frame);
+  };
+  playing.frame = requestAnimationFrame(frame);
+  button.textContent = "Stop";
+}
+
+button.addEventListener("click", () => (playing ? stop() : play()));"#;
+
+/// A string escaped for HTML text and attributes
+fn escaped(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// How an element is written in the tab
+fn element_text(element: &TabElement) -> String {
+    match element {
+        TabElement::Fret(fret) => fret.to_string(),
+        TabElement::Rest => "-".to_string(),
+        TabElement::DeadNote => "x".to_string(),
+        TabElement::Bend => "b".to_string(),
+        TabElement::HammerOn => "h".to_string(),
+        TabElement::Pull => "p".to_string(),
+        TabElement::Release => "r".to_string(),
+        TabElement::Slide => "/".to_string(),
+        TabElement::Vibrato => "~".to_string(),
+    }
+}
+
+/// Writes a column of the tab: a cell above the strings, and a cell for every string
+fn write_column(
+    buf: &mut String, attributes: &str, top: &str, cells: &[String],
+) -> std::fmt::Result {
+    write!(buf, r#"<div class="col"{attributes}><span class="n">{top}</span>"#)?;
+    for cell in cells {
+        write!(buf, "<span>{}</span>", escaped(cell))?;
+    }
+    writeln!(buf, "</div>")
+}
+
+/// Writes the whole page into `buf`
+pub fn write_html(
+    parsed: &ParseResult, buf: &mut String, settings: &HtmlSettings,
+) -> std::fmt::Result {
+    let title = settings.title.as_deref();
+    let title = title.or(parsed.tracks.first().map(|x| x.name.as_str())).unwrap_or("Untitled");
+    let title = escaped(title);
+    writeln!(buf, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(buf, "<title>{title}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>")?;
+    writeln!(buf, "<h1>{title}</h1>")?;
+    let (min_tempo, max_tempo) = TEMPOS.into_inner();
+    writeln!(
+        buf,
+        r#"<p><button id="play">Play</button> <label>Tempo <input id="tempo" type="number" min="{min_tempo}" max="{max_tempo}" value="{}"></label></p>"#,
+        settings.tempo
+    )?;
+
+    let part_of = |stream_idx: usize| {
+        parsed.offsets.partition_point(|x| x.1 as usize <= stream_idx).saturating_sub(1)
+    };
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        if parsed.tracks.len() > 1 {
+            writeln!(buf, "<h2>{}</h2>", escaped(&track.name))?;
+        }
+        let mut tick = 0;
+        let mut current_part = None;
+        for (measure_number, measure_idx) in track.measures.iter().enumerate() {
+            let range = &parsed.measures[*measure_idx as usize].data_range;
+            let part = part_of(*range.start() as usize);
+            if current_part != Some(part) {
+                if current_part.is_some() {
+                    write_column(buf, "", "", &vec!["|".to_string(); 6])?;
+                    writeln!(buf, "</div>")?;
+                }
+                current_part = Some(part);
+                writeln!(buf, r#"<div class="part">"#)?;
+                let names = parsed.base_notes[part * 6..part * 6 + 6].iter().map(|x| x.to_string());
+                write_column(buf, "", "", &names.collect::<Vec<_>>())?;
+            }
+            write_column(buf, "", "", &vec!["|".to_string(); 6])?;
+            for (tick_in_measure, stream_idx) in
+                (*range.start() as usize..*range.end() as usize).step_by(6).enumerate()
+            {
+                let mut cells = (0..6)
+                    .map(|string| element_text(&parsed.tick_stream[stream_idx + string]))
+                    .collect::<Vec<_>>();
+                let width = cells.iter().map(|x| x.len()).max().unwrap_or(1);
+                for cell in &mut cells {
+                    while cell.len() < width {
+                        cell.push('-');
+                    }
+                }
+                let number = match tick_in_measure {
+                    0 => (measure_number + 1).to_string(),
+                    _ => String::new(),
+                };
+                let id = format!(r#" id="c{track_idx}_{tick}""#);
+                write_column(buf, &id, &number, &cells)?;
+                tick += 1;
+            }
+        }
+        if current_part.is_some() {
+            write_column(buf, "", "", &vec!["|".to_string(); 6])?;
+            writeln!(buf, "</div>")?;
+        }
+    }
+
+    write!(buf, r#"<script id="notes" type="application/json">{{"tracks":["#)?;
+    for track in parsed.score().tracks() {
+        if track.index() > 0 {
+            buf.push(',');
+        }
+        write_track_notes(buf, track)?;
+    }
+    writeln!(buf, "]}}</script>")?;
+    writeln!(buf, "<script>\n{SCRIPT}\n</script>\n</body>\n</html>")
+}
+
+/// Writes the notes of a track as a JSON object, like `{"ticks":8,"notes":[{"t":1,"d":2,"p":55}]}`
+fn write_track_notes(buf: &mut String, track: Track) -> std::fmt::Result {
+    let mut notes = track.sounding_notes();
+    notes.sort_by_key(|x| (x.tick, x.pitch));
+
+    write!(buf, r#"{{"ticks":{},"notes":["#, track.tick_count())?;
+    for (idx, note) in notes.iter().enumerate() {
+        if idx > 0 {
+            buf.push(',');
+        }
+        write!(buf, r#"{{"t":{},"d":{},"p":{}"#, note.tick, note.len, note.pitch)?;
+        if !note.glides.is_empty() {
+            let glides = note.glides.iter().map(|x| format!("[{},{},{}]", x.tick, x.len, x.to));
+            write!(buf, r#","g":[{}]"#, glides.collect::<Vec<_>>().join(","))?;
+        }
+        if let Some(vibrato) = note.vibrato {
+            write!(buf, r#","v":{vibrato}"#)?;
+        }
+        if note.dead {
+            write!(buf, r#","x":1"#)?;
+        }
+        buf.push('}');
+    }
+    write!(buf, "]}}")
+}
+
+#[test]
+fn test_html() {
+    use crate::backend::errors::backend_error_kind::BackendErrorKind;
+
+    let input = std::fs::read_to_string("input/multi_track.tab").unwrap();
+    let input = input.replace("Track: Bass", "Track: Bass</script><script>alert(1)");
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut out = vec![];
+    let settings = HtmlSettings { title: Some("Riff <1>".to_string()), tempo: 100 };
+    let res = HtmlBackend::process(&lines, &mut out, settings);
+    assert!(res.err.is_none(), "{:?}", res.err);
+    let page = String::from_utf8(out).unwrap();
+    assert!(page.contains("<h1>Riff &lt;1&gt;</h1>"));
+    // a track name can't end the script with the notes early
+    assert!(page.contains("<h2>Bass&lt;/script&gt;&lt;script&gt;alert(1)</h2>"));
+    assert!(!page.contains("</script><script>alert"));
+    assert_eq!(page.matches("</script>").count(), 2);
+    assert!(page.contains(r#"min="1" max="1000" value="100""#));
+    assert!(page.contains(r#"<script id="notes" type="application/json">{"tracks":[{"ticks":16,"#));
+    // nothing is loaded from elsewhere
+    assert!(!page.contains("src="));
+    assert!(!page.contains("href="));
+
+    let settings = HtmlSettings { title: None, tempo: 0 };
+    let res = HtmlBackend::process(&lines, &mut vec![], settings);
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidOption(_)));
+}
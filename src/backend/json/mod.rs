@@ -0,0 +1,222 @@
+//! Writes the parsed score as JSON, so other tools can use scoreman's parse without reading tabs
+//! themselves. [crate::import::ir] reads it back into a [ParseResult].
+//!
+//! The schema is versioned with [VERSION], which changes whenever a reader of an older version
+//! could misread the output. A file looks like this:
+//!
+//! ```json
+//! {
+//!   "format": "scoreman",
+//!   "version": 1,
+//!   "tracks": [{"name": "Guitar1", "program": null, "midi_program": 25}],
+//!   "parts": [
+//!     {
+//!       "track": 0,
+//!       "line": 0,
+//!       "strings": ["e", "B", "G", "D", "A", "E"],
+//!       "tuning": [64, 59, 55, 50, 45, 40],
+//!       "measures": [
+//!         {"ticks": [
+//!           {"notes": [{"string": 3, "fret": 5, "pitch": 55, "span": {"line": 3, "start": 2, "end": 3}}], "techniques": []},
+//!           {"notes": [], "techniques": [{"string": 3, "kind": "hammer-on", "span": {"line": 3, "start": 3, "end": 4}}]}
+//!         ]}
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! - Every Part belongs to the track at the index `track`, and its measures follow the measures of
+//!   the Parts before it in that track.
+//! - `line` is the index of the line with the first string of the Part. Strings are numbered from
+//!   0, the first line of the Part, which is usually the highest string.
+//! - `tuning` and `pitch` are MIDI keys, read from the string names like in the other backends.
+//! - Every tick is an eighth. Rests are not listed.
+//! - A dead note has `"dead": true` instead of a fret and a pitch.
+//! - The `kind` of a technique is one of `bend`, `release`, `hammer-on`, `pull-off`, `slide` and
+//!   `vibrato`. A technique is written on its own tick, after the note it belongs to.
+//! - A `span` points into the input: the line index, and the range of character indices taken by
+//!   the tick.
+//!
+//! `midi_program`, `tuning`, `pitch` and `span` are only there for readers, and are ignored when
+//! the file is loaded.
+
+use std::{fmt::Write, time::Instant};
+
+use super::{
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    fingering::refret::part_tunings,
+    parser::{
+        parser::{parse, ParseResult},
+        tab_element::TabElement,
+    },
+    rlen, time,
+};
+
+/// The value of the `format` field, which tells the file apart from other JSON
+pub const FORMAT: &str = "scoreman";
+/// The version of the schema
+pub const VERSION: u32 = 1;
+
+/// The names of the techniques in the schema
+pub const TECHNIQUES: [(TabElement, &str); 6] = [
+    (TabElement::Bend, "bend"),
+    (TabElement::Release, "release"),
+    (TabElement::HammerOn, "hammer-on"),
+    (TabElement::Pull, "pull-off"),
+    (TabElement::Slide, "slide"),
+    (TabElement::Vibrato, "vibrato"),
+];
+
+pub struct JsonBackend();
+
+impl Backend for JsonBackend {
+    type BackendSettings = ();
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, _settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut json = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        if let Err(e) = write_json(&parsed, input, &mut json) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(json.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    registry.register(SelectorBackend::new(
+        "json",
+        "Writes the parsed score as JSON",
+        &["json"],
+        &[],
+        |_| Ok(BackendSelector::Json),
+    ));
+}
+
+/// A JSON string literal
+pub fn quoted(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Writes the schema described in the module documentation. `input` is only used for the spans,
+/// to account for the indentation of the lines, and can be empty.
+pub fn write_json(parsed: &ParseResult, input: &[String], buf: &mut String) -> std::fmt::Result {
+    writeln!(buf, "{{\n  \"format\": {},\n  \"version\": {VERSION},", quoted(FORMAT))?;
+    writeln!(buf, "  \"tracks\": [")?;
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        let program = track.program.map(|x| x.to_string()).unwrap_or("null".to_string());
+        write!(buf, "    {{\"name\": {}, \"program\": {program}, ", quoted(&track.name))?;
+        write!(buf, "\"midi_program\": {}}}", track.midi_program())?;
+        buf.push_str(if track_idx + 1 < parsed.tracks.len() { ",\n" } else { "\n" });
+    }
+    writeln!(buf, "  ],\n  \"parts\": [")?;
+
+    let tunings = part_tunings(parsed);
+    for (part, (line, _)) in parsed.offsets.iter().enumerate() {
+        let line = *line as usize;
+        let part_start = parsed.offsets[part].1;
+        let part_end = parsed.offsets.get(part + 1).map(|x| x.1).unwrap_or(u32::MAX);
+        let measures = parsed
+            .measures
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| (part_start..part_end).contains(x.data_range.start()))
+            .collect::<Vec<_>>();
+        let track = measures
+            .first()
+            .and_then(|(idx, _)| {
+                parsed.tracks.iter().position(|x| x.measures.contains(&(*idx as u32)))
+            })
+            .unwrap_or(0);
+        let strings = &parsed.base_notes[part * 6..part * 6 + 6];
+        let tuning = tunings[part].strings;
+        writeln!(buf, "    {{\n      \"track\": {track},\n      \"line\": {line},")?;
+        let names = strings.iter().map(|x| quoted(&x.to_string())).collect::<Vec<_>>();
+        writeln!(buf, "      \"strings\": [{}],", names.join(", "))?;
+        let pitches = tuning.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        writeln!(buf, "      \"tuning\": [{}],", pitches.join(", "))?;
+        writeln!(buf, "      \"measures\": [")?;
+
+        // the character index of the current tick on every line, after the indentation and `e|`
+        let mut columns: [usize; 6] = std::array::from_fn(|s| {
+            let src = input.get(line + s).map(|x| x.as_str()).unwrap_or("");
+            src.len() - src.trim_start().len() + 2
+        });
+        for (measure_idx, (_, measure)) in measures.iter().enumerate() {
+            writeln!(buf, "        {{\"ticks\": [")?;
+            let start = *measure.data_range.start() as usize;
+            let tick_cnt = rlen(&measure.data_range) as usize / 6;
+            for tick_idx in 0..tick_cnt {
+                let tick = &parsed.tick_stream[start + tick_idx * 6..start + tick_idx * 6 + 6];
+                let width = tick.iter().map(|x| x.repr_len()).max().unwrap() as usize;
+                let span = |s: usize| {
+                    let (line, start) = (line + s, columns[s]);
+                    format!("{{\"line\": {line}, \"start\": {start}, \"end\": {}}}", start + width)
+                };
+                let mut notes = vec![];
+                let mut techniques = vec![];
+                for (s, elem) in tick.iter().enumerate() {
+                    match elem {
+                        TabElement::Fret(fret) => notes.push(format!(
+                            "{{\"string\": {s}, \"fret\": {fret}, \"pitch\": {}, \"span\": {}}}",
+                            tuning[s] + fret,
+                            span(s)
+                        )),
+                        TabElement::DeadNote => notes.push(format!(
+                            "{{\"string\": {s}, \"dead\": true, \"span\": {}}}",
+                            span(s)
+                        )),
+                        TabElement::Rest => (),
+                        technique => {
+                            let name = TECHNIQUES.iter().find(|x| x.0 == *technique).unwrap().1;
+                            techniques.push(format!(
+                                "{{\"string\": {s}, \"kind\": {}, \"span\": {}}}",
+                                quoted(name),
+                                span(s)
+                            ))
+                        }
+                    }
+                }
+                write!(buf, "          {{\"notes\": [{}], ", notes.join(", "))?;
+                write!(buf, "\"techniques\": [{}]}}", techniques.join(", "))?;
+                buf.push_str(if tick_idx + 1 < tick_cnt { ",\n" } else { "\n" });
+                columns.iter_mut().for_each(|x| *x += width);
+            }
+            // the barline
+            columns.iter_mut().for_each(|x| *x += 1);
+            write!(buf, "        ]}}")?;
+            buf.push_str(if measure_idx + 1 < measures.len() { ",\n" } else { "\n" });
+        }
+        write!(buf, "      ]\n    }}")?;
+        buf.push_str(if part + 1 < parsed.offsets.len() { ",\n" } else { "\n" });
+    }
+    writeln!(buf, "  ]\n}}")
+}
@@ -0,0 +1,17 @@
+use crate::backend::lilypond::{LilypondBackend, LilypondSettings};
+use crate::backend::{process_input_file, INPUT_TABS};
+
+#[test]
+fn test_lilypond() {
+    for name in INPUT_TABS {
+        let out = process_input_file::<LilypondBackend>(name, LilypondSettings::default());
+        insta::assert_snapshot!(name, out);
+    }
+}
+
+#[test]
+fn test_lilypond_simplified_time_signature() {
+    let settings = LilypondSettings { simplify_time_signature: true, ..Default::default() };
+    let out = process_input_file::<LilypondBackend>("ties", settings);
+    assert!(out.contains(r"\time 4/4"), "{out}");
+}
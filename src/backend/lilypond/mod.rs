@@ -0,0 +1,382 @@
+//! Writes a LilyPond (.ly) file, with a staff in standard notation above a tab staff.
+//!
+//! Every tick is an eighth, like in [super::muxml]. A note rings through the `~` and technique
+//! ticks after it, and is tied across barlines when it is held into the next measure. Notes are
+//! written at their sounding pitch on an octave treble clef, with their string numbers, so the
+//! tab staff shows the same fingering as the input.
+
+#[cfg(test)]
+mod lilypond_tests;
+
+use std::{fmt::Write, time::Instant};
+
+use super::{
+    muxml::{
+        detect_key,
+        key::{Key, Spelling},
+        settings::Meter,
+    },
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    fingering::{refret::part_tunings, Tuning},
+    parser::{
+        parser::{parse, ParseResult, Track},
+        tab_element::TabElement,
+    },
+    rlen, time, traceln,
+};
+
+pub struct LilypondBackend();
+
+#[derive(Clone, Debug, Default)]
+pub struct LilypondSettings {
+    /// Detected from the notes if not set
+    pub key: Option<Key>,
+    /// Write time signatures like 4/4 instead of 8/8
+    pub simplify_time_signature: bool,
+}
+
+impl Backend for LilypondBackend {
+    type BackendSettings = LilypondSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut document = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        if let Err(e) = write_lilypond(&parsed, &mut document, &settings) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(document.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("key", "Key signature, like `Bb` or `-1`. Detected if not given."),
+        ("simplify-time-signature", "Simplify time signatures, e.g. 8/8 -> 4/4"),
+    ];
+    registry.register(SelectorBackend::new(
+        "lilypond",
+        "Writes a LilyPond file with a staff and a tab staff",
+        &["ly"],
+        options,
+        |o| {
+            Ok(BackendSelector::Lilypond(LilypondSettings {
+                key: o.get("key")?,
+                simplify_time_signature: o.flag("simplify-time-signature")?,
+            }))
+        },
+    ));
+}
+
+/// A run of ticks in a measure, which is written as a single (possibly tied) chord or rest
+#[derive(Debug, PartialEq)]
+pub(super) struct Event {
+    /// The first stream index of the tick with the notes, [None] for a rest
+    pub tick: Option<usize>,
+    /// In eighths
+    pub len: u32,
+    /// Continues the chord of the last event of the previous measure
+    pub tied: bool,
+}
+
+/// Splits every measure of the track into chords and rests
+pub(super) fn track_events(parsed: &ParseResult, track: &Track) -> Vec<Vec<Event>> {
+    let mut measures: Vec<Vec<Event>> = vec![];
+    // whether the last event, in this measure or the one before, is a chord that can be held
+    let mut sounding = false;
+    for measure_idx in &track.measures {
+        let range = &parsed.measures[*measure_idx as usize].data_range;
+        let mut events: Vec<Event> = vec![];
+        for tick in (*range.start() as usize..*range.end() as usize).step_by(6) {
+            let elements = &parsed.tick_stream[tick..tick + 6];
+            let has_notes =
+                elements.iter().any(|x| matches!(x, TabElement::Fret(_) | TabElement::DeadNote));
+            let holds = elements.iter().any(|x| *x != TabElement::Rest);
+            match events.last_mut() {
+                _ if has_notes => {
+                    events.push(Event { tick: Some(tick), len: 1, tied: false });
+                    sounding = true;
+                    continue;
+                }
+                Some(last) if holds && sounding => last.len += 1,
+                None if holds && sounding => {
+                    let prev = measures.last().and_then(|x| x.last()).and_then(|x| x.tick);
+                    events.push(Event { tick: prev, len: 1, tied: true });
+                }
+                Some(Event { tick: None, len, .. }) => *len += 1,
+                _ => {
+                    events.push(Event { tick: None, len: 1, tied: false });
+                    sounding = false;
+                }
+            }
+        }
+        measures.push(events);
+    }
+    measures
+}
+
+/// Splits a number of eighths into lengths which can be written as a single (possibly dotted)
+/// note, longest first. Notes with more than one length are written as tied ones.
+pub(super) fn note_lengths(mut len: u32) -> Vec<u32> {
+    let mut result = vec![];
+    while len > 0 {
+        let eighths = [8, 6, 4, 3, 2, 1].into_iter().find(|x| *x <= len).unwrap();
+        result.push(eighths);
+        len -= eighths;
+    }
+    result
+}
+
+/// The LilyPond duration of one of the [note_lengths]
+fn duration(eighths: u32) -> &'static str {
+    match eighths {
+        8 => "1",
+        6 => "2.",
+        4 => "2",
+        3 => "4.",
+        2 => "4",
+        _ => "8",
+    }
+}
+
+/// A pitch in LilyPond's absolute mode, like `fis'` or `e,`
+fn pitch_name(spelling: Spelling) -> String {
+    let mut name = spelling.step.to_ascii_lowercase().to_string();
+    for _ in 0..spelling.alter.unsigned_abs() {
+        name.push_str(if spelling.alter > 0 { "is" } else { "es" });
+    }
+    // `c` is the octave below middle C, which is octave 4 in the numbering of [Key::spell]
+    let octave = spelling.octave as i8 - 4;
+    let mark = if octave > 0 { "'" } else { "," };
+    name.push_str(&mark.repeat(octave.unsigned_abs() as usize));
+    name
+}
+
+/// `trackA`, `trackB`, ..., `trackZ`, `trackAA`: LilyPond variable names can't have digits
+fn variable_name(track_idx: usize) -> String {
+    let mut letters = vec![];
+    let mut idx = track_idx + 1;
+    while idx > 0 {
+        letters.push((b'A' + ((idx - 1) % 26) as u8) as char);
+        idx = (idx - 1) / 26;
+    }
+    format!("track{}", letters.iter().rev().collect::<String>())
+}
+
+/// Writes the notes of the tick starting at `tick` as a chord (or a single note) without its
+/// duration. A single note takes its string number after the duration, so it is left out here.
+fn write_chord(
+    parsed: &ParseResult, buf: &mut String, tick: usize, tuning: &Tuning, key: &Key,
+) -> std::fmt::Result {
+    let notes = (0..6)
+        .filter_map(|string| match parsed.tick_stream[tick + string] {
+            TabElement::Fret(fret) => Some((string, fret, false)),
+            TabElement::DeadNote => Some((string, 0, true)),
+            _ => None,
+        })
+        // lowest first, like LilyPond prints them
+        .rev()
+        .collect::<Vec<_>>();
+    let chord = notes.len() > 1;
+    if chord {
+        buf.push('<');
+    }
+    for (idx, (string, fret, dead)) in notes.iter().enumerate() {
+        if idx > 0 {
+            buf.push(' ');
+        }
+        if *dead {
+            buf.push_str("\\deadNote ");
+        }
+        buf.push_str(&pitch_name(key.spell(tuning.strings[*string] + fret)));
+        if chord {
+            write!(buf, "\\{}", string + 1)?;
+        }
+    }
+    if chord {
+        buf.push('>');
+    }
+    Ok(())
+}
+
+/// The techniques of a chord: whether it ends a slur, starts one, and what is attached to it
+#[derive(Default)]
+pub(super) struct Articulations {
+    pub slur_end: bool,
+    pub slur_start: bool,
+    /// A slide starts at this chord
+    pub glissando: bool,
+    /// A slide ends at this chord
+    pub glissando_end: bool,
+    /// In semitones
+    pub bend: Option<i16>,
+}
+
+/// The techniques of the chord at `tick`, which are only followed up to `measure_end`
+pub(super) fn articulations(
+    parsed: &ParseResult, tick: usize, measure_end: usize,
+) -> Articulations {
+    let mut a = Articulations::default();
+    for string in 0..6 {
+        let at =
+            |idx: Option<usize>| idx.filter(|x| *x < measure_end).map(|x| &parsed.tick_stream[x]);
+        let TabElement::Fret(fret) = parsed.tick_stream[tick + string] else {
+            continue;
+        };
+        let before = tick.checked_sub(6).map(|x| x + string);
+        match at(before) {
+            Some(TabElement::HammerOn | TabElement::Pull) => a.slur_end = true,
+            Some(TabElement::Slide) => a.glissando_end = true,
+            _ => (),
+        }
+        let target = match at(Some(tick + 12 + string)) {
+            Some(TabElement::Fret(x)) => Some(*x as i16 - fret as i16),
+            _ => None,
+        };
+        match at(Some(tick + 6 + string)) {
+            Some(TabElement::HammerOn | TabElement::Pull) if target.is_some() => {
+                a.slur_start = true
+            }
+            Some(TabElement::Slide) => a.glissando = true,
+            Some(TabElement::Bend) => a.bend = Some(target.unwrap_or(2)),
+            Some(TabElement::Release) => a.bend = Some(target.unwrap_or(-2)),
+            _ => (),
+        }
+    }
+    a
+}
+
+/// Writes the whole .ly document into `buf`
+pub fn write_lilypond(
+    parsed: &ParseResult, buf: &mut String, settings: &LilypondSettings,
+) -> std::fmt::Result {
+    let key = settings.key.unwrap_or_else(|| detect_key(parsed));
+    let tunings = part_tunings(parsed);
+    let tuning_of = |stream_idx: usize| {
+        let part = parsed.offsets.partition_point(|x| x.1 as usize <= stream_idx);
+        tunings.get(part.saturating_sub(1)).copied().unwrap_or_default()
+    };
+    writeln!(buf, "\\version \"2.24.0\"")?;
+
+    let (tonic, alter) = key.tonic();
+    let tonic =
+        pitch_name(Spelling { step: "CDEFGAB".as_bytes()[tonic] as char, alter, octave: 4 });
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        writeln!(buf, "\n{} = {{", variable_name(track_idx))?;
+        writeln!(buf, "  \\key {tonic} \\major")?;
+        let mut last_meter = None;
+        let mut slur_open = false;
+        let measures = track_events(parsed, track);
+        for (measure_number, events) in measures.iter().enumerate() {
+            let measure_idx = track.measures[measure_number] as usize;
+            let range = &parsed.measures[measure_idx].data_range;
+            let measure_end = *range.end() as usize + 1;
+            let len = rlen(range) / 6;
+            let meter = Meter::from_eighths(len, settings.simplify_time_signature);
+            buf.push_str("  ");
+            if last_meter != Some(meter) {
+                write!(buf, "\\time {}/{} ", meter.beats, meter.beat_type)?;
+                last_meter = Some(meter);
+            }
+            traceln!("lilypond: measure {measure_idx} has events {events:?}");
+            for (event_idx, event) in events.iter().enumerate() {
+                let Some(tick) = event.tick else {
+                    for len in note_lengths(event.len) {
+                        write!(buf, "r{} ", duration(len))?;
+                    }
+                    continue;
+                };
+                let tied_over = match events.get(event_idx + 1) {
+                    Some(_) => false,
+                    None => measures
+                        .get(measure_number + 1)
+                        .is_some_and(|x| x.first().is_some_and(|x| x.tied && x.tick == Some(tick))),
+                };
+                let a = match event.tied {
+                    // the techniques are written on the first part of the chord
+                    true => Articulations::default(),
+                    false => articulations(parsed, tick, measure_end),
+                };
+                let tuning = tuning_of(tick);
+                let lengths = note_lengths(event.len);
+                let single = (0..6).filter(|x| {
+                    matches!(
+                        parsed.tick_stream[tick + x],
+                        TabElement::Fret(_) | TabElement::DeadNote
+                    )
+                });
+                let single = match single.collect::<Vec<_>>().as_slice() {
+                    [string] => Some(string + 1),
+                    _ => None,
+                };
+                for (idx, len) in lengths.iter().enumerate() {
+                    write_chord(parsed, buf, tick, &tuning, &key)?;
+                    buf.push_str(duration(*len));
+                    if let Some(string) = single {
+                        write!(buf, "\\{string}")?;
+                    }
+                    if idx == 0 && a.slur_end && slur_open {
+                        buf.push(')');
+                        slur_open = false;
+                    }
+                    let last = idx == lengths.len() - 1;
+                    if last && a.slur_start && !slur_open {
+                        buf.push('(');
+                        slur_open = true;
+                    }
+                    if last && a.glissando {
+                        buf.push_str("\\glissando");
+                    }
+                    if let Some(bend) = a.bend.filter(|_| last) {
+                        write!(buf, "-\\bendAfter #{bend:+}")?;
+                    }
+                    if !last || tied_over {
+                        buf.push('~');
+                    }
+                    buf.push(' ');
+                }
+            }
+            buf.push_str("|\n");
+        }
+        buf.push_str("}\n");
+    }
+
+    buf.push_str("\n\\score {\n  <<\n");
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        let tuning = track
+            .measures
+            .first()
+            .map(|x| tuning_of(*parsed.measures[*x as usize].data_range.start() as usize))
+            .unwrap_or_default();
+        let strings = tuning.strings.iter().rev().map(|x| pitch_name(Key::default().spell(*x)));
+        let name = track.name.replace('\\', "\\\\").replace('"', "\\\"");
+        let variable = variable_name(track_idx);
+        writeln!(buf, "    \\new StaffGroup \\with {{ instrumentName = \"{name}\" }} <<")?;
+        writeln!(
+            buf,
+            "      \\new Staff \\with {{ \\omit StringNumber }} {{ \\clef \"treble_8\" \\{variable} }}"
+        )?;
+        writeln!(
+            buf,
+            "      \\new TabStaff \\with {{ stringTunings = \\stringTuning <{}> }} {{ \\{variable} }}",
+            strings.collect::<Vec<_>>().join(" ")
+        )?;
+        buf.push_str("    >>\n");
+    }
+    buf.push_str("  >>\n  \\layout { }\n}\n");
+    Ok(())
+}
@@ -1,11 +1,14 @@
-use std::{collections::HashMap, iter, time::Instant};
+use std::{iter, time::Instant};
 
 use midly::{
-    num::{u28, u7},
+    num::{u28, u4, u7},
     Format, Header, MetaMessage, MidiMessage, Smf, TrackEvent, TrackEventKind,
 };
 
-use super::{Backend, BackendResult};
+use super::{
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
 use crate::parser::parser::{parse, ParseResult};
 use crate::parser::tab_element::TabElement;
 use crate::parser::tab_element::TabElement::Fret;
@@ -67,64 +70,111 @@ impl Backend for MidiBackend {
     }
 }
 
-fn convert_to_midi(parsed: &ParseResult) -> Vec<Vec<TrackEvent<'static>>> {
-    // TODO: maybe use the traditional note resolving logic here?
-    let mut string_freq = HashMap::new();
-    string_freq.insert('E', 52);
-    string_freq.insert('A', 57);
-    string_freq.insert('D', 62);
-    string_freq.insert('G', 67);
-    string_freq.insert('B', 71);
-    string_freq.insert('d', 74);
-    string_freq.insert('e', 76);
-    let track_len = parsed.tick_stream.len() / 6;
-    // https://rust-lang.github.io/rust-clippy/master/index.html#repeat_vec_with_capacity
-    let mut tracks: Vec<Vec<TrackEvent>> =
-        iter::repeat_with(|| Vec::with_capacity(track_len)).take(6).collect();
-    let mut delta_carry_on = [u28::new(0); 6];
-    for (event_idx, event) in parsed.tick_stream.iter().enumerate() {
-        // TODO: eventually try to interpolate for slurred decorators
-        let track = event_idx % 6;
-        match &event {
-            Fret(fret) => {
-                let string_name = parsed.base_notes[track];
-                let pitch = fret + string_freq[&string_name];
-                let (note_on, note_off) = gen_note_events(pitch.into(), delta_carry_on[track]);
-                delta_carry_on[track] = 0.into();
-                tracks[track].push(note_on);
-                tracks[track].push(note_off);
+pub(crate) fn register(registry: &mut Registry) {
+    registry.register(SelectorBackend::new(
+        "midi",
+        "Writes a MIDI file, for playback",
+        &["mid", "midi", "smf"],
+        &[],
+        |_| Ok(BackendSelector::Midi),
+    ));
+}
+
+/// Produces six tracks (one per string) for every instrument in the score. Every instrument gets
+/// its own channel, skipping the percussion channel.
+fn convert_to_midi(parsed: &ParseResult) -> Vec<Vec<TrackEvent<'_>>> {
+    let score = parsed.score();
+    let mut tracks: Vec<Vec<TrackEvent>> = Vec::with_capacity(parsed.tracks.len() * 6);
+    for instrument in score.tracks() {
+        let channel = instrument_channel(instrument.index());
+        let track_len: usize = instrument.measures().map(|x| x.tick_count()).sum();
+        // https://rust-lang.github.io/rust-clippy/master/index.html#repeat_vec_with_capacity
+        let mut string_tracks: Vec<Vec<TrackEvent>> =
+            iter::repeat_with(|| Vec::with_capacity(track_len)).take(6).collect();
+        for track in string_tracks.iter_mut() {
+            track.push(TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::TrackName(instrument.name().as_bytes())),
+            });
+            track.push(TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::ProgramChange {
+                        program: instrument.midi_program().into(),
+                    },
+                },
+            });
+        }
+        let mut delta_carry_on = [u28::new(0); 6];
+        // The key that is still sounding on each string. A `~` after a note sustains it instead of
+        // starting a new one, so held and tied notes become a single note-on.
+        let mut held: [Option<u7>; 6] = [None; 6];
+        for tick in instrument.measures().flat_map(|x| x.ticks()) {
+            // TODO: eventually try to interpolate for slurred decorators
+            for (track, event) in tick.elements().iter().enumerate() {
+                let (track_events, held, delta_carry_on) =
+                    (&mut string_tracks[track], &mut held[track], &mut delta_carry_on[track]);
+                match &event {
+                    Fret(_) => {
+                        release_note(track_events, held, delta_carry_on, channel);
+                        let note = tick.note(track).unwrap();
+                        // played at the written pitch, an octave above the sounding pitch
+                        let pitch = (note.pitch() + 12).into();
+                        track_events.push(gen_note_event(pitch, *delta_carry_on, true, channel));
+                        *held = Some(pitch);
+                        *delta_carry_on = LENGTH_OF_EIGHTH.into();
+                    }
+                    TabElement::Vibrato if held.is_some() => {
+                        *delta_carry_on += LENGTH_OF_EIGHTH.into()
+                    }
+                    TabElement::Rest => {
+                        release_note(track_events, held, delta_carry_on, channel);
+                        *delta_carry_on += LENGTH_OF_EIGHTH.into()
+                    }
+                    TabElement::Bend
+                    | TabElement::HammerOn
+                    | TabElement::Pull
+                    | TabElement::Release
+                    | TabElement::Slide
+                    | TabElement::DeadNote
+                    | TabElement::Vibrato => (),
+                }
             }
-            TabElement::Rest => delta_carry_on[track] += LENGTH_OF_EIGHTH.into(),
-            TabElement::Bend
-            | TabElement::HammerOn
-            | TabElement::Pull
-            | TabElement::Release
-            | TabElement::Slide
-            | TabElement::DeadNote
-            | TabElement::Vibrato => (),
         }
+        for (track, track_events) in string_tracks.iter_mut().enumerate() {
+            release_note(track_events, &mut held[track], &mut delta_carry_on[track], channel);
+            track_events.push(TrackEvent {
+                delta: 0.into(),
+                kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+            })
+        }
+        tracks.append(&mut string_tracks);
     }
-    tracks.iter_mut().for_each(|x| {
-        x.push(TrackEvent { delta: 0.into(), kind: TrackEventKind::Meta(MetaMessage::EndOfTrack) })
-    });
     tracks
 }
 
-fn gen_note_events<'a>(key: u7, initial_delta: u28) -> (TrackEvent<'a>, TrackEvent<'a>) {
-    let note_on = TrackEvent {
-        delta: initial_delta,
-        kind: TrackEventKind::Midi {
-            channel: 0.into(),
-            message: MidiMessage::NoteOn { key, vel: 100.into() },
-        },
-    };
+/// Channel 10 (index 9) is reserved for percussion, so it is skipped
+fn instrument_channel(instrument_idx: usize) -> u4 {
+    let channel = if instrument_idx >= 9 { instrument_idx + 1 } else { instrument_idx };
+    u4::new((channel % 16) as u8)
+}
+
+/// Ends the note sounding on this string, if there is one
+fn release_note(
+    track: &mut Vec<TrackEvent>, held: &mut Option<u7>, delta_carry_on: &mut u28, channel: u4,
+) {
+    if let Some(key) = held.take() {
+        track.push(gen_note_event(key, *delta_carry_on, false, channel));
+        *delta_carry_on = 0.into();
+    }
+}
 
-    let note_off = TrackEvent {
-        delta: LENGTH_OF_EIGHTH.into(),
-        kind: TrackEventKind::Midi {
-            channel: 0.into(),
-            message: MidiMessage::NoteOff { key, vel: 100.into() },
-        },
+fn gen_note_event<'a>(key: u7, delta: u28, on: bool, channel: u4) -> TrackEvent<'a> {
+    let message = if on {
+        MidiMessage::NoteOn { key, vel: 100.into() }
+    } else {
+        MidiMessage::NoteOff { key, vel: 100.into() }
     };
-    (note_on, note_off)
+    TrackEvent { delta, kind: TrackEventKind::Midi { channel, message } }
 }
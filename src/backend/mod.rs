@@ -1,10 +1,30 @@
 use errors::{backend_error::BackendError, diagnostic::Diagnostic};
 
-use std::{fmt::Display, time::Duration};
+use itertools::Itertools;
+
+use crate::import;
+
+use std::{fmt::Display, ops::RangeInclusive, time::Duration};
+pub mod abc;
+pub mod alphatex;
+pub mod audio;
 pub mod errors;
 pub mod fixup;
+pub mod format;
+pub mod gp5;
+pub mod html;
+pub mod json;
+pub mod lilypond;
 pub mod midi;
 pub mod muxml;
+pub mod refinger;
+pub mod registry;
+#[cfg(test)]
+mod registry_tests;
+pub mod retune;
+pub mod svg;
+pub mod transpose;
+pub mod vextab;
 pub struct BackendResult {
     pub diagnostics: Vec<Diagnostic>,
     pub err: Option<BackendError>,
@@ -19,6 +39,19 @@ impl BackendResult {
         Self { diagnostics, err, timing_parse, timing_gen }
     }
 }
+
+/// The tempos the backends which play a tab or store its tempo accept, in quarter notes per minute
+pub const TEMPOS: RangeInclusive<u32> = 1..=1000;
+
+/// Fails unless `tempo` is one of [TEMPOS]
+pub(crate) fn check_tempo(tempo: u32) -> Result<(), BackendError> {
+    if TEMPOS.contains(&tempo) {
+        return Ok(());
+    }
+    let (min, max) = TEMPOS.into_inner();
+    Err(BackendError::invalid_option(format!("tempo should be from {min} to {max}")))
+}
+
 pub trait Backend {
     type BackendSettings;
 
@@ -27,6 +60,23 @@ pub trait Backend {
     ) -> BackendResult;
 }
 
+/// The tabs in `input/` the text backends are snapshot tested with. Between them they have
+/// several tracks, bends and releases, ties over barlines and dead notes.
+#[cfg(test)]
+pub(crate) const INPUT_TABS: [&str; 5] = ["c_major", "multi_track", "bends", "ties", "techniques"];
+
+/// Runs `B` on one of the tabs in `input/` and returns what it wrote
+#[cfg(test)]
+pub(crate) fn process_input_file<B: Backend>(name: &str, settings: B::BackendSettings) -> String {
+    let path = format!("input/{name}.tab");
+    let input = std::fs::read_to_string(&path).unwrap();
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut out = vec![];
+    let res = B::process(&lines, &mut out, settings);
+    assert!(res.err.is_none(), "{path}: {:?}", res.err);
+    String::from_utf8(out).unwrap()
+}
+
 /// Handles backend dispatch. Can be easily created from a string identifier.
 ///
 /// The primary usage of this struct is to idiomatically call a backend if you use scoreman as a library,
@@ -39,30 +89,139 @@ pub trait Backend {
 #[derive(Clone)]
 pub enum BackendSelector {
     Midi,
+    /// Writes the parsed score as JSON
+    Json,
     Muxml(muxml::settings::Settings),
+    /// Writes a tune in ABC notation
+    Abc(abc::AbcSettings),
+    /// Writes alphaTex, for rendering with alphaTab
+    AlphaTex(alphatex::AlphaTexSettings),
+    /// Writes VexTab, for rendering with VexFlow
+    VexTab(vextab::VexTabSettings),
+    /// Renders the tab to a WAV file with a plucked string synth
+    Audio(audio::AudioSettings),
+    /// Draws the tab as an SVG image
+    Svg(svg::SvgSettings),
+    /// Writes a Guitar Pro 5 file
+    Gp5(gp5::Gp5Settings),
+    /// Writes an HTML page with the tab and a player
+    Html(html::HtmlSettings),
+    /// Writes a LilyPond file with a staff and a tab staff
+    Lilypond(lilypond::LilypondSettings),
     Fixup(fixup::FixupBackendSettings),
+    /// Rewrites a tab in a canonical layout
+    Format(format::FormatSettings),
+    /// Transposes a tab, keeping its tuning
+    Transpose(transpose::TransposeSettings),
+    /// Rewrites a tab for another tuning
+    Retune(retune::RetuneSettings),
+    /// Moves the notes of a tab to the strings that are easiest to play
+    Refinger(crate::fingering::optimize::FingeringSettings),
+    /// Reads MusicXML and writes a tab
+    MuxmlImport(import::ImportSettings),
+    /// Reads the JSON written by [BackendSelector::Json] and writes a tab
+    JsonImport(import::ir::JsonImportSettings),
+    /// Reads a MIDI file and writes a tab. Its input is binary, see [BackendSelector::process_bytes].
+    MidiImport(import::midi::MidiImportSettings),
 }
 
 impl BackendSelector {
     pub fn process<Out: std::io::Write>(self, input: &[String], out: &mut Out) -> BackendResult {
         match self {
             BackendSelector::Midi => midi::MidiBackend::process(input, out, ()),
+            BackendSelector::Json => json::JsonBackend::process(input, out, ()),
             BackendSelector::Muxml(settings) => muxml::MuxmlBackend::process(input, out, settings),
+            BackendSelector::Abc(settings) => abc::AbcBackend::process(input, out, settings),
+            BackendSelector::AlphaTex(settings) => {
+                alphatex::AlphaTexBackend::process(input, out, settings)
+            }
+            BackendSelector::VexTab(settings) => {
+                vextab::VexTabBackend::process(input, out, settings)
+            }
+            BackendSelector::Audio(settings) => audio::AudioBackend::process(input, out, settings),
+            BackendSelector::Svg(settings) => svg::SvgBackend::process(input, out, settings),
+            BackendSelector::Gp5(settings) => gp5::Gp5Backend::process(input, out, settings),
+            BackendSelector::Html(settings) => html::HtmlBackend::process(input, out, settings),
+            BackendSelector::Lilypond(settings) => {
+                lilypond::LilypondBackend::process(input, out, settings)
+            }
             BackendSelector::Fixup(settings) => fixup::FixupBackend::process(input, out, settings),
+            BackendSelector::Format(settings) => {
+                format::FormatBackend::process(input, out, settings)
+            }
+            BackendSelector::Transpose(settings) => {
+                transpose::TransposeBackend::process(input, out, settings)
+            }
+            BackendSelector::Retune(settings) => {
+                retune::RetuneBackend::process(input, out, settings)
+            }
+            BackendSelector::Refinger(settings) => {
+                refinger::RefingerBackend::process(input, out, settings)
+            }
+            BackendSelector::MuxmlImport(settings) => {
+                import::musicxml::MuxmlImportBackend::process(input, out, settings)
+            }
+            BackendSelector::JsonImport(settings) => {
+                import::ir::JsonImportBackend::process(input, out, settings)
+            }
+            // a binary file doesn't survive being read as lines
+            BackendSelector::MidiImport(_) => {
+                let message = "MIDI input is binary, it has to be given to process_bytes";
+                let err = BackendError::invalid_option(message.to_string());
+                BackendResult::new(vec![], Some(err), None, None)
+            }
+        }
+    }
+
+    /// The name of the backend in [registry::Registry::with_builtins]
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackendSelector::Midi => "midi",
+            BackendSelector::Json => "json",
+            BackendSelector::Muxml(_) => "muxml",
+            BackendSelector::Lilypond(_) => "lilypond",
+            BackendSelector::Abc(_) => "abc",
+            BackendSelector::AlphaTex(_) => "alphatex",
+            BackendSelector::VexTab(_) => "vextab",
+            BackendSelector::Gp5(_) => "gp5",
+            BackendSelector::Html(_) => "html",
+            BackendSelector::Svg(_) => "svg",
+            BackendSelector::Audio(_) => "audio",
+            BackendSelector::Fixup(_) => "fixup",
+            BackendSelector::Format(_) => "fmt",
+            BackendSelector::Transpose(_) => "transpose",
+            BackendSelector::Retune(_) => "retune",
+            BackendSelector::Refinger(_) => "refinger",
+            BackendSelector::MuxmlImport(_) => "from-muxml",
+            BackendSelector::JsonImport(_) => "from-json",
+            BackendSelector::MidiImport(_) => "from-midi",
+        }
+    }
+
+    /// Whether the input of this backend has to be given to [BackendSelector::process_bytes]
+    /// instead of being read as lines
+    pub fn input_is_binary(&self) -> bool {
+        matches!(self, BackendSelector::MidiImport(_))
+    }
+
+    /// Like [BackendSelector::process], but for the raw contents of the input file. Text is split
+    /// into lines for the backends which read text.
+    pub fn process_bytes<Out: std::io::Write>(self, input: &[u8], out: &mut Out) -> BackendResult {
+        match self {
+            BackendSelector::MidiImport(settings) => {
+                import::midi::MidiImportBackend::process_bytes(input, out, settings)
+            }
+            _ => {
+                let lines =
+                    String::from_utf8_lossy(input).lines().map(|x| x.to_string()).collect_vec();
+                self.process(&lines, out)
+            }
         }
     }
 }
 
 impl Display for BackendSelector {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                BackendSelector::Midi => "midi",
-                BackendSelector::Muxml(_) => "muxml",
-                BackendSelector::Fixup(_) => "fixup",
-            }
-        )
+        write!(f, "{}", self.name())
     }
 }
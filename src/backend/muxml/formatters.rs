@@ -1,4 +1,8 @@
-use crate::backend::muxml::{NoteProperties, Vibrato};
+use crate::backend::muxml::{
+    key::{Key, Spelling},
+    settings::{Clef, Meter, OctaveMode, Settings},
+    Beam, NoteProperties, Tie,
+};
 use crate::debugln;
 use itoa::Buffer;
 // This file uses explicit .write_str() -s, instead of writing a format!()ted string, because I
@@ -6,7 +10,7 @@ use itoa::Buffer;
 // Maybe there is a nice solution to this - but I've yet to find anything as performant as this one.
 #[inline]
 pub fn write_muxml2_rest(
-    buf: &mut impl std::fmt::Write, r#type: &str, duration: u8,
+    buf: &mut impl std::fmt::Write, r#type: &str, duration: u8, voice: u8,
 ) -> Result<(), std::fmt::Error> {
     buf.write_str(
         r#"<note>
@@ -15,19 +19,45 @@ pub fn write_muxml2_rest(
     )?;
     let mut dbuf = Buffer::new();
     buf.write_str(dbuf.format(duration))?;
-    buf.write_str(
-        r#"</duration>
-<voice>1</voice>
-<type>"#,
-    )?;
+    buf.write_str("</duration>\n<voice>")?;
+    buf.write_str(dbuf.format(voice))?;
+    buf.write_str("</voice>\n<type>")?;
     buf.write_str(r#type)?;
     buf.write_str("</type>\n</note>\n")?;
     Ok(())
 }
 
+/// A rest which fills the whole measure, whatever its length is
 #[inline]
+pub fn write_muxml2_measure_rest(
+    buf: &mut impl std::fmt::Write, duration: u32, voice: u8,
+) -> Result<(), std::fmt::Error> {
+    buf.write_str("<note>\n<rest measure=\"yes\"/>\n<duration>")?;
+    let mut dbuf = Buffer::new();
+    buf.write_str(dbuf.format(duration))?;
+    buf.write_str("</duration>\n<voice>")?;
+    buf.write_str(dbuf.format(voice))?;
+    buf.write_str("</voice>\n</note>\n")?;
+    Ok(())
+}
+
+/// Moves back to the start of the measure, so that the next voice can be written
+#[inline]
+pub fn write_muxml2_backup(
+    buf: &mut impl std::fmt::Write, duration: u32,
+) -> Result<(), std::fmt::Error> {
+    buf.write_str("<backup><duration>")?;
+    let mut dbuf = Buffer::new();
+    buf.write_str(dbuf.format(duration))?;
+    buf.write_str("</duration></backup>\n")?;
+    Ok(())
+}
+
+#[inline]
+#[allow(clippy::too_many_arguments)]
 pub fn write_muxml2_note(
-    buf: &mut impl std::fmt::Write, step: char, octave: u8, sharp: bool, chord: bool, dead: bool,
+    buf: &mut impl std::fmt::Write, spelling: Spelling, accidental: Option<&str>, chord: bool,
+    dead: bool, duration: u32, beam: Option<Beam>, voice: Option<u8>,
     properties: Option<&NoteProperties>,
 ) -> Result<(), std::fmt::Error> {
     buf.write_str("<note>\n")?;
@@ -35,32 +65,70 @@ pub fn write_muxml2_note(
         buf.write_str("<chord/>\n")?
     }
     buf.write_str("<pitch><step>")?;
-    buf.write_char(step)?;
+    buf.write_char(spelling.step)?;
     buf.write_str("</step>\n")?;
-    if sharp {
-        buf.write_str("<alter>1</alter>\n")?
+    let mut octave_buf = itoa::Buffer::new();
+    if spelling.alter != 0 {
+        buf.write_str("<alter>")?;
+        buf.write_str(octave_buf.format(spelling.alter))?;
+        buf.write_str("</alter>\n")?
     }
     buf.write_str("<octave>")?;
-    let mut octave_buf = itoa::Buffer::new();
-    buf.write_str(octave_buf.format(octave))?;
+    buf.write_str(octave_buf.format(spelling.octave))?;
     buf.write_str("</octave>\n")?;
-    buf.write_str(
-        r#"</pitch>
-<duration>1</duration>
-<type>eighth</type>
-"#,
-    )?;
-    if sharp {
-        buf.write_str("<accidental>sharp</accidental>\n")?;
+    buf.write_str("</pitch>\n<duration>")?;
+    buf.write_str(octave_buf.format(duration))?;
+    buf.write_str("</duration>\n")?;
+    let tie = properties.and_then(|x| x.tie);
+    if let Some(tie) = tie {
+        write_muxml2_ties(buf, "tie", tie)?;
+    }
+    if let Some(voice) = voice {
+        buf.write_str("<voice>")?;
+        buf.write_str(octave_buf.format(voice))?;
+        buf.write_str("</voice>\n")?;
+    }
+    let (note_type, dotted) = match duration {
+        1 => ("eighth", false),
+        2 => ("quarter", false),
+        3 => ("quarter", true),
+        4 => ("half", false),
+        6 => ("half", true),
+        8 => ("whole", false),
+        _ => ("whole", true),
+    };
+    buf.write_str("<type>")?;
+    buf.write_str(note_type)?;
+    buf.write_str("</type>\n")?;
+    if dotted {
+        buf.write_str("<dot/>\n")?;
+    }
+    if let Some(accidental) = accidental {
+        buf.write_str("<accidental>")?;
+        buf.write_str(accidental)?;
+        buf.write_str("</accidental>\n")?;
+    }
+    if let Some(voice) = voice {
+        buf.write_str(if voice == 1 { "<stem>up</stem>\n" } else { "<stem>down</stem>\n" })?;
     }
     if dead {
         buf.write_str("<notehead>x</notehead>\n")?;
     }
+    if let Some(beam) = beam {
+        buf.write_str(match beam {
+            Beam::Begin => "<beam number=\"1\">begin</beam>\n",
+            Beam::Continue => "<beam number=\"1\">continue</beam>\n",
+            Beam::End => "<beam number=\"1\">end</beam>\n",
+        })?;
+    }
     match properties {
         None => (),
-        Some(NoteProperties { slurs, slide, vibrato }) => {
+        Some(NoteProperties { slurs, slide, vibrato, tie }) => {
             debugln!("slurs: {slurs:?}");
             buf.write_str("<notations>\n")?;
+            if let Some(tie) = tie {
+                write_muxml2_ties(buf, "tied", *tie)?;
+            }
             for slur in slurs {
                 buf.write_str(r#"<slur type=""#)?;
                 buf.write_str(if slur.start { "start" } else { "stop" })?;
@@ -77,9 +145,12 @@ pub fn write_muxml2_note(
             }
             if let Some(vibrato) = vibrato {
                 buf.write_str("<ornaments>\n")?;
-                buf.write_str("<wavy-line type=\"")?;
-                buf.write_str(if matches!(vibrato, Vibrato::Start) { "start" } else { "stop" })?;
-                buf.write_str("\" />\n")?;
+                if vibrato.starts() {
+                    buf.write_str("<wavy-line type=\"start\" />\n")?;
+                }
+                if vibrato.stops() {
+                    buf.write_str("<wavy-line type=\"stop\" />\n")?;
+                }
                 buf.write_str("</ornaments>\n")?;
             }
             buf.write_str("</notations>\n")?;
@@ -88,32 +159,104 @@ pub fn write_muxml2_note(
     buf.write_str("</note>\n")?;
     Ok(())
 }
+/// Writes `<tie>` or `<tied>` elements, stop first, as MusicXML expects
+#[inline]
+fn write_muxml2_ties(buf: &mut impl std::fmt::Write, element: &str, tie: Tie) -> std::fmt::Result {
+    if tie.stops() {
+        buf.write_char('<')?;
+        buf.write_str(element)?;
+        buf.write_str(" type=\"stop\"/>\n")?;
+    }
+    if tie.starts() {
+        buf.write_char('<')?;
+        buf.write_str(element)?;
+        buf.write_str(" type=\"start\"/>\n")?;
+    }
+    Ok(())
+}
+#[inline]
+pub fn write_muxml2_score_part(
+    buf: &mut impl std::fmt::Write, track_idx: usize, name: &str,
+) -> Result<(), std::fmt::Error> {
+    buf.write_str("    <score-part id=\"P")?;
+    let mut nbuf = Buffer::new();
+    buf.write_str(nbuf.format(track_idx + 1))?;
+    buf.write_str("\">\n      <part-name>")?;
+    write_xml_escaped(buf, name)?;
+    buf.write_str("</part-name>\n    </score-part>\n")?;
+    Ok(())
+}
+
+#[inline]
+pub fn write_muxml2_part_start(
+    buf: &mut impl std::fmt::Write, track_idx: usize,
+) -> Result<(), std::fmt::Error> {
+    buf.write_str("  <part id=\"P")?;
+    let mut nbuf = Buffer::new();
+    buf.write_str(nbuf.format(track_idx + 1))?;
+    buf.write_str("\">\n")?;
+    Ok(())
+}
+
+pub fn write_xml_escaped(buf: &mut impl std::fmt::Write, s: &str) -> Result<(), std::fmt::Error> {
+    for c in s.chars() {
+        match c {
+            '&' => buf.write_str("&amp;")?,
+            '<' => buf.write_str("&lt;")?,
+            '>' => buf.write_str("&gt;")?,
+            '"' => buf.write_str("&quot;")?,
+            _ => buf.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Only writes `<attributes>` if something changes in this measure
 #[inline]
 pub fn write_muxml2_measure_prelude(
-    buf: &mut impl std::fmt::Write, number: usize, note_count: usize, note_type: usize,
+    buf: &mut impl std::fmt::Write, number: usize, time: Option<Meter>, key: Key,
+    multi_rest: Option<usize>, settings: &Settings,
 ) -> Result<(), std::fmt::Error> {
     let first_measure = number == 0;
     buf.write_str(r#"<measure number=""#)?;
     let mut nbuf = Buffer::new();
     buf.write_str(nbuf.format(number))?;
-    buf.write_str(
-        r#"">
-<attributes>
-<divisions>2</divisions>
-"#,
-    )?;
+    buf.write_str("\">\n")?;
+    if !first_measure && time.is_none() && multi_rest.is_none() {
+        return Ok(());
+    }
+    buf.write_str("<attributes>\n")?;
     if first_measure {
-        buf.write_str("<key><fifths>0</fifths></key>\n")?
+        buf.write_str("<divisions>2</divisions>\n<key><fifths>")?;
+        buf.write_str(nbuf.format(key.fifths))?;
+        buf.write_str("</fifths></key>\n")?
     };
-    buf.write_str("<time><beats>")?;
-    let mut note_count_buf = Buffer::new();
-    buf.write_str(note_count_buf.format(note_count))?;
-    buf.write_str("</beats><beat-type>")?;
-    let mut note_type_buf = Buffer::new();
-    buf.write_str(note_type_buf.format(note_type))?;
-    buf.write_str("</beat-type></time>\n")?;
+    if let Some(time) = time {
+        buf.write_str("<time><beats>")?;
+        buf.write_str(nbuf.format(time.beats))?;
+        buf.write_str("</beats><beat-type>")?;
+        buf.write_str(nbuf.format(time.beat_type))?;
+        buf.write_str("</beat-type></time>\n")?;
+    }
     if first_measure {
-        buf.write_str("<clef><sign>G</sign><line>2</line></clef>\n")?
+        buf.write_str(match settings.clef {
+            Clef::Treble => "<clef><sign>G</sign><line>2</line>",
+            Clef::Bass => "<clef><sign>F</sign><line>4</line>",
+        })?;
+        if settings.octave == OctaveMode::Sounding {
+            buf.write_str("<clef-octave-change>-1</clef-octave-change>")?;
+        }
+        buf.write_str("</clef>\n")?;
+        if settings.octave == OctaveMode::Transposing {
+            buf.write_str(
+                "<transpose><diatonic>0</diatonic><chromatic>0</chromatic><octave-change>-1</octave-change></transpose>\n",
+            )?;
+        }
+    }
+    if let Some(multi_rest) = multi_rest {
+        buf.write_str("<measure-style><multiple-rest>")?;
+        buf.write_str(nbuf.format(multi_rest))?;
+        buf.write_str("</multiple-rest></measure-style>\n")?;
     }
     buf.write_str("</attributes>\n")?;
     Ok(())
@@ -133,13 +276,7 @@ pub const MUXML_INCOMPLETE_DOC_PRELUDE: &str = r#"
     </encoding>
   </identification>
   <part-list>
-    <score-part id="P1">
-      <part-name>Guitar1</part-name>
-    </score-part>
-  </part-list>
-  <part id="P1">
-"#;
-pub const MUXML2_DOCUMENT_END: &str = r#"
-</part>
-</score-partwise>
 "#;
+pub const MUXML2_PART_LIST_END: &str = "  </part-list>\n";
+pub const MUXML2_PART_END: &str = "\n</part>\n";
+pub const MUXML2_DOCUMENT_END: &str = "</score-partwise>\n";
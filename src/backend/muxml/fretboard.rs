@@ -1,20 +1,7 @@
+use super::key::{Key, Spelling};
 use crate::backend::errors::backend_error::BackendError;
 
-const NOTE2_STEPS: [(char, bool); 12] = [
-    ('C', false),
-    ('C', true),
-    ('D', false),
-    ('D', true),
-    ('E', false),
-    ('F', false),
-    ('F', true),
-    ('G', false),
-    ('G', true),
-    ('A', false),
-    ('A', true),
-    ('B', false),
-];
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct MuxmlNote2 {
     /// Numeric representation of the frequency.
     ///
@@ -28,10 +15,8 @@ pub struct MuxmlNote2 {
     pub dead: bool,
 }
 impl MuxmlNote2 {
-    pub fn step_octave_sharp(&self) -> (char, u8, bool) {
-        let stepidx = (self.step % 12) as usize;
-        let octave = self.step / 12;
-        (NOTE2_STEPS[stepidx].0, octave, NOTE2_STEPS[stepidx].1)
+    pub fn spell(&self, key: &Key) -> Spelling {
+        key.spell(self.step)
     }
 }
 
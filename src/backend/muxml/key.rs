@@ -0,0 +1,197 @@
+use std::str::FromStr;
+
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+/// The pitch class of the natural notes in [LETTERS]
+const LETTER_PITCH_CLASSES: [i8; 7] = [0, 2, 4, 5, 7, 9, 11];
+/// Indices into [LETTERS] in the order sharps are added to a key signature: F C G D A E B.
+/// Flats are added in the reverse order.
+const SHARP_ORDER: [usize; 7] = [3, 0, 4, 1, 5, 2, 6];
+
+/// A major key signature (or its relative minor), stored the same way as MusicXML's `<fifths>`:
+/// positive for sharps, negative for flats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Key {
+    pub fifths: i8,
+}
+
+/// How a pitch is written down: letter, alteration in semitones and octave
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spelling {
+    pub step: char,
+    pub alter: i8,
+    pub octave: u8,
+}
+
+impl Key {
+    pub fn new(fifths: i8) -> Self {
+        Self { fifths }
+    }
+
+    /// The alteration the key signature applies to the letter at `letter_idx` in `CDEFGAB`
+    pub fn letter_alter(&self, letter_idx: usize) -> i8 {
+        let pos = SHARP_ORDER.iter().position(|x| *x == letter_idx).unwrap() as i8;
+        if self.fifths > 0 && pos < self.fifths {
+            1
+        } else if self.fifths < 0 && 6 - pos < -self.fifths {
+            -1
+        } else {
+            0
+        }
+    }
+
+    /// The tonic of the major key as an index into `CDEFGAB` and its alteration
+    pub fn tonic(&self) -> (usize, i8) {
+        let letter_idx = (self.fifths as i32 * 4).rem_euclid(7) as usize;
+        (letter_idx, self.letter_alter(letter_idx))
+    }
+
+    fn is_diatonic(&self, pitch_class: i8) -> Option<(usize, i8)> {
+        (0..7)
+            .map(|l| (l, self.letter_alter(l)))
+            .find(|(l, alter)| (LETTER_PITCH_CLASSES[*l] + alter).rem_euclid(12) == pitch_class)
+    }
+
+    /// Spells `step` (see [super::fretboard::MuxmlNote2::step]) in this key.
+    ///
+    /// Notes of the scale are spelled as the key signature does it, naturals stay naturals, and
+    /// the remaining notes are spelled with sharps in sharp keys and with flats in flat keys.
+    pub fn spell(&self, step: u8) -> Spelling {
+        let pitch_class = (step % 12) as i8;
+        let (letter_idx, alter) = self.is_diatonic(pitch_class).unwrap_or_else(|| {
+            if let Some(natural) = LETTER_PITCH_CLASSES.iter().position(|x| *x == pitch_class) {
+                (natural, 0)
+            } else if self.fifths >= 0 {
+                let below = pitch_class - 1;
+                (LETTER_PITCH_CLASSES.iter().position(|x| *x == below).unwrap(), 1)
+            } else {
+                let above = pitch_class + 1;
+                (LETTER_PITCH_CLASSES.iter().position(|x| *x == above).unwrap(), -1)
+            }
+        });
+        // B# and Cb belong to the neighbouring octave
+        let octave = ((step as i16 - alter as i16) / 12) as u8;
+        Spelling { step: LETTERS[letter_idx], alter, octave }
+    }
+
+    /// Picks the key signature whose scale covers most of the notes in the pitch class histogram.
+    /// On a tie, the key with fewer accidentals wins.
+    pub fn detect(histogram: &[u32; 12]) -> Self {
+        (-6..=6)
+            .map(Key::new)
+            .max_by_key(|key| {
+                let in_key: u32 = (0..12)
+                    .filter(|pc| key.is_diatonic(*pc as i8).is_some())
+                    .map(|pc| histogram[pc])
+                    .sum();
+                (in_key, -key.fifths.abs(), key.fifths)
+            })
+            .unwrap()
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    /// Accepts a number of fifths (`-1`, `3`), or a key name like `F`, `Bb`, `F#m`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(fifths) = s.parse::<i8>() {
+            return match fifths {
+                -7..=7 => Ok(Key::new(fifths)),
+                _ => Err(format!("A key signature has at most 7 accidentals, got {fifths}")),
+            };
+        }
+        let err = || format!("Unknown key {s}, expected something like C, Bb, F#m or -2");
+        let mut chars = s.chars();
+        let letter = chars.next().ok_or_else(err)?.to_ascii_uppercase();
+        let letter_idx = LETTERS.iter().position(|x| *x == letter).ok_or_else(err)?;
+        let rest = chars.as_str();
+        let (alter, minor) = match rest {
+            "" => (0, false),
+            "m" => (0, true),
+            "#" => (1, false),
+            "#m" => (1, true),
+            "b" => (-1, false),
+            "bm" => (-1, true),
+            _ => return Err(err()),
+        };
+        (-7..=7)
+            .map(Key::new)
+            .find(|key| {
+                let (tonic, _) = key.tonic();
+                // the relative minor is a sixth above the major tonic
+                let tonic = if minor { (tonic + 5) % 7 } else { tonic };
+                tonic == letter_idx && key.letter_alter(tonic) == alter
+            })
+            .ok_or_else(err)
+    }
+}
+
+/// Tracks the accidentals in the current measure, so that `<accidental>` is only written when a
+/// note differs from the key signature or from an earlier accidental on the same line.
+pub struct AccidentalState {
+    key: Key,
+    /// Indexed by `octave * 7 + letter`
+    current: Vec<Option<i8>>,
+}
+
+impl AccidentalState {
+    pub fn new(key: Key) -> Self {
+        Self { key, current: vec![] }
+    }
+
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Call this at every barline
+    pub fn reset(&mut self) {
+        self.current.clear();
+    }
+
+    /// Returns the accidental that has to be displayed for this note, if any
+    pub fn accidental(&mut self, spelling: &Spelling) -> Option<&'static str> {
+        let letter_idx = LETTERS.iter().position(|x| *x == spelling.step).unwrap();
+        let slot = spelling.octave as usize * 7 + letter_idx;
+        if self.current.len() <= slot {
+            self.current.resize(slot + 1, None);
+        }
+        let expected = self.current[slot].unwrap_or_else(|| self.key.letter_alter(letter_idx));
+        if expected == spelling.alter {
+            return None;
+        }
+        self.current[slot] = Some(spelling.alter);
+        Some(match spelling.alter {
+            -2 => "flat-flat",
+            -1 => "flat",
+            0 => "natural",
+            1 => "sharp",
+            _ => "double-sharp",
+        })
+    }
+}
+
+#[test]
+fn test_key_spelling() {
+    let f_major: Key = "F".parse().unwrap();
+    assert_eq!(f_major, Key::new(-1));
+    // A#/Bb in F major
+    assert_eq!(f_major.spell(58), Spelling { step: 'B', alter: -1, octave: 4 });
+    // C#/Db is outside of F major, so it is spelled with a flat
+    assert_eq!(f_major.spell(61), Spelling { step: 'D', alter: -1, octave: 5 });
+    let c_sharp_major = Key::new(7);
+    assert_eq!(c_sharp_major.spell(60), Spelling { step: 'B', alter: 1, octave: 4 });
+    assert_eq!("F#m".parse::<Key>().unwrap(), Key::new(3));
+    assert_eq!("Bb".parse::<Key>().unwrap(), Key::new(-2));
+    assert!("H".parse::<Key>().is_err());
+}
+
+#[test]
+fn test_key_detect() {
+    let mut histogram = [0; 12];
+    // Bb major scale
+    for pc in [10, 0, 2, 3, 5, 7, 9] {
+        histogram[pc] += 1;
+    }
+    assert_eq!(Key::detect(&histogram), Key::new(-2));
+    assert_eq!(Key::detect(&[0; 12]), Key::new(0));
+}
@@ -1,23 +1,34 @@
 pub mod formatters;
 pub mod fretboard;
+pub mod key;
 #[cfg(test)]
 mod muxml2_tests;
 pub mod settings;
-use crate::backend::errors::backend_error::BackendError;
+use crate::backend::errors::{
+    backend_error::BackendError, diagnostic::Diagnostic, diagnostic_kind::DiagnosticKind,
+    error_location::ErrorLocation,
+};
 use crate::parser::parser;
 use crate::parser::parser::{source_location_from_stream, ParseResult};
 use crate::parser::tab_element::TabElement;
 use crate::{
-    backend::{Backend, BackendResult},
+    backend::{
+        registry::{Registry, SelectorBackend},
+        Backend, BackendResult, BackendSelector,
+    },
     debugln, rlen, time, traceln,
 };
 use formatters::{
-    write_muxml2_measure_prelude, write_muxml2_note, write_muxml2_rest, MUXML2_DOCUMENT_END,
-    MUXML_INCOMPLETE_DOC_PRELUDE,
+    write_muxml2_backup, write_muxml2_measure_prelude, write_muxml2_measure_rest,
+    write_muxml2_note, write_muxml2_part_start, write_muxml2_rest, write_muxml2_score_part,
+    MUXML2_DOCUMENT_END, MUXML2_PART_END, MUXML2_PART_LIST_END, MUXML_INCOMPLETE_DOC_PRELUDE,
 };
-use fretboard::get_fretboard_note2;
+use fretboard::MuxmlNote2;
+use itertools::Itertools;
+use key::{AccidentalState, Key};
 use rustc_hash::FxBuildHasher;
-use std::collections::HashMap;
+use settings::{Meter, Settings, VoiceSplit};
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
 pub struct MuxmlBackend();
@@ -45,20 +56,56 @@ impl Backend for MuxmlBackend {
     }
 }
 
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("trim-measure", "Remove the rests before and after the content of a measure"),
+        ("remove-rest-between-notes", "Remove rests which only separate notes"),
+        ("simplify-time-signature", "Simplify time signatures, e.g. 8/8 -> 4/4"),
+        ("key", "Key signature, like `Bb` or `-1`. Detected if not given."),
+        ("clef", "treble or bass, treble by default"),
+        ("octave", "plain, sounding or transposing, plain by default"),
+        ("multi-measure-rests", "Group consecutive empty measures"),
+        ("meter", "The time signature, like 3/4. Detected if not given."),
+        ("voice-split", "A string number or a pitch to split the staff into two voices at"),
+    ];
+    registry.register(SelectorBackend::new(
+        "muxml",
+        "Writes a MusicXML score",
+        &["musicxml", "xml"],
+        options,
+        |o| {
+            Ok(BackendSelector::Muxml(Settings {
+                remove_rest_between_notes: o.flag("remove-rest-between-notes")?,
+                trim_measure: o.flag("trim-measure")?,
+                simplify_time_signature: o.flag("simplify-time-signature")?,
+                key: o.get("key")?,
+                clef: o.choice("clef")?.unwrap_or_default(),
+                octave: o.choice("octave")?.unwrap_or_default(),
+                multi_measure_rests: o.flag("multi-measure-rests")?,
+                meter: o.get("meter")?,
+                voice_split: o.get("voice-split")?,
+            }))
+        },
+    ));
+}
+
 #[derive(Debug)]
 pub enum Muxml2TabElement {
     Rest(u32),
-    CopyTick(u32),
+    /// A rest filling the whole measure
+    MeasureRest(u32),
+    /// The notes of the tick starting at a stream index, and how many eighths they are held for
+    CopyTick(u32, u32),
     /// used in optimizing, should generate no code for this type
     Invalid,
 }
 
 #[inline(always)]
-fn write_rest(buf: &mut impl std::fmt::Write, mut x: u32) -> std::fmt::Result {
+fn write_rest(buf: &mut impl std::fmt::Write, mut x: u32, voice: u8) -> std::fmt::Result {
     while x != 0 {
         for bound in [(8, "whole"), (4, "half"), (2, "quarter"), (1, "eighth")] {
             if x >= bound.0 {
-                write_muxml2_rest(buf, bound.1, bound.0 as u8)?;
+                write_muxml2_rest(buf, bound.1, bound.0 as u8, voice)?;
                 x -= bound.0;
             }
         }
@@ -66,43 +113,80 @@ fn write_rest(buf: &mut impl std::fmt::Write, mut x: u32) -> std::fmt::Result {
     Ok(())
 }
 
+/// Splits a held note into lengths that can be written as a single note, which are then tied
+fn note_lengths(mut x: u32) -> Vec<u32> {
+    let mut lengths = vec![];
+    while x != 0 {
+        let len = [12, 8, 6, 4, 3, 2, 1].into_iter().find(|len| *len <= x).unwrap();
+        lengths.push(len);
+        x -= len;
+    }
+    lengths
+}
+
+/// Everything the elements of a measure need to write themselves
+struct WriteContext<'a> {
+    parsed: &'a ParseResult,
+    note_properties: &'a HashMap<u32, NoteProperties, FxBuildHasher>,
+    accidentals: AccidentalState,
+    pitch_shift: u8,
+    voice_split: Option<VoiceSplit>,
+}
+
+impl WriteContext<'_> {
+    /// The notes of the tick starting at `tick_idx` which belong to `voice`, with their stream index
+    fn tick_notes(
+        &self, tick_idx: u32, voice: u8,
+    ) -> impl Iterator<Item = (usize, MuxmlNote2)> + '_ {
+        let tick = self.parsed.score().tick(tick_idx as usize / 6).unwrap();
+        tick.notes()
+            .map(|note| {
+                let step = note.pitch() - self.pitch_shift;
+                (note.stream_idx(), MuxmlNote2 { step, dead: note.is_dead() })
+            })
+            .filter(move |(elem_idx, note)| match self.voice_split {
+                None => true,
+                Some(split) => split.voice_of(elem_idx % 6, note.step) == voice,
+            })
+    }
+}
+
 impl Muxml2TabElement {
     fn write_muxml<A: std::fmt::Write>(
-        &self, parsed: &ParseResult, buf: &mut A,
-        note_properties: &HashMap<u32, NoteProperties, impl std::hash::BuildHasher>,
+        &self, ctx: &mut WriteContext, buf: &mut A, beam: Option<Beam>, voice: u8,
     ) -> std::fmt::Result {
         match self {
-            Muxml2TabElement::Rest(x) => write_rest(buf, *x),
-            Muxml2TabElement::CopyTick(tick_idx) => {
-                let note_range = *tick_idx as usize..=(*tick_idx as usize + 5);
-                let notes_iter = parsed.tick_stream[note_range]
-                    .iter()
-                    .enumerate()
-                    .filter(|x| !matches!(x.1, TabElement::Rest))
-                    .map(|(x, y)| (x + *tick_idx as usize, y));
-                // at least two notes here
-                let tick_chord = notes_iter.clone().take(2).count() == 2;
+            Muxml2TabElement::Rest(x) => write_rest(buf, *x, voice),
+            Muxml2TabElement::MeasureRest(x) => write_muxml2_measure_rest(buf, *x, voice),
+            Muxml2TabElement::CopyTick(tick_idx, len) => {
+                // at most six notes, collected so that the accidentals can be updated while writing
+                let notes: Vec<_> = ctx.tick_notes(*tick_idx, voice).collect();
+                let tick_chord = notes.len() >= 2;
                 traceln!(
-                    "for CopyTick({tick_idx}): range {:?}, chord={tick_chord}",
+                    "for CopyTick({tick_idx}, {len}): range {:?}, chord={tick_chord}",
                     *tick_idx as usize..=(*tick_idx as usize + 5)
                 );
-                // TODO: use dynamic base notes - we parse it but we don't use it
-                let mut chord_first_written = false;
-                for (elem_idx, elem) in notes_iter {
-                    let need_chord = tick_chord && chord_first_written;
-                    chord_first_written = true;
-
-                    let Some((dead, fret)) = (match elem {
-                        TabElement::DeadNote => Some((true, 0)),
-                        TabElement::Fret(x) => Some((false, *x)),
-                        _ => None,
-                    }) else {
-                        continue;
-                    };
-                    let note = get_fretboard_note2(parsed.base_notes[elem_idx % 6], fret).unwrap();
-                    let (step, octave, sharp) = note.step_octave_sharp();
-                    let properties = note_properties.get(&(elem_idx as u32));
-                    write_muxml2_note(buf, step, octave, sharp, need_chord, dead, properties)?;
+                // notes only get an explicit voice when the staff is split
+                let note_voice = ctx.voice_split.map(|_| voice);
+                let lengths = note_lengths(*len);
+                for (piece_idx, duration) in lengths.iter().enumerate() {
+                    let (first, last) = (piece_idx == 0, piece_idx == lengths.len() - 1);
+                    for (i, (elem_idx, note)) in notes.iter().enumerate() {
+                        let need_chord = tick_chord && i > 0;
+                        let spelling = note.spell(&ctx.accidentals.key());
+                        let accidental = ctx.accidentals.accidental(&spelling);
+                        let properties = ctx.note_properties.get(&(*elem_idx as u32));
+                        let piece = (lengths.len() > 1)
+                            .then(|| NoteProperties::piece(properties, first, last));
+                        let properties = piece.as_ref().or(properties);
+                        // the beam is only written on the first note of a chord, and only eighths
+                        // are beamed
+                        let beam = if need_chord || *len > 1 { None } else { beam };
+                        write_muxml2_note(
+                            buf, spelling, accidental, need_chord, note.dead, *duration, beam,
+                            note_voice, properties,
+                        )?;
+                    }
                 }
 
                 Ok(())
@@ -112,7 +196,48 @@ impl Muxml2TabElement {
     }
 }
 
-#[derive(Default, Debug)]
+/// The elements of `measure` as seen by one voice: ticks without notes in this voice become rests
+fn voice_elements(
+    measure: &[Muxml2TabElement], ctx: &WriteContext, voice: u8,
+) -> Vec<Muxml2TabElement> {
+    let mut elements: Vec<Muxml2TabElement> = Vec::with_capacity(measure.len());
+    for elem in measure {
+        let elem = match elem {
+            Muxml2TabElement::CopyTick(tick_idx, len) => {
+                if ctx.tick_notes(*tick_idx, voice).next().is_some() {
+                    Muxml2TabElement::CopyTick(*tick_idx, *len)
+                } else {
+                    Muxml2TabElement::Rest(*len)
+                }
+            }
+            Muxml2TabElement::Rest(x) => Muxml2TabElement::Rest(*x),
+            Muxml2TabElement::MeasureRest(x) => Muxml2TabElement::MeasureRest(*x),
+            Muxml2TabElement::Invalid => continue,
+        };
+        match (elements.last_mut(), elem) {
+            (Some(Muxml2TabElement::Rest(last)), Muxml2TabElement::Rest(x)) => *last += x,
+            (_, elem) => elements.push(elem),
+        }
+    }
+    elements
+}
+
+struct ProcessedMeasure {
+    elements: Vec<Muxml2TabElement>,
+    /// In eighths
+    content_len: u32,
+    /// Contains only rests
+    silent: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Beam {
+    Begin,
+    Continue,
+    End,
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct Slur {
     pub number: u16,
     pub start: bool,
@@ -128,7 +253,7 @@ impl Slur {
         Slur { number, start: false }
     }
 }
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Slide {
     pub number: u16,
     pub start: bool,
@@ -145,11 +270,70 @@ pub struct NoteProperties {
     pub slurs: Vec<Slur>,
     pub slide: Option<Slide>,
     pub vibrato: Option<Vibrato>,
+    pub tie: Option<Tie>,
 }
-#[derive(Debug)]
+impl NoteProperties {
+    /// The properties of one of the tied notes a held note is written as. Whatever leads into the
+    /// note goes on the first one, whatever leads out of it on the last one.
+    fn piece(properties: Option<&NoteProperties>, first: bool, last: bool) -> NoteProperties {
+        let own = properties.map(|x| x.tie).unwrap_or_default();
+        let tie = match (
+            !first || own.is_some_and(|x| x.stops()),
+            !last || own.is_some_and(|x| x.starts()),
+        ) {
+            (true, true) => Some(Tie::StopStart),
+            (true, false) => Some(Tie::Stop),
+            (false, true) => Some(Tie::Start),
+            (false, false) => None,
+        };
+        let Some(properties) = properties else {
+            return NoteProperties { tie, ..Default::default() };
+        };
+        let keep = |start: bool| if start { last } else { first };
+        let vibrato = match (properties.vibrato, first, last) {
+            (Some(Vibrato::Both), true, true) => Some(Vibrato::Both),
+            (Some(Vibrato::Start | Vibrato::Both), true, _) => Some(Vibrato::Start),
+            (Some(Vibrato::Stop | Vibrato::Both), _, true) => Some(Vibrato::Stop),
+            _ => None,
+        };
+        NoteProperties {
+            slurs: properties.slurs.iter().filter(|x| keep(x.start)).cloned().collect(),
+            slide: properties.slide.as_ref().filter(|x| keep(x.start)).cloned(),
+            vibrato,
+            tie,
+        }
+    }
+}
+/// A wavy line over a held note, which can go on over the notes it is tied to
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Vibrato {
     Start,
     Stop,
+    /// The wavy line starts and stops on the same note
+    Both,
+}
+impl Vibrato {
+    pub fn starts(&self) -> bool {
+        matches!(self, Vibrato::Start | Vibrato::Both)
+    }
+    pub fn stops(&self) -> bool {
+        matches!(self, Vibrato::Stop | Vibrato::Both)
+    }
+}
+/// A note can end one tie and start the next one, when a held note spans multiple barlines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tie {
+    Start,
+    Stop,
+    StopStart,
+}
+impl Tie {
+    pub fn starts(&self) -> bool {
+        matches!(self, Tie::Start | Tie::StopStart)
+    }
+    pub fn stops(&self) -> bool {
+        matches!(self, Tie::Stop | Tie::StopStart)
+    }
 }
 
 fn gen_muxml2(
@@ -167,10 +351,15 @@ fn gen_muxml2(
     let mut slur_cnt = 0;
     let mut slide_count = 0;
     let mut note_properties: HashMap<u32, NoteProperties, FxBuildHasher> = HashMap::default();
+    let held_ticks = resolve_holds(&mut parsed, &mut note_properties);
+    let key = settings.key.unwrap_or_else(|| detect_key(&parsed));
+    debugln!("muxml2: using key {key:?}");
+    let accidentals = AccidentalState::new(key);
+    let mut processed: Vec<ProcessedMeasure> = Vec::with_capacity(number_of_measures);
     for measure_idx in 0..number_of_measures {
         traceln!("Muxml2: processing measure {}", measure_idx);
         let ticks_in_measure = rlen(&parsed.measures[measure_idx].data_range) / 6;
-        debug_assert!(rlen(&parsed.measures[measure_idx].data_range) % 6 == 0);
+        debug_assert!(rlen(&parsed.measures[measure_idx].data_range).is_multiple_of(6));
         // Length of actual content in measure. `remove_space_between_notes` will reduce this for
         // example
         let mut measure_content_len = ticks_in_measure;
@@ -180,6 +369,7 @@ fn gen_muxml2(
         let mut stream_idx: usize = *parsed.measures[measure_idx].data_range.start() as usize;
         let mut note_count = 0;
         let mut stream_proc_cnt = 0;
+        let mut holds = false;
         while stream_idx <= *parsed.measures[measure_idx].data_range.end() as usize {
             let elem = &parsed.tick_stream[stream_idx];
             //traceln!(
@@ -189,16 +379,8 @@ fn gen_muxml2(
             match elem {
                 TabElement::Fret(..) | TabElement::DeadNote => note_count += 1,
                 TabElement::Rest => {}
-                TabElement::Vibrato => {
-                    let last_idx = stream_idx.saturating_sub(6) as u32;
-                    note_properties.entry(last_idx).or_default().vibrato = Some(Vibrato::Start);
-                    let next_idx = stream_idx + 6;
-                    if next_idx >= parsed.tick_stream.len() {
-                        parsed.tick_stream.extend([const { TabElement::Rest }; 6]);
-                    }
-                    note_properties.entry(next_idx as u32).or_default().vibrato =
-                        Some(Vibrato::Stop);
-                }
+                // see resolve_holds
+                TabElement::Vibrato => holds = held_ticks.contains(&(stream_idx as u32 / 6)),
                 TabElement::Bend
                 | TabElement::HammerOn
                 | TabElement::Pull
@@ -276,12 +458,15 @@ fn gen_muxml2(
             stream_idx += 1;
 
             if stream_proc_cnt == 5 {
-                if note_count > 0 {
-                    measure_processed.push(Muxml2TabElement::CopyTick(stream_idx as u32 - 6));
-                // TODO: maybe pass the non-rest tick ids here instead?
-                } else {
-                    measure_processed.push(Muxml2TabElement::Rest(1));
+                match measure_processed.last_mut() {
+                    _ if note_count > 0 => {
+                        measure_processed.push(Muxml2TabElement::CopyTick(stream_idx as u32 - 6, 1))
+                    }
+                    Some(Muxml2TabElement::CopyTick(_, len)) if holds => *len += 1,
+                    // TODO: maybe pass the non-rest tick ids here instead?
+                    _ => measure_processed.push(Muxml2TabElement::Rest(1)),
                 }
+                holds = false;
                 traceln!(depth = 1, "Parsed a tick, a {:?} ", measure_processed.last().unwrap());
                 note_count = 0;
                 stream_proc_cnt = 0;
@@ -289,34 +474,91 @@ fn gen_muxml2(
                 stream_proc_cnt += 1;
             }
         }
-        if settings.remove_rest_between_notes {
-            remove_rest_between_notes(&mut measure_processed, &mut measure_content_len);
-        }
-        merge_rests_in_measure(&mut measure_processed);
-        if settings.trim_measure {
-            trim_measure(&mut measure_processed, &mut measure_content_len, Direction::Forward);
-            trim_measure(&mut measure_processed, &mut measure_content_len, Direction::Backward);
-        }
-        // Try to simplify e.g 8/8 to 4/4
-        let (mut measure_enumerator, mut measure_denominator) = (measure_content_len, 8);
-        if settings.simplify_time_signature && measure_content_len % 2 == 0 {
-            measure_enumerator /= 2;
-            measure_denominator /= 2;
+        let silent = !measure_processed.iter().any(|x| matches!(x, Muxml2TabElement::CopyTick(..)));
+        if silent {
+            // the length is set to the meter later
+            measure_processed = vec![Muxml2TabElement::MeasureRest(measure_content_len)];
+        } else {
+            if settings.remove_rest_between_notes {
+                remove_rest_between_notes(&mut measure_processed, &mut measure_content_len);
+            }
+            merge_rests_in_measure(&mut measure_processed);
+            if settings.trim_measure {
+                trim_measure(&mut measure_processed, &mut measure_content_len, Direction::Forward);
+                trim_measure(&mut measure_processed, &mut measure_content_len, Direction::Backward);
+            }
         }
-        write_muxml2_measure_prelude(
-            &mut document,
-            measure_idx,
-            measure_enumerator as usize,
-            measure_denominator,
-        )
-        .unwrap();
-        for proc_elem in measure_processed {
-            if let Err(x) = proc_elem.write_muxml(&parsed, &mut document, &note_properties) {
+        processed.push(ProcessedMeasure {
+            elements: measure_processed,
+            content_len: measure_content_len,
+            silent,
+        });
+    }
+
+    let meter = settings.meter.unwrap_or_else(|| detect_meter(&processed, &settings));
+    debugln!("muxml2: using meter {meter:?}");
+    fit_to_meter(&mut processed, &parsed, meter, &mut r.diagnostics);
+
+    let mut ctx = WriteContext {
+        parsed: &parsed,
+        note_properties: &note_properties,
+        accidentals,
+        pitch_shift: settings.pitch_shift(),
+        voice_split: settings.voice_split,
+    };
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        write_muxml2_score_part(&mut document, track_idx, &track.name).unwrap();
+    }
+    document += MUXML2_PART_LIST_END;
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        write_muxml2_part_start(&mut document, track_idx).unwrap();
+        let silent_measures =
+            track.measures.iter().map(|x| processed[*x as usize].silent).collect_vec();
+        let mut last_time = None;
+        // how many measures are left from the current multi-measure rest
+        let mut multi_rest_left = 0;
+        for (measure_number, measure_idx) in track.measures.iter().enumerate() {
+            let measure = &processed[*measure_idx as usize];
+            let multi_rest = if measure.silent
+                && settings.multi_measure_rests
+                && multi_rest_left == 0
+            {
+                let len =
+                    1 + silent_measures[measure_number + 1..].iter().take_while(|x| **x).count();
+                multi_rest_left = len;
+                (len > 1).then_some(len)
+            } else {
+                None
+            };
+            multi_rest_left = multi_rest_left.saturating_sub(1);
+            let time = if measure.content_len == meter.eighths() {
+                meter
+            } else {
+                Meter::from_eighths(measure.content_len, settings.simplify_time_signature)
+            };
+            let time_change = (last_time != Some(time)).then_some(time);
+            last_time = Some(time);
+            write_muxml2_measure_prelude(
+                &mut document,
+                measure_number,
+                time_change,
+                key,
+                multi_rest,
+                &settings,
+            )
+            .unwrap();
+            ctx.accidentals.reset();
+            let result = match settings.voice_split {
+                None => write_voice(&mut ctx, &mut document, &measure.elements, time, 1),
+                Some(_) => write_split_measure(&mut ctx, &mut document, measure, time),
+            };
+            if let Err(x) = result {
                 r.err = Some(x.into());
                 return (None, r);
             }
+            document.push_str("</measure>");
         }
-        document.push_str("</measure>");
+        document += MUXML2_PART_END;
     }
 
     document += MUXML2_DOCUMENT_END;
@@ -324,6 +566,207 @@ fn gen_muxml2(
     (Some(document), r)
 }
 
+/// Picks the most common measure length as the meter. Empty measures are ignored, unless there
+/// is nothing else.
+fn detect_meter(processed: &[ProcessedMeasure], settings: &Settings) -> Meter {
+    let len = common_measure_len(processed.iter().map(|x| (x.content_len, x.silent)));
+    Meter::from_eighths(len, settings.simplify_time_signature)
+}
+
+/// The most common length of `measures`, given as (length in eighths, has no notes). Measures
+/// without notes are ignored, unless there is nothing else. Ties go to the longer length.
+pub(crate) fn common_measure_len(measures: impl Iterator<Item = (u32, bool)> + Clone) -> u32 {
+    let mut counts: HashMap<u32, u32, FxBuildHasher> = HashMap::default();
+    let has_notes = measures.clone().any(|x| !x.1);
+    for (len, _) in measures.filter(|x| !has_notes || !x.1) {
+        *counts.entry(len).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .filter(|(len, _)| *len != 0)
+        .max_by_key(|(len, count)| (*count, *len))
+        .map(|x| x.0)
+        .unwrap_or(8)
+}
+
+/// Pads measures shorter than the meter with rests, and reports the ones which don't fit.
+/// Longer measures keep their own time signature.
+fn fit_to_meter(
+    processed: &mut [ProcessedMeasure], parsed: &ParseResult, meter: Meter,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let meter_len = meter.eighths();
+    for (measure_idx, measure) in processed.iter_mut().enumerate() {
+        if measure.silent {
+            measure.content_len = meter_len;
+            measure.elements = vec![Muxml2TabElement::MeasureRest(meter_len)];
+            continue;
+        }
+        if measure.content_len == meter_len {
+            continue;
+        }
+        let score_measure = parsed.score().measure(measure_idx).unwrap();
+        let location = ErrorLocation::LineAndMeasure(
+            score_measure.part().line(),
+            score_measure.index_in_part(),
+        );
+        if measure.content_len > meter_len {
+            diagnostics.push(Diagnostic::warn(location, DiagnosticKind::MeasureLongerThanMeter));
+            continue;
+        }
+        let padding = meter_len - measure.content_len;
+        match measure.elements.iter_mut().rev().find(|x| !matches!(x, Muxml2TabElement::Invalid)) {
+            Some(Muxml2TabElement::Rest(x)) => *x += padding,
+            _ => measure.elements.push(Muxml2TabElement::Rest(padding)),
+        }
+        measure.content_len = meter_len;
+        diagnostics.push(Diagnostic::info(location, DiagnosticKind::MeasurePaddedToMeter));
+    }
+}
+
+/// Guesses the key signature from the pitch classes of every note in the score
+pub(crate) fn detect_key(parsed: &ParseResult) -> Key {
+    let mut histogram = [0; 12];
+    let notes = parsed.score().measures().flat_map(|x| x.ticks()).flat_map(|x| x.notes());
+    for note in notes.filter(|x| !x.is_dead()) {
+        histogram[(note.pitch() % 12) as usize] += 1;
+    }
+    Key::detect(&histogram)
+}
+
+/// Turns every run of `~` after a note into a held note. A `~` on the first tick of a measure
+/// becomes a tied copy of the note held in the previous measure of the track, and the notes of a
+/// run get a wavy line from the first one to the last one.
+///
+/// This has to happen before generating any measure, because the tie start is written on the note
+/// in the previous measure. Returns the ticks which hold a note, which lengthen the note before
+/// them instead of being rests.
+fn resolve_holds(
+    parsed: &mut ParseResult, note_properties: &mut HashMap<u32, NoteProperties, FxBuildHasher>,
+) -> HashSet<u32, FxBuildHasher> {
+    let mut held_ticks = HashSet::default();
+    let mut end_wavy_line = |wavy_line: &mut Option<usize>, held: Option<usize>| {
+        let (Some(start), Some(end)) = (wavy_line.take(), held) else { return };
+        if start == end {
+            note_properties.entry(start as u32).or_default().vibrato = Some(Vibrato::Both);
+        } else {
+            note_properties.entry(start as u32).or_default().vibrato = Some(Vibrato::Start);
+            note_properties.entry(end as u32).or_default().vibrato = Some(Vibrato::Stop);
+        }
+    };
+    let mut ties = vec![];
+    for track in &parsed.tracks {
+        for string in 0..6 {
+            // the note sounding on this string, and the first note of its wavy line
+            let (mut held, mut wavy_line) = (None, None);
+            for measure_idx in &track.measures {
+                let range = &parsed.measures[*measure_idx as usize].data_range;
+                for tick_start in range.clone().step_by(6) {
+                    let stream_idx = tick_start as usize + string;
+                    match parsed.tick_stream[stream_idx] {
+                        TabElement::Vibrato => {
+                            let Some(source_idx) = held else { continue };
+                            wavy_line.get_or_insert(source_idx);
+                            if tick_start == *range.start() {
+                                traceln!("muxml2: tie from {source_idx} to {stream_idx}");
+                                ties.push((source_idx, stream_idx));
+                                held = Some(stream_idx);
+                            } else {
+                                held_ticks.insert(tick_start / 6);
+                            }
+                        }
+                        TabElement::Fret(_) | TabElement::DeadNote => {
+                            end_wavy_line(&mut wavy_line, held);
+                            held = Some(stream_idx);
+                        }
+                        _ => {
+                            end_wavy_line(&mut wavy_line, held);
+                            held = None;
+                        }
+                    }
+                }
+            }
+            end_wavy_line(&mut wavy_line, held);
+        }
+    }
+    for (source_idx, stream_idx) in ties {
+        parsed.tick_stream[stream_idx] = parsed.tick_stream[source_idx].clone();
+        let source = note_properties.entry(source_idx as u32).or_default();
+        source.tie = Some(match source.tie {
+            Some(Tie::Stop) => Tie::StopStart,
+            _ => Tie::Start,
+        });
+        note_properties.entry(stream_idx as u32).or_default().tie = Some(Tie::Stop);
+    }
+    held_ticks
+}
+
+fn write_voice(
+    ctx: &mut WriteContext, buf: &mut String, elements: &[Muxml2TabElement], time: Meter, voice: u8,
+) -> std::fmt::Result {
+    let beams = beam_measure(elements, time.beam_len());
+    for (elem, beam) in elements.iter().zip(beams) {
+        elem.write_muxml(ctx, buf, beam, voice)?;
+    }
+    Ok(())
+}
+
+/// Writes the first voice, then goes back to the start of the measure to write the second one,
+/// if it has any notes in this measure
+fn write_split_measure(
+    ctx: &mut WriteContext, buf: &mut String, measure: &ProcessedMeasure, time: Meter,
+) -> std::fmt::Result {
+    let upper = voice_elements(&measure.elements, ctx, 1);
+    write_voice(ctx, buf, &upper, time, 1)?;
+    let lower = voice_elements(&measure.elements, ctx, 2);
+    if !lower.iter().any(|x| matches!(x, Muxml2TabElement::CopyTick(..))) {
+        return Ok(());
+    }
+    write_muxml2_backup(buf, measure.content_len)?;
+    write_voice(ctx, buf, &lower, time, 2)
+}
+
+/// Beams the consecutive notes inside each beat. Rests and beat boundaries break the beams.
+///
+/// Every note is an eighth for now, so only primary beams are needed.
+fn beam_measure(measure: &[Muxml2TabElement], beat_len: u32) -> Vec<Option<Beam>> {
+    let mut beams = vec![None; measure.len()];
+    let mut group: Vec<usize> = Vec::with_capacity(beat_len as usize);
+    let mut flush = |group: &mut Vec<usize>| {
+        if group.len() > 1 {
+            beams[group[0]] = Some(Beam::Begin);
+            for &i in &group[1..group.len() - 1] {
+                beams[i] = Some(Beam::Continue);
+            }
+            beams[group[group.len() - 1]] = Some(Beam::End);
+        }
+        group.clear();
+    };
+    let (mut position, mut group_beat) = (0, 0);
+    for (i, elem) in measure.iter().enumerate() {
+        match elem {
+            Muxml2TabElement::CopyTick(_, 1) => {
+                let beat = position / beat_len;
+                if beat != group_beat {
+                    flush(&mut group);
+                    group_beat = beat;
+                }
+                group.push(i);
+                position += 1;
+            }
+            Muxml2TabElement::Rest(x)
+            | Muxml2TabElement::MeasureRest(x)
+            | Muxml2TabElement::CopyTick(_, x) => {
+                flush(&mut group);
+                position += x;
+            }
+            Muxml2TabElement::Invalid => {}
+        }
+    }
+    flush(&mut group);
+    beams
+}
+
 fn merge_rests_in_measure(measure: &mut [Muxml2TabElement]) {
     for mut i in 0..measure.len() {
         match measure[i] {
@@ -336,7 +779,9 @@ fn merge_rests_in_measure(measure: &mut [Muxml2TabElement]) {
                 }
                 measure[original_i] = Muxml2TabElement::Rest((i - original_i) as u32);
             }
-            Muxml2TabElement::CopyTick(..) | Muxml2TabElement::Invalid => continue,
+            Muxml2TabElement::CopyTick(..)
+            | Muxml2TabElement::MeasureRest(_)
+            | Muxml2TabElement::Invalid => continue,
         }
     }
 }
@@ -347,12 +792,12 @@ fn remove_rest_between_notes(measure: &mut [Muxml2TabElement], content_len: &mut
     while i < measure.len() {
         use Muxml2TabElement::*;
         match (measure.get(i), measure.get(i + 1), measure.get(i + 2)) {
-            (Some(CopyTick(_)), Some(Rest(1)), Some(CopyTick(_))) => {
+            (Some(CopyTick(..)), Some(Rest(1)), Some(CopyTick(..))) => {
                 measure[i + 1] = Muxml2TabElement::Invalid;
                 i += 3;
                 *content_len -= 1;
             }
-            (Some(Rest(1)), Some(CopyTick(_)), Some(Rest(1))) => {
+            (Some(Rest(1)), Some(CopyTick(..)), Some(Rest(1))) => {
                 measure[i] = Muxml2TabElement::Invalid;
                 measure[i + 2] = Muxml2TabElement::Invalid;
                 i += 3;
@@ -377,12 +822,11 @@ fn trim_measure(measure: &mut [Muxml2TabElement], content_len: &mut u32, directi
     loop {
         match &measure[i] {
             Muxml2TabElement::Rest(rest_len) => {
-                // TODO: this generates an empty measure for full-rest measures. maybe think of a solution
                 *content_len = content_len.saturating_sub(*rest_len);
                 measure[i] = Muxml2TabElement::Invalid;
                 break;
             }
-            Muxml2TabElement::CopyTick(_) => break,
+            Muxml2TabElement::CopyTick(..) | Muxml2TabElement::MeasureRest(_) => break,
             Muxml2TabElement::Invalid => {
                 if i == last {
                     break;
@@ -1,10 +1,11 @@
 use crate::backend::errors::backend_error_kind::BackendErrorKind;
 use crate::backend::{
-    muxml::{settings::Settings, MuxmlBackend},
+    muxml::{
+        settings::{Settings, VoiceSplit},
+        MuxmlBackend,
+    },
     Backend,
 };
-use crate::debugln;
-use crate::parser::parser::{parse, source_location_from_stream};
 use itertools::Itertools;
 
 #[test]
@@ -22,6 +23,7 @@ E|--------------|
         remove_rest_between_notes: true,
         trim_measure: true,
         simplify_time_signature: true,
+        ..Default::default()
     };
     MuxmlBackend::process(
         &i1.lines().map(|x| x.to_string()).collect::<Vec<_>>(),
@@ -46,6 +48,7 @@ E|----|
         remove_rest_between_notes: true,
         trim_measure: true,
         simplify_time_signature: true,
+        ..Default::default()
     };
     MuxmlBackend::process(
         &i1.lines().map(|x| x.to_string()).collect::<Vec<_>>(),
@@ -78,6 +81,7 @@ E|-----9-|-----9-|"#;
         remove_rest_between_notes: true,
         trim_measure: true,
         simplify_time_signature: true,
+        ..Default::default()
     };
     let res = MuxmlBackend::process(
         &example_score.lines().map(|x| x.to_string()).collect_vec(),
@@ -89,3 +93,212 @@ E|-----9-|-----9-|"#;
     assert_eq!(e.main_location, ErrorLocation::LineAndChar(3, 25));
     assert!(matches!(e.kind, BackendErrorKind::BendOnInvalid));
 }
+
+#[test]
+fn test_muxml_ties() -> anyhow::Result<()> {
+    let i1 = r#"
+e|--------|--------|
+B|--------|--------|
+G|-5~~~~~~|~~~-----|
+D|--------|--------|
+A|3-------|--------|
+E|--------|--------|
+    "#;
+    let mut out = vec![];
+    let settings = Settings {
+        remove_rest_between_notes: false,
+        trim_measure: false,
+        simplify_time_signature: true,
+        ..Default::default()
+    };
+    let res = MuxmlBackend::process(
+        &i1.lines().map(|x| x.to_string()).collect::<Vec<_>>(),
+        &mut out,
+        settings,
+    );
+    assert!(res.err.is_none());
+    insta::assert_snapshot!(String::from_utf8_lossy(&out));
+    Ok(())
+}
+
+#[test]
+fn test_muxml_octave_modes() {
+    use crate::backend::muxml::settings::{Clef, OctaveMode};
+    let i1 = r#"
+e|---|
+B|---|
+G|---|
+D|---|
+A|---|
+E|0--|"#
+        .lines()
+        .map(|x| x.to_string())
+        .collect_vec();
+    let process = |clef, octave| {
+        let mut out = vec![];
+        let settings = Settings { clef, octave, ..Default::default() };
+        MuxmlBackend::process(&i1, &mut out, settings);
+        String::from_utf8(out).unwrap()
+    };
+    let plain = process(Clef::Treble, OctaveMode::Plain);
+    assert!(plain.contains("<step>E</step>\n<octave>3</octave>"));
+    assert!(!plain.contains("<clef-octave-change>") && !plain.contains("<transpose>"));
+
+    let sounding = process(Clef::Treble, OctaveMode::Sounding);
+    assert!(sounding.contains("<step>E</step>\n<octave>2</octave>"));
+    assert!(sounding.contains("<clef-octave-change>-1</clef-octave-change>"));
+
+    let transposing = process(Clef::Treble, OctaveMode::Transposing);
+    assert!(transposing.contains("<step>E</step>\n<octave>3</octave>"));
+    assert!(transposing.contains("<octave-change>-1</octave-change></transpose>"));
+
+    let bass = process(Clef::Bass, OctaveMode::Transposing);
+    assert!(bass.contains("<clef><sign>F</sign><line>4</line></clef>"));
+    assert!(bass.contains("<step>E</step>\n<octave>2</octave>"));
+}
+
+#[test]
+fn test_beam_measure() {
+    use crate::backend::muxml::{beam_measure, Beam, Muxml2TabElement::*};
+    // 4/4: two beamed eighths, a lone eighth broken off by a rest, then two beamed eighths
+    let measure = [
+        CopyTick(0, 1),
+        CopyTick(6, 1),
+        CopyTick(12, 1),
+        Rest(1),
+        Invalid,
+        CopyTick(24, 1),
+        CopyTick(30, 1),
+    ];
+    assert_eq!(
+        beam_measure(&measure, 2),
+        [Some(Beam::Begin), Some(Beam::End), None, None, None, Some(Beam::Begin), Some(Beam::End)]
+    );
+}
+
+#[test]
+fn test_muxml_measure_rests() {
+    let input = r#"
+e|--------|--------|--------|--------|
+B|--------|--------|--------|--------|
+G|--1-2---|--------|--------|--------|
+D|--------|--------|--------|--------|
+A|--------|--------|--------|--------|
+E|--------|--------|--------|--------|"#;
+    let mut out = vec![];
+    let settings = Settings {
+        trim_measure: true,
+        simplify_time_signature: true,
+        multi_measure_rests: true,
+        ..Default::default()
+    };
+    let res = MuxmlBackend::process(
+        &input.lines().map(|x| x.to_string()).collect_vec(),
+        &mut out,
+        settings,
+    );
+    assert!(res.err.is_none());
+    let out = String::from_utf8(out).unwrap();
+    assert!(!out.contains("<beats>0</beats>"));
+    assert_eq!(out.matches(r#"<rest measure="yes"/>"#).count(), 3);
+    assert!(out.contains("<multiple-rest>3</multiple-rest>"));
+}
+
+#[test]
+fn test_muxml_meter() {
+    use crate::backend::errors::diagnostic_kind::DiagnosticKind;
+    let input = r#"
+e|1-------|1-------|1------|1--------|1-------|
+B|--------|--------|-------|---------|--------|
+G|--------|--------|-------|---------|--------|
+D|--------|--------|-------|---------|--------|
+A|--------|--------|-------|---------|--------|
+E|--------|--------|-------|---------|--------|"#;
+    let mut out = vec![];
+    let settings = Settings { simplify_time_signature: true, ..Default::default() };
+    let res = MuxmlBackend::process(
+        &input.lines().map(|x| x.to_string()).collect_vec(),
+        &mut out,
+        settings,
+    );
+    assert!(res.err.is_none());
+    let out = String::from_utf8(out).unwrap();
+    // 4/4 at the start, 9/8 for the long measure and 4/4 again after it
+    assert_eq!(out.matches("<time>").count(), 3);
+    assert!(out.contains("<time><beats>9</beats><beat-type>8</beat-type></time>"));
+    let kinds = res.diagnostics.iter().map(|x| x.kind.clone()).collect_vec();
+    assert!(matches!(
+        kinds.as_slice(),
+        [DiagnosticKind::MeasurePaddedToMeter, DiagnosticKind::MeasureLongerThanMeter]
+    ));
+}
+
+#[test]
+fn test_muxml_tracks() {
+    let input = r#"
+Track: Guitar
+e|-1-|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+Track: Bass & Co
+e|---|
+B|---|
+G|---|
+D|---|
+A|-3-|
+E|---|"#;
+    let mut out = vec![];
+    let res = MuxmlBackend::process(
+        &input.lines().map(|x| x.to_string()).collect_vec(),
+        &mut out,
+        Settings::default(),
+    );
+    assert!(res.err.is_none());
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.matches("<score-part id=").count(), 2);
+    assert!(out.contains("<part-name>Bass &amp; Co</part-name>"));
+    assert!(out.contains(r#"<part id="P2">"#));
+    // measure numbers restart in every part
+    assert_eq!(out.matches(r#"<measure number="0">"#).count(), 2);
+}
+
+#[test]
+fn test_muxml_voice_split() {
+    // a melody on the high strings over a bass line that alternates between E and D
+    let input = r#"
+e|-0---3---|
+B|---------|
+G|---------|
+D|-----0---|
+A|---------|
+E|-0-------|"#;
+    let lines = input.lines().map(|x| x.to_string()).collect_vec();
+    let mut out = vec![];
+    let settings = Settings { voice_split: Some(VoiceSplit::String(4)), ..Default::default() };
+    let res = MuxmlBackend::process(&lines, &mut out, settings);
+    assert!(res.err.is_none());
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.matches("<backup><duration>").count(), 1);
+    assert_eq!(out.matches("<stem>up</stem>").count(), 2);
+    assert_eq!(out.matches("<stem>down</stem>").count(), 2);
+    // the two voices are separate, so nothing is written as a chord
+    assert!(!out.contains("<chord/>"));
+
+    // the same split by pitch, the D string's open note is D4
+    let by_pitch: VoiceSplit = "E4".parse().unwrap();
+    assert_eq!(by_pitch, VoiceSplit::Pitch(52));
+    let mut pitch_out = vec![];
+    let settings = Settings { voice_split: Some(by_pitch), ..Default::default() };
+    MuxmlBackend::process(&lines, &mut pitch_out, settings);
+    assert_eq!(String::from_utf8(pitch_out).unwrap(), out);
+
+    // a voice without notes is not written at all
+    let mut melody_out = vec![];
+    let settings = Settings { voice_split: Some(VoiceSplit::Pitch(0)), ..Default::default() };
+    MuxmlBackend::process(&lines, &mut melody_out, settings);
+    assert!(!String::from_utf8(melody_out).unwrap().contains("<backup>"));
+    assert!("7".parse::<VoiceSplit>().is_err());
+}
@@ -1,7 +1,165 @@
+use super::key::Key;
+use clap::ValueEnum;
+use std::str::FromStr;
+
 /// These are documented in cli_args.rs
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct Settings {
     pub remove_rest_between_notes: bool,
     pub trim_measure: bool,
     pub simplify_time_signature: bool,
+    /// Detected from the notes if not set
+    pub key: Option<Key>,
+    pub clef: Clef,
+    pub octave: OctaveMode,
+    pub multi_measure_rests: bool,
+    /// Detected as the most common measure length if not set
+    pub meter: Option<Meter>,
+    /// Splits the staff into two voices if set
+    pub voice_split: Option<VoiceSplit>,
+}
+
+/// Decides which notes go to the second voice (with stems down), when splitting a staff into two
+/// voices, e.g. for an alternating bass under a melody
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VoiceSplit {
+    /// Notes on this string and the strings below it. The highest string is 1.
+    String(u8),
+    /// Notes below this written pitch, see [super::fretboard::MuxmlNote2::step]
+    Pitch(u8),
+}
+
+impl VoiceSplit {
+    /// `string_idx` is 0 for the highest string
+    pub fn voice_of(&self, string_idx: usize, step: u8) -> u8 {
+        let lower = match self {
+            VoiceSplit::String(first_lower) => string_idx + 1 >= *first_lower as usize,
+            VoiceSplit::Pitch(threshold) => step < *threshold,
+        };
+        if lower {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+impl FromStr for VoiceSplit {
+    type Err = String;
+
+    /// Accepts a string number (`4`), or a pitch with an octave (`G3`, `F#3`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(string) = s.parse::<u8>() {
+            return match string {
+                1..=6 => Ok(VoiceSplit::String(string)),
+                _ => Err(format!("There is no string {string}, expected 1 to 6")),
+            };
+        }
+        let err = || format!("Invalid voice split {s}, expected a string (4) or a pitch (G3)");
+        let (name, octave) = s.split_at(s.find(|x: char| x.is_ascii_digit()).ok_or_else(err)?);
+        let octave: u8 = octave.parse().map_err(|_| err())?;
+        let mut chars = name.chars();
+        let pitch_class: i16 = match chars.next().map(|x| x.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => return Err(err()),
+        };
+        let alter = match chars.as_str() {
+            "" => 0,
+            "#" => 1,
+            "b" => -1,
+            _ => return Err(err()),
+        };
+        let step = octave as i16 * 12 + pitch_class + alter;
+        u8::try_from(step).map(VoiceSplit::Pitch).map_err(|_| err())
+    }
+}
+
+/// A time signature, like 4/4
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Meter {
+    pub beats: u32,
+    pub beat_type: u32,
+}
+
+impl Meter {
+    /// Every tick is an eighth, so this is `len/8`, or `(len/2)/4` if simplified
+    pub fn from_eighths(len: u32, simplify: bool) -> Self {
+        if simplify && len.is_multiple_of(2) {
+            Meter { beats: len / 2, beat_type: 4 }
+        } else {
+            Meter { beats: len, beat_type: 8 }
+        }
+    }
+
+    /// The length of a measure in eighths
+    pub fn eighths(&self) -> u32 {
+        self.beats * 8 / self.beat_type
+    }
+
+    /// The length of a beamed group in eighths: a dotted quarter in compound meters like 6/8,
+    /// a quarter otherwise
+    pub fn beam_len(&self) -> u32 {
+        if self.beat_type == 8 && self.beats.is_multiple_of(3) {
+            3
+        } else {
+            2
+        }
+    }
+}
+
+impl FromStr for Meter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid time signature {s}, expected something like 4/4 or 6/8");
+        let (beats, beat_type) = s.split_once('/').ok_or_else(err)?;
+        let beats: u32 = beats.trim().parse().map_err(|_| err())?;
+        let beat_type: u32 = beat_type.trim().parse().map_err(|_| err())?;
+        // the shortest note we write is an eighth
+        if beats == 0 || !matches!(beat_type, 1 | 2 | 4 | 8) {
+            return Err(err());
+        }
+        Ok(Meter { beats, beat_type })
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub enum Clef {
+    #[default]
+    Treble,
+    /// For bass tabs, one octave below the treble clef
+    Bass,
+}
+
+/// Guitars (and basses) sound an octave lower than written
+#[derive(ValueEnum, Clone, Copy, Default, Debug, PartialEq)]
+pub enum OctaveMode {
+    /// Written pitch on a plain clef, without marking the octave transposition
+    #[default]
+    Plain,
+    /// Sounding pitch on an octave clef (`<clef-octave-change>-1</clef-octave-change>`)
+    Sounding,
+    /// Written pitch, with the octave transposition recorded in `<transpose>`
+    Transposing,
+}
+
+impl Settings {
+    /// How many semitones to lower the written guitar pitch by
+    pub fn pitch_shift(&self) -> u8 {
+        let clef_shift = match self.clef {
+            Clef::Treble => 0,
+            Clef::Bass => 12,
+        };
+        let octave_shift = match self.octave {
+            OctaveMode::Plain | OctaveMode::Transposing => 0,
+            OctaveMode::Sounding => 12,
+        };
+        clef_shift + octave_shift
+    }
 }
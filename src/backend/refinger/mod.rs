@@ -0,0 +1,101 @@
+//! Moves the notes of a tab to the strings that are easiest to play.
+
+use super::{
+    format::process_rewrite,
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::fingering::{optimize::FingeringSettings, refinger::refinger};
+
+pub struct RefingerBackend();
+
+impl Backend for RefingerBackend {
+    type BackendSettings = FingeringSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let header = (settings.capo > 0).then(|| format!("// Capo: {}\n", settings.capo));
+        process_rewrite(input, out, header.as_deref(), |parsed, diagnostics| {
+            refinger(parsed, &settings, diagnostics)
+        })
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("max-span", "The most frets the hand may stretch over in a chord, 4 by default"),
+        ("capo", "Play the tab with a capo on this fret, 0 by default"),
+        ("no-open-strings", "Avoid open strings when a note can be fretted instead"),
+        ("max-fret", "The highest fret a note may be placed on, 24 by default"),
+    ];
+    registry.register(SelectorBackend::new(
+        "refinger",
+        "Moves the notes of a tab to the strings that are easiest to play",
+        &[],
+        options,
+        |o| {
+            let default = FingeringSettings::default();
+            Ok(BackendSelector::Refinger(FingeringSettings {
+                max_fret: o.get_or("max-fret", default.max_fret)?,
+                max_span: o.get_or("max-span", default.max_span)?,
+                prefer_open_strings: !o.flag("no-open-strings")?,
+                capo: o.get_or("capo", default.capo)?,
+                ..default
+            }))
+        },
+    ));
+}
+
+#[test]
+fn test_refinger_backend() {
+    let input = "
+// intro
+e|----------|
+B|----------|
+G|----------|
+D|----------|
+A|-5-7h9-3--|
+E|-------3--|";
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let refinger = |settings| {
+        let mut out = vec![];
+        let res = RefingerBackend::process(&lines, &mut out, settings);
+        assert!(res.err.is_none());
+        (String::from_utf8(out).unwrap(), res.diagnostics.len())
+    };
+    // the hammer-on stays on its string, the D moves next to it and the chord is kept
+    assert_eq!(
+        refinger(FingeringSettings::default()),
+        (
+            "// intro
+e|----------|
+B|----------|
+G|----------|
+D|-0--------|
+A|---7h9-3--|
+E|-------3--|
+"
+            .to_string(),
+            0
+        )
+    );
+    // the capo is above the open D string
+    let settings = FingeringSettings { capo: 2, ..Default::default() };
+    assert_eq!(
+        refinger(settings),
+        (
+            "// Capo: 2
+// intro
+e|----------|
+B|----------|
+G|----------|
+D|----------|
+A|-3-5h7-1--|
+E|-------1--|
+"
+            .to_string(),
+            0
+        )
+    );
+}
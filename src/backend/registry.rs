@@ -0,0 +1,423 @@
+//! Backends picked at runtime, by name or by the extensions of the files they read and write.
+//!
+//! [BackendSelector] only knows the backends of this crate. Other crates implement [DynBackend]
+//! and add it to a [Registry], next to the built-in backends:
+//! ```
+//! use scoreman::backend::{
+//!     registry::{DynBackend, Options, Registry},
+//!     BackendResult,
+//! };
+//!
+//! struct LineCount;
+//! impl DynBackend for LineCount {
+//!     fn name(&self) -> &str {
+//!         "lines"
+//!     }
+//!     fn description(&self) -> &str {
+//!         "Counts the lines of the input"
+//!     }
+//!     fn extensions(&self) -> &[&str] {
+//!         &["count"]
+//!     }
+//!     fn process(
+//!         &self, input: &[String], out: &mut dyn std::io::Write, _options: &Options,
+//!     ) -> BackendResult {
+//!         let err = write!(out, "{}", input.len()).err().map(|x| x.into());
+//!         BackendResult::new(vec![], err, None, None)
+//!     }
+//! }
+//!
+//! let mut registry = Registry::with_builtins();
+//! registry.register(LineCount);
+//! let backend = registry.for_paths("song.tab", "song.count").unwrap();
+//! let mut out = vec![];
+//! backend.process(&["e|-1-|".to_string()], &mut out, &Options::new());
+//! assert_eq!(out, b"1");
+//! ```
+//!
+//! Settings are passed as [Options], which are read the same way by every backend: the
+//! `--tempo 100` of the CLI is `tempo=100`.
+
+use std::{fmt::Display, io::Write, str::FromStr};
+
+use clap::ValueEnum;
+use itertools::Itertools;
+
+use super::{errors::backend_error::BackendError, BackendResult, BackendSelector};
+use crate::import;
+
+/// The extensions of a tab, which most backends read
+pub const TAB_EXTENSIONS: &[&str] = &["tab", "txt"];
+
+/// A backend which can be stored as a `Box<dyn DynBackend>`, unlike [super::Backend]
+pub trait DynBackend: Send + Sync {
+    /// The name the backend is picked by, like `midi`
+    fn name(&self) -> &str;
+
+    /// One line for lists of backends
+    fn description(&self) -> &str;
+
+    /// The extensions of the files the backend writes, without the dot. Empty for backends
+    /// which can only be picked by name.
+    fn extensions(&self) -> &[&str];
+
+    /// The extensions of the files the backend reads
+    fn input_extensions(&self) -> &[&str] {
+        TAB_EXTENSIONS
+    }
+
+    /// The options the backend reads, with a description
+    fn options(&self) -> &[(&str, &str)] {
+        &[]
+    }
+
+    /// Whether the input has to be given to [DynBackend::process_bytes] instead of being read
+    /// as lines
+    fn input_is_binary(&self) -> bool {
+        false
+    }
+
+    fn process(&self, input: &[String], out: &mut dyn Write, options: &Options) -> BackendResult;
+
+    /// Like [DynBackend::process], but for the raw contents of the input file
+    fn process_bytes(&self, input: &[u8], out: &mut dyn Write, options: &Options) -> BackendResult {
+        let lines = String::from_utf8_lossy(input).lines().map(|x| x.to_string()).collect_vec();
+        self.process(&lines, out, options)
+    }
+}
+
+/// A backend borrowed from a [Registry]
+impl<T: DynBackend + ?Sized> DynBackend for &T {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn description(&self) -> &str {
+        (**self).description()
+    }
+
+    fn extensions(&self) -> &[&str] {
+        (**self).extensions()
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        (**self).input_extensions()
+    }
+
+    fn options(&self) -> &[(&str, &str)] {
+        (**self).options()
+    }
+
+    fn input_is_binary(&self) -> bool {
+        (**self).input_is_binary()
+    }
+
+    fn process(&self, input: &[String], out: &mut dyn Write, options: &Options) -> BackendResult {
+        (**self).process(input, out, options)
+    }
+
+    fn process_bytes(&self, input: &[u8], out: &mut dyn Write, options: &Options) -> BackendResult {
+        (**self).process_bytes(input, out, options)
+    }
+}
+
+/// Settings for a [DynBackend], as `key=value` pairs. Keys are written like the long flags of
+/// the CLI, e.g. `max-fret`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Options {
+    values: Vec<(String, String)>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads `key=value` pairs. A key without a value sets a flag, like `tab-only`.
+    pub fn parse<S: AsRef<str>>(args: impl IntoIterator<Item = S>) -> Result<Self, BackendError> {
+        let mut options = Self::new();
+        for arg in args {
+            let arg = arg.as_ref();
+            let (key, value) = arg.split_once('=').unwrap_or((arg, "true"));
+            if key.trim().is_empty() {
+                return Err(invalid(format!("Expected key=value, found `{arg}`")));
+            }
+            options.insert(key.trim(), value.trim());
+        }
+        Ok(options)
+    }
+
+    /// Sets `key` to `value`, replacing an earlier value
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
+        self.values.retain(|x| x.0 != key);
+        self.values.push((key, value.into()));
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.iter().map(|x| x.0.as_str())
+    }
+
+    pub fn raw(&self, key: &str) -> Option<&str> {
+        self.values.iter().find(|x| x.0 == key).map(|x| x.1.as_str())
+    }
+
+    /// The value of `key`, if it is set
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<Option<T>, BackendError>
+    where
+        T::Err: Display,
+    {
+        let Some(value) = self.raw(key) else { return Ok(None) };
+        match value.parse() {
+            Ok(x) => Ok(Some(x)),
+            Err(e) => Err(invalid(format!("`{value}` is not a valid value for {key}: {e}"))),
+        }
+    }
+
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, BackendError>
+    where
+        T::Err: Display,
+    {
+        Ok(self.get(key)?.unwrap_or(default))
+    }
+
+    pub fn require<T: FromStr>(&self, key: &str) -> Result<T, BackendError>
+    where
+        T::Err: Display,
+    {
+        self.get(key)?.ok_or_else(|| invalid(format!("The option {key} is required")))
+    }
+
+    /// A flag, which is off if not set
+    pub fn flag(&self, key: &str) -> Result<bool, BackendError> {
+        self.get_or(key, false)
+    }
+
+    /// One of the values of a CLI enum, like the `clef` of [super::muxml]
+    pub fn choice<T: ValueEnum>(&self, key: &str) -> Result<Option<T>, BackendError> {
+        let Some(value) = self.raw(key) else { return Ok(None) };
+        T::from_str(value, true).map(Some).map_err(|_| {
+            let names = T::value_variants().iter().filter_map(|x| x.to_possible_value());
+            let names = names.map(|x| x.get_name().to_string()).join(", ");
+            invalid(format!("`{value}` is not a valid value for {key}, expected one of {names}"))
+        })
+    }
+
+    /// Fails on the first key that is not in `known`
+    pub fn check(&self, known: &[&str]) -> Result<(), BackendError> {
+        match self.keys().find(|x| !known.contains(x)) {
+            None => Ok(()),
+            Some(key) if known.is_empty() => {
+                Err(invalid(format!("Unknown option {key}, the backend has no options")))
+            }
+            Some(key) => Err(invalid(format!(
+                "Unknown option {key}, expected one of {}",
+                known.iter().join(", ")
+            ))),
+        }
+    }
+}
+
+fn invalid(message: String) -> BackendError {
+    BackendError::invalid_option(message)
+}
+
+/// A built-in backend, which reads its options into a [BackendSelector]
+pub(crate) struct SelectorBackend {
+    name: &'static str,
+    description: &'static str,
+    extensions: &'static [&'static str],
+    input_extensions: &'static [&'static str],
+    binary: bool,
+    options: &'static [(&'static str, &'static str)],
+    selector: fn(&Options) -> Result<BackendSelector, BackendError>,
+}
+
+impl SelectorBackend {
+    /// A backend which reads a tab
+    pub fn new(
+        name: &'static str, description: &'static str, extensions: &'static [&'static str],
+        options: &'static [(&'static str, &'static str)],
+        selector: fn(&Options) -> Result<BackendSelector, BackendError>,
+    ) -> Self {
+        let input_extensions = TAB_EXTENSIONS;
+        Self { name, description, extensions, input_extensions, binary: false, options, selector }
+    }
+
+    /// Reads files with these extensions instead of a tab
+    pub fn reads(mut self, input_extensions: &'static [&'static str], binary: bool) -> Self {
+        self.input_extensions = input_extensions;
+        self.binary = binary;
+        self
+    }
+
+    fn select(&self, options: &Options) -> Result<BackendSelector, BackendError> {
+        options.check(&self.options.iter().map(|x| x.0).collect_vec())?;
+        (self.selector)(options)
+    }
+}
+
+impl DynBackend for SelectorBackend {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn description(&self) -> &str {
+        self.description
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+
+    fn input_extensions(&self) -> &[&str] {
+        self.input_extensions
+    }
+
+    fn options(&self) -> &[(&str, &str)] {
+        self.options
+    }
+
+    fn input_is_binary(&self) -> bool {
+        self.binary
+    }
+
+    fn process(
+        &self, input: &[String], mut out: &mut dyn Write, options: &Options,
+    ) -> BackendResult {
+        match self.select(options) {
+            Ok(selector) => selector.process(input, &mut out),
+            Err(e) => BackendResult::new(vec![], Some(e), None, None),
+        }
+    }
+
+    fn process_bytes(
+        &self, input: &[u8], mut out: &mut dyn Write, options: &Options,
+    ) -> BackendResult {
+        match self.select(options) {
+            Ok(selector) => selector.process_bytes(input, &mut out),
+            Err(e) => BackendResult::new(vec![], Some(e), None, None),
+        }
+    }
+}
+
+/// A [BackendSelector] already has its settings, so it takes no options
+impl DynBackend for BackendSelector {
+    fn name(&self) -> &str {
+        BackendSelector::name(self)
+    }
+
+    fn description(&self) -> &str {
+        ""
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    fn input_is_binary(&self) -> bool {
+        BackendSelector::input_is_binary(self)
+    }
+
+    fn process(
+        &self, input: &[String], mut out: &mut dyn Write, options: &Options,
+    ) -> BackendResult {
+        match options.check(&[]) {
+            Ok(()) => self.clone().process(input, &mut out),
+            Err(e) => BackendResult::new(vec![], Some(e), None, None),
+        }
+    }
+
+    fn process_bytes(
+        &self, input: &[u8], mut out: &mut dyn Write, options: &Options,
+    ) -> BackendResult {
+        match options.check(&[]) {
+            Ok(()) => self.clone().process_bytes(input, &mut out),
+            Err(e) => BackendResult::new(vec![], Some(e), None, None),
+        }
+    }
+}
+
+/// Backends by name. A backend registered under a name which is already taken replaces the
+/// earlier one.
+#[derive(Default)]
+pub struct Registry {
+    backends: Vec<Box<dyn DynBackend>>,
+}
+
+impl Registry {
+    /// An empty registry, see [Registry::with_builtins]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every backend of this crate
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        super::midi::register(&mut registry);
+        super::json::register(&mut registry);
+        super::muxml::register(&mut registry);
+        super::lilypond::register(&mut registry);
+        super::abc::register(&mut registry);
+        super::alphatex::register(&mut registry);
+        super::vextab::register(&mut registry);
+        super::gp5::register(&mut registry);
+        super::html::register(&mut registry);
+        super::svg::register(&mut registry);
+        super::audio::register(&mut registry);
+        super::fixup::register(&mut registry);
+        super::format::register(&mut registry);
+        super::transpose::register(&mut registry);
+        super::retune::register(&mut registry);
+        super::refinger::register(&mut registry);
+        import::musicxml::register(&mut registry);
+        import::ir::register(&mut registry);
+        import::midi::register(&mut registry);
+        registry
+    }
+
+    pub fn register(&mut self, backend: impl DynBackend + 'static) {
+        self.register_boxed(Box::new(backend))
+    }
+
+    pub fn register_boxed(&mut self, backend: Box<dyn DynBackend>) {
+        match self.backends.iter_mut().find(|x| x.name() == backend.name()) {
+            Some(x) => *x = backend,
+            None => self.backends.push(backend),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn DynBackend> {
+        self.backends.iter().find(|x| x.name() == name).map(|x| x.as_ref())
+    }
+
+    /// In the order they were registered
+    pub fn backends(&self) -> impl Iterator<Item = &dyn DynBackend> {
+        self.backends.iter().map(|x| x.as_ref())
+    }
+
+    /// The backends which write files with the extension `ext`
+    pub fn for_extension<'a>(&'a self, ext: &'a str) -> impl Iterator<Item = &'a dyn DynBackend> {
+        self.backends().filter(move |x| x.extensions().iter().any(|e| e.eq_ignore_ascii_case(ext)))
+    }
+
+    /// The backend which converts `input_path` into `output_path`, picked by their extensions.
+    /// None if there is no such backend, or more than one.
+    pub fn for_paths(&self, input_path: &str, output_path: &str) -> Option<&dyn DynBackend> {
+        let matches = |extensions: &[&str], path| {
+            let ext = extension(path).unwrap_or_default();
+            extensions.iter().any(|x| x.eq_ignore_ascii_case(ext))
+        };
+        let mut candidates = self.backends().filter(|x| {
+            matches(x.extensions(), output_path) && matches(x.input_extensions(), input_path)
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(x), None) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+fn extension(path: &str) -> Option<&str> {
+    std::path::Path::new(path).extension()?.to_str()
+}
@@ -0,0 +1,159 @@
+use crate::backend::{
+    errors::backend_error_kind::BackendErrorKind,
+    registry::{DynBackend, Options, Registry, TAB_EXTENSIONS},
+    BackendResult, BackendSelector,
+};
+use itertools::Itertools;
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(|x| x.to_string()).collect_vec()
+}
+
+const TAB: &str = "
+e|-------|
+B|-------|
+G|-----9-|
+D|-5h7/--|
+A|-------|
+E|-0-----|";
+
+fn run(backend: &dyn DynBackend, options: &[&str]) -> (BackendResult, Vec<u8>) {
+    let mut out = vec![];
+    let res = backend.process(&lines(TAB), &mut out, &Options::parse(options).unwrap());
+    (res, out)
+}
+
+fn option_error(res: BackendResult) -> String {
+    match res.err.map(|x| x.kind) {
+        Some(BackendErrorKind::InvalidOption(x)) => x,
+        other => panic!("{other:?}"),
+    }
+}
+
+/// The options without which a backend fails
+const REQUIRED: &[(&str, &[&str])] =
+    &[("transpose", &["semitones=-2"]), ("retune", &["to=drop-c"])];
+
+/// What `backend` reads: [TAB], or [TAB] written by the backend which writes its input
+fn input(registry: &Registry, backend: &dyn DynBackend) -> Vec<u8> {
+    let extension = backend.input_extensions()[0];
+    let mut writers = registry.backends().filter(|x| x.input_extensions() == TAB_EXTENSIONS);
+    let Some(writer) = writers.find(|x| x.extensions().contains(&extension)) else {
+        return TAB.as_bytes().to_vec();
+    };
+    let mut out = vec![];
+    let res = writer.process(&lines(TAB), &mut out, &Options::new());
+    assert!(res.err.is_none(), "{}: {:?}", writer.name(), res.err);
+    out
+}
+
+#[test]
+fn test_builtins() {
+    let registry = Registry::with_builtins();
+    let names = registry.backends().map(|x| x.name()).collect_vec();
+    assert_eq!(names.len(), 19);
+    assert!(names.iter().all_unique());
+    for backend in registry.backends() {
+        let name = backend.name();
+        assert!(!backend.description().is_empty(), "{name}");
+        assert!(backend.options().iter().all(|x| !x.1.is_empty()), "{name}");
+        let required = REQUIRED.iter().find(|x| x.0 == name).map_or(&[][..], |x| x.1);
+        let mut out = vec![];
+        let options = Options::parse(required).unwrap();
+        let res = backend.process_bytes(&input(&registry, backend), &mut out, &options);
+        assert!(res.err.is_none(), "{name}: {:?}", res.err);
+        assert!(!out.is_empty(), "{name}");
+    }
+    // the defaults of the options are the defaults of the settings, the CLI is checked against
+    // them in cli_args_tests
+    for selector in [
+        BackendSelector::Midi,
+        BackendSelector::Json,
+        BackendSelector::Abc(Default::default()),
+        BackendSelector::Audio(Default::default()),
+        BackendSelector::Svg(Default::default()),
+        BackendSelector::Html(Default::default()),
+    ] {
+        let name = selector.name();
+        let mut expected = vec![];
+        selector.process(&lines(TAB), &mut expected);
+        let (res, out) = run(registry.get(name).unwrap(), &[]);
+        assert!(res.err.is_none(), "{name}");
+        assert_eq!(out, expected, "{name}");
+    }
+}
+
+#[test]
+fn test_options() {
+    let registry = Registry::with_builtins();
+    let svg = registry.get("svg").unwrap();
+    let (res, out) = run(svg, &["width=300", "notation", "title = Riff"]);
+    assert!(res.err.is_none());
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("width=\"300\"") && out.contains(">Riff<"));
+
+    let err = option_error(run(svg, &["width=wide"]).0);
+    assert!(err.contains("`wide` is not a valid value for width"), "{err}");
+    let err = option_error(run(svg, &["tempo=100"]).0);
+    assert!(err.contains("Unknown option tempo"), "{err}");
+    let err = option_error(run(registry.get("midi").unwrap(), &["tempo=100"]).0);
+    assert!(err.contains("no options"), "{err}");
+    let err = option_error(run(registry.get("fixup").unwrap(), &["dump=tree"]).0);
+    assert!(err.contains("tick-stream, pretty-tracks"), "{err}");
+    let err = option_error(run(registry.get("retune").unwrap(), &[]).0);
+    assert!(err.contains("to is required"), "{err}");
+    for name in ["audio", "gp5", "html"] {
+        let err = option_error(run(registry.get(name).unwrap(), &["tempo=0"]).0);
+        assert!(err.contains("tempo should be from 1 to 1000"), "{name}: {err}");
+    }
+
+    let (res, out) = run(registry.get("transpose").unwrap(), &["semitones=-2"]);
+    assert!(res.err.is_none());
+    assert!(String::from_utf8(out).unwrap().contains("D|-3h5/--|"));
+    assert!(Options::parse(["=1"]).is_err());
+}
+
+#[test]
+fn test_lookup() {
+    let mut registry = Registry::with_builtins();
+    let pick = |registry: &Registry, input, output| {
+        registry.for_paths(input, output).map(|x| x.name().to_string())
+    };
+    assert_eq!(pick(&registry, "song.tab", "song.MID").as_deref(), Some("midi"));
+    assert_eq!(pick(&registry, "song.txt", "song.musicxml").as_deref(), Some("muxml"));
+    assert_eq!(pick(&registry, "song.musicxml", "song.tab").as_deref(), Some("from-muxml"));
+    assert_eq!(pick(&registry, "song.mid", "song.tab").as_deref(), Some("from-midi"));
+    assert!(registry.get("from-midi").unwrap().input_is_binary());
+    // every rewrite of a tab writes a tab, so they have to be picked by name
+    assert_eq!(pick(&registry, "song.tab", "song.tab"), None);
+    assert_eq!(pick(&registry, "-", "-"), None);
+
+    // a backend of another crate replaces the built-in one with the same name
+    struct Upper;
+    impl DynBackend for Upper {
+        fn name(&self) -> &str {
+            "json"
+        }
+        fn description(&self) -> &str {
+            "Writes the input in upper case"
+        }
+        fn extensions(&self) -> &[&str] {
+            &["upper"]
+        }
+        fn process(
+            &self, input: &[String], out: &mut dyn std::io::Write, options: &Options,
+        ) -> BackendResult {
+            let err = options.check(&[]).err();
+            let err = err.or_else(|| {
+                out.write_all(input.join("\n").to_uppercase().as_bytes()).err().map(|x| x.into())
+            });
+            BackendResult::new(vec![], err, None, None)
+        }
+    }
+    registry.register(Upper);
+    assert_eq!(registry.backends().count(), 19);
+    assert_eq!(pick(&registry, "song.tab", "song.json"), None);
+    let (res, out) = run(registry.for_paths("a.tab", "b.upper").unwrap(), &[]);
+    assert!(res.err.is_none());
+    assert!(String::from_utf8(out).unwrap().starts_with("\nE|-------|"));
+}
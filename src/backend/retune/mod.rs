@@ -0,0 +1,137 @@
+//! Rewrites a tab for another tuning, like Drop D to standard.
+
+use super::{
+    errors::backend_error::BackendError,
+    format::process_rewrite,
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::fingering::{
+    refret::{refret, RefretSettings},
+    Tuning,
+};
+
+pub struct RetuneBackend();
+
+#[derive(Clone, Debug)]
+pub struct RetuneSettings {
+    /// The tuning the tab is written in. Read from the string names if not set, which works for
+    /// tunings like Drop D, but not for ones with sharps or flats.
+    pub from: Option<Tuning>,
+    /// Has to have string names, see [Tuning::names]
+    pub to: Tuning,
+    /// Notes that would go above this fret are moved to another string
+    pub max_fret: u8,
+}
+
+impl Backend for RetuneBackend {
+    type BackendSettings = RetuneSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let refret_settings = RefretSettings {
+            source: settings.from,
+            tuning: Some(settings.to),
+            max_fret: settings.max_fret,
+            ..Default::default()
+        };
+        // the tab would be read back in another tuning
+        if settings.to.names().is_none() {
+            let message = format!(
+                "Tuning {} can't be written with one letter per string, like DADGBE",
+                settings.to
+            );
+            return BackendResult::new(
+                vec![],
+                Some(BackendError::invalid_option(message)),
+                None,
+                None,
+            );
+        }
+        process_rewrite(input, out, None, |parsed, diagnostics| {
+            refret(parsed, &refret_settings, diagnostics)
+        })
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("from", "The tuning the tab is written in. Read from the string names if not given."),
+        ("to", "The tuning to write the tab in, like `drop-d` or `DADGBE`, without sharps or flats. Required."),
+        ("max-fret", "The highest fret a note may be placed on, 24 by default"),
+    ];
+    registry.register(SelectorBackend::new(
+        "retune",
+        "Rewrites a tab for another tuning",
+        &[],
+        options,
+        |o| {
+            Ok(BackendSelector::Retune(RetuneSettings {
+                from: o.get("from")?,
+                to: o.require("to")?,
+                max_fret: o.get_or("max-fret", 24)?,
+            }))
+        },
+    ));
+}
+
+#[test]
+fn test_retune() {
+    use super::errors::backend_error_kind::BackendErrorKind;
+    let input = "
+e|-------|
+B|-------|
+G|-------|
+D|-----2-|
+A|-----2-|
+D|-0-2-0-|";
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let retune = |from: Option<&str>, to: &str| {
+        let mut out = vec![];
+        let settings = RetuneSettings {
+            from: from.map(|x| x.parse().unwrap()),
+            to: to.parse().unwrap(),
+            max_fret: 24,
+        };
+        let res = RetuneBackend::process(&lines, &mut out, settings);
+        assert!(res.err.is_none());
+        (String::from_utf8(out).unwrap(), res.diagnostics.len())
+    };
+    // the low D is out of range in standard tuning
+    assert_eq!(
+        retune(None, "standard"),
+        (
+            "e|-------|
+B|-------|
+G|-------|
+D|-----2-|
+A|-----2-|
+E|---0---|
+"
+            .to_string(),
+            2
+        )
+    );
+    // read as D standard, only the lowest string goes down a whole step
+    assert_eq!(
+        retune(Some("d-standard"), "drop-c"),
+        (
+            "D|-------|
+A|-------|
+F|-------|
+C|-----2-|
+G|-----2-|
+C|-2-4-2-|
+"
+            .to_string(),
+            0
+        )
+    );
+    // one letter per string can't name the sharps
+    let mut out = vec![];
+    let settings =
+        RetuneSettings { from: None, to: "half-step-down".parse().unwrap(), max_fret: 24 };
+    let res = RetuneBackend::process(&lines, &mut out, settings);
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidOption(_)));
+}
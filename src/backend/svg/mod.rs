@@ -0,0 +1,478 @@
+//! Draws a tab as an SVG image, optionally with a simplified staff in standard notation above it.
+//!
+//! Every tick of the tab takes the same width, and measures are wrapped into systems that fit the
+//! page width. All coordinates are whole pixels, so the same tab always gives the same file.
+//!
+//! The staff is read like in [super::lilypond]: a note rings through the `~` and technique ticks
+//! after it. It only shows the note heads, stems and accidentals of the written pitches, with no
+//! rests, beams or ties.
+
+#[cfg(test)]
+mod svg_tests;
+
+use std::{fmt::Write, ops::Range, time::Instant};
+
+use super::{
+    lilypond::{track_events, Event},
+    muxml::{
+        detect_key,
+        key::{AccidentalState, Key},
+    },
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    fingering::{refret::part_tunings, Tuning},
+    parser::{
+        parser::{parse, ParseResult, Track},
+        tab_element::TabElement,
+    },
+    rlen, time,
+};
+
+pub struct SvgBackend();
+
+#[derive(Clone, Debug)]
+pub struct SvgSettings {
+    /// The width of the image in pixels, which measures are wrapped to
+    pub width: u32,
+    /// Draw a staff in standard notation above the tab
+    pub notation: bool,
+    /// Written above the first system. Left out if not set.
+    pub title: Option<String>,
+}
+
+impl Default for SvgSettings {
+    fn default() -> Self {
+        Self { width: 800, notation: false, title: None }
+    }
+}
+
+impl Backend for SvgBackend {
+    type BackendSettings = SvgSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut image = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        if let Err(e) = write_svg(&parsed, &mut image, &settings) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(image.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("width", "The width of the image in pixels, 800 by default"),
+        ("notation", "Draw a staff in standard notation above the tab"),
+        ("title", "The title of the song"),
+    ];
+    registry.register(SelectorBackend::new(
+        "svg",
+        "Draws the tab as an SVG image",
+        &["svg"],
+        options,
+        |o| {
+            let default = SvgSettings::default();
+            Ok(BackendSelector::Svg(SvgSettings {
+                width: o.get_or("width", default.width)?,
+                notation: o.flag("notation")?,
+                title: o.get("title")?,
+            }))
+        },
+    ));
+}
+
+const MARGIN: u32 = 20;
+const TICK_WIDTH: u32 = 14;
+/// The room at both ends of a measure
+const MEASURE_PADDING: u32 = 6;
+/// The room for the string names at the start of a system
+const NAMES_WIDTH: u32 = 20;
+const STRING_GAP: u32 = 12;
+/// The distance between the lines of the staff. Every step of the scale is half of it.
+const LINE_GAP: u32 = 8;
+/// The room above the staff, and between the staff and the tab, for notes on ledger lines
+const STAFF_MARGIN: u32 = 36;
+/// The room above the tab for the measure number when there is no staff
+const TAB_MARGIN: u32 = 18;
+const SYSTEM_GAP: u32 = 24;
+
+/// The step of the scale on the bottom line of the staff, a written E4, counted like
+/// `octave * 7 + letter` with the octaves of [Key::spell]
+const BOTTOM_LINE_STEP: i32 = 4 * 7 + 2;
+/// The steps on the staff of the sharps of a key signature, in order. The flats are
+/// `FLAT_STEPS`.
+const SHARP_STEPS: [i32; 7] = [38, 35, 39, 36, 33, 37, 34];
+const FLAT_STEPS: [i32; 7] = [34, 37, 33, 36, 32, 35, 31];
+
+const STYLE: &str = "text{font-family:sans-serif;text-anchor:middle}
+line,path{stroke:black;stroke-width:1}
+.bar{stroke-width:1.5}
+.title{font-size:20px}
+.track{font-size:14px;font-weight:bold;text-anchor:start}
+.number{font-size:9px;text-anchor:start}
+.name{font-size:10px}
+.fret{font-size:11px;paint-order:stroke;stroke:white;stroke-width:3px}
+.tech{font-size:10px;font-style:italic;paint-order:stroke;stroke:white;stroke-width:3px}
+.clef{font-family:serif;font-size:34px}
+.acc{font-family:serif;font-size:13px}
+.head{stroke:black}
+.hollow{fill:white;stroke:black}";
+
+/// A string escaped for SVG text and attributes
+fn escaped(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A glyph for the accidentals of [AccidentalState::accidental]
+fn accidental_glyph(accidental: &str) -> &'static str {
+    match accidental {
+        "sharp" => "♯",
+        "double-sharp" => "𝄪",
+        "flat" => "♭",
+        "flat-flat" => "𝄫",
+        _ => "♮",
+    }
+}
+
+/// The width of a measure with `ticks` ticks
+fn measure_width(ticks: u32) -> u32 {
+    ticks * TICK_WIDTH + 2 * MEASURE_PADDING
+}
+
+/// Splits the measures of a track into systems which fit into `width` after a header of
+/// `header` pixels. A measure that is wider than that gets a system of its own.
+fn systems(parsed: &ParseResult, track: &Track, width: u32, header: u32) -> Vec<Range<usize>> {
+    let available = width.saturating_sub(2 * MARGIN + header);
+    let mut systems = vec![];
+    let mut start = 0;
+    let mut used = 0;
+    for (measure_number, measure_idx) in track.measures.iter().enumerate() {
+        let range = &parsed.measures[*measure_idx as usize].data_range;
+        let w = measure_width(rlen(range) / 6);
+        if used + w > available && measure_number > start {
+            systems.push(start..measure_number);
+            start = measure_number;
+            used = 0;
+        }
+        used += w;
+    }
+    if start < track.measures.len() {
+        systems.push(start..track.measures.len());
+    }
+    systems
+}
+
+/// Writes the whole image into `buf`
+pub fn write_svg(
+    parsed: &ParseResult, buf: &mut String, settings: &SvgSettings,
+) -> std::fmt::Result {
+    let mut svg = Svg {
+        parsed,
+        settings,
+        key: detect_key(parsed),
+        tunings: part_tunings(parsed),
+        body: String::new(),
+        y: MARGIN,
+    };
+    if let Some(title) = &settings.title {
+        svg.y += 20;
+        writeln!(
+            svg.body,
+            r#"<text x="{}" y="{}" class="title">{}</text>"#,
+            settings.width / 2,
+            svg.y,
+            escaped(title)
+        )?;
+        svg.y += 12;
+    }
+    for track in &parsed.tracks {
+        if parsed.tracks.len() > 1 {
+            svg.y += 14;
+            writeln!(
+                svg.body,
+                r#"<text x="{MARGIN}" y="{}" class="track">{}</text>"#,
+                svg.y,
+                escaped(&track.name)
+            )?;
+            svg.y += 4;
+        }
+        let events = track_events(parsed, track);
+        for system in systems(parsed, track, settings.width, svg.header_width()) {
+            svg.write_system(track, &events, system)?;
+        }
+    }
+    let height = svg.y - SYSTEM_GAP + MARGIN;
+    let width = settings.width;
+    writeln!(
+        buf,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    )?;
+    writeln!(buf, "<style>\n{STYLE}\n</style>")?;
+    writeln!(buf, r#"<rect width="{width}" height="{height}" fill="white"/>"#)?;
+    buf.push_str(&svg.body);
+    writeln!(buf, "</svg>")
+}
+
+/// The state of the drawing: what goes into the `<svg>` element, and how far down it is filled
+struct Svg<'a> {
+    parsed: &'a ParseResult,
+    settings: &'a SvgSettings,
+    key: Key,
+    tunings: Vec<Tuning>,
+    body: String,
+    y: u32,
+}
+
+impl Svg<'_> {
+    /// The room at the start of every system, for the string names and the clef and key
+    /// signature of the staff
+    fn header_width(&self) -> u32 {
+        match self.settings.notation {
+            true => NAMES_WIDTH.max(26 + 7 * self.key.fifths.unsigned_abs() as u32),
+            false => NAMES_WIDTH,
+        }
+    }
+
+    /// The tuning of the part the stream index belongs to
+    fn tuning(&self, stream_idx: usize) -> Tuning {
+        let part = self.parsed.offsets.partition_point(|x| x.1 as usize <= stream_idx);
+        self.tunings.get(part.saturating_sub(1)).copied().unwrap_or_default()
+    }
+
+    fn line(
+        &mut self, x1: u32, y1: u32, x2: u32, y2: u32, class: Option<&str>,
+    ) -> std::fmt::Result {
+        let class = class.map(|x| format!(r#" class="{x}""#)).unwrap_or_default();
+        writeln!(self.body, r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}"{class}/>"#)
+    }
+
+    fn text(&mut self, x: u32, y: u32, class: &str, text: &str) -> std::fmt::Result {
+        writeln!(self.body, r#"<text x="{x}" y="{y}" class="{class}">{text}</text>"#)
+    }
+
+    /// Draws the measures of a system and moves below it
+    fn write_system(
+        &mut self, track: &Track, events: &[Vec<Event>], measures: Range<usize>,
+    ) -> std::fmt::Result {
+        let parsed = self.parsed;
+        let ranges = measures
+            .clone()
+            .map(|x| parsed.measures[track.measures[x] as usize].data_range.clone())
+            .collect::<Vec<_>>();
+        let left = MARGIN;
+        let start = left + self.header_width();
+        let right = start + ranges.iter().map(|x| measure_width(rlen(x) / 6)).sum::<u32>();
+        let staff_top = self.y + STAFF_MARGIN;
+        let tab_top = match self.settings.notation {
+            true => staff_top + 4 * LINE_GAP + STAFF_MARGIN,
+            false => self.y + TAB_MARGIN,
+        };
+        let tab_bottom = tab_top + 5 * STRING_GAP;
+        let number_y = if self.settings.notation { staff_top - 24 } else { tab_top - 8 };
+        self.text(start, number_y, "number", &(measures.start + 1).to_string())?;
+
+        if self.settings.notation {
+            for line in 0..5 {
+                let y = staff_top + line * LINE_GAP;
+                self.line(left, y, right, y, None)?;
+            }
+            self.line(left, staff_top, left, staff_top + 4 * LINE_GAP, Some("bar"))?;
+            self.text(left + 10, staff_top + 3 * LINE_GAP + 6, "clef", "𝄞")?;
+            self.text(left + 10, staff_top + 4 * LINE_GAP + 20, "name", "8")?;
+            let (glyph, steps) = match self.key.fifths > 0 {
+                true => ("♯", SHARP_STEPS),
+                false => ("♭", FLAT_STEPS),
+            };
+            for (idx, step) in
+                steps.iter().take(self.key.fifths.unsigned_abs() as usize).enumerate()
+            {
+                let x = left + 26 + 7 * idx as u32;
+                self.text(x, self.step_y(staff_top, *step) + 4, "acc", glyph)?;
+            }
+        }
+
+        let tuning = self.tuning(*ranges[0].start() as usize);
+        for string in 0..6 {
+            let y = tab_top + string * STRING_GAP;
+            self.line(left, y, right, y, None)?;
+            const NAMES: [&str; 12] =
+                ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+            let name = NAMES[tuning.strings[string as usize] as usize % 12];
+            // like the first line of a tab, the highest string is written in lower case
+            let name = if string == 0 { name.to_lowercase() } else { name.to_string() };
+            self.text(left + 8, y + 4, "name", &name)?;
+        }
+        self.line(left, tab_top, left, tab_bottom, Some("bar"))?;
+
+        let mut x = start;
+        for (offset, range) in ranges.iter().enumerate() {
+            let measure_number = measures.start + offset;
+            let ticks = rlen(range) / 6;
+            self.write_tab_measure(*range.start() as usize, ticks, x, tab_top)?;
+            if self.settings.notation {
+                self.write_staff_measure(
+                    &events[measure_number],
+                    *range.start() as usize,
+                    x,
+                    staff_top,
+                )?;
+            }
+            x += measure_width(ticks);
+            self.line(x, tab_top, x, tab_bottom, Some("bar"))?;
+            if self.settings.notation {
+                self.line(x, staff_top, x, staff_top + 4 * LINE_GAP, Some("bar"))?;
+            }
+        }
+        self.y = tab_bottom + SYSTEM_GAP;
+        Ok(())
+    }
+
+    /// The horizontal center of a tick of the measure which starts at `x`
+    fn tick_x(x: u32, tick: u32) -> u32 {
+        x + MEASURE_PADDING + tick * TICK_WIDTH + TICK_WIDTH / 2
+    }
+
+    /// Writes the frets and techniques of a measure like they are written in the tab
+    fn write_tab_measure(
+        &mut self, measure_start: usize, ticks: u32, x: u32, tab_top: u32,
+    ) -> std::fmt::Result {
+        let stream = &self.parsed.tick_stream;
+        for tick in 0..ticks {
+            let tick_x = Self::tick_x(x, tick);
+            for string in 0..6 {
+                let idx = measure_start + tick as usize * 6 + string;
+                let (class, text) = match &stream[idx] {
+                    TabElement::Fret(fret) => ("fret", fret.to_string()),
+                    TabElement::DeadNote => ("fret", "x".to_string()),
+                    TabElement::HammerOn => ("tech", "h".to_string()),
+                    TabElement::Pull => ("tech", "p".to_string()),
+                    TabElement::Bend => ("tech", "b".to_string()),
+                    TabElement::Release => ("tech", "r".to_string()),
+                    TabElement::Vibrato => ("tech", "~".to_string()),
+                    TabElement::Slide => {
+                        let fret = |idx: Option<usize>| match idx.and_then(|x| stream.get(x)) {
+                            Some(TabElement::Fret(x)) => Some(*x),
+                            _ => None,
+                        };
+                        let down = matches!(
+                            (fret(idx.checked_sub(6)), fret(Some(idx + 6))),
+                            (Some(from), Some(to)) if to < from
+                        );
+                        ("tech", if down { "\\" } else { "/" }.to_string())
+                    }
+                    TabElement::Rest => continue,
+                };
+                self.text(tick_x, tab_top + string as u32 * STRING_GAP + 4, class, &text)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The height of a step of the scale on the staff whose top line is at `staff_top`
+    fn step_y(&self, staff_top: u32, step: i32) -> u32 {
+        let bottom = (staff_top + 4 * LINE_GAP) as i32;
+        (bottom - (step - BOTTOM_LINE_STEP) * LINE_GAP as i32 / 2) as u32
+    }
+
+    /// Draws the chords of a measure as note heads at the written pitch, with a stem (and a flag
+    /// for eighths) that shows their length
+    fn write_staff_measure(
+        &mut self, events: &[Event], measure_start: usize, x: u32, staff_top: u32,
+    ) -> std::fmt::Result {
+        let stream = &self.parsed.tick_stream;
+        let tuning = self.tuning(measure_start);
+        let mut accidentals = AccidentalState::new(self.key);
+        let mut tick = 0;
+        for event in events {
+            let event_tick = tick;
+            tick += event.len;
+            let Some(stream_tick) = event.tick else {
+                continue;
+            };
+            let head_x = Self::tick_x(x, event_tick);
+            // the written pitch of every note, lowest first
+            let mut notes = (0..6)
+                .filter_map(|string| match stream[stream_tick + string] {
+                    TabElement::Fret(fret) => Some((tuning.strings[string] + fret, false)),
+                    TabElement::DeadNote => Some((tuning.strings[string], true)),
+                    _ => None,
+                })
+                .map(|(pitch, dead)| (self.key.spell(pitch), dead))
+                .collect::<Vec<_>>();
+            notes.sort_by_key(|(spelling, _)| (spelling.octave, "CDEFGAB".find(spelling.step)));
+            let steps = notes
+                .iter()
+                .map(|(spelling, _)| {
+                    spelling.octave as i32 * 7 + "CDEFGAB".find(spelling.step).unwrap() as i32
+                })
+                .collect::<Vec<_>>();
+            let (Some(lowest), Some(highest)) = (steps.first(), steps.last()) else {
+                continue;
+            };
+
+            for ((spelling, dead), step) in notes.iter().zip(&steps) {
+                let y = self.step_y(staff_top, *step);
+                // ledger lines below and above the staff
+                let ledgers = (*step..BOTTOM_LINE_STEP - 1).chain(BOTTOM_LINE_STEP + 9..*step + 1);
+                for ledger in ledgers.filter(|x| (x - BOTTOM_LINE_STEP) % 2 == 0) {
+                    let ledger_y = self.step_y(staff_top, ledger);
+                    self.line(head_x - 8, ledger_y, head_x + 8, ledger_y, None)?;
+                }
+                if let Some(accidental) = accidentals.accidental(spelling) {
+                    self.text(head_x - 11, y + 4, "acc", accidental_glyph(accidental))?;
+                }
+                match (dead, event.len) {
+                    (true, _) => self.text(head_x, y + 4, "fret", "x")?,
+                    (false, 4..) => writeln!(
+                        self.body,
+                        r#"<ellipse cx="{head_x}" cy="{y}" rx="5" ry="4" class="hollow"/>"#
+                    )?,
+                    (false, _) => writeln!(
+                        self.body,
+                        r#"<ellipse cx="{head_x}" cy="{y}" rx="5" ry="4" class="head"/>"#
+                    )?,
+                }
+                if matches!(event.len, 3 | 6) {
+                    self.text(head_x + 9, y + 2, "name", ".")?;
+                }
+            }
+
+            if event.len >= 8 {
+                continue;
+            }
+            // stems go up from notes below the middle line
+            let low_y = self.step_y(staff_top, *lowest);
+            let high_y = self.step_y(staff_top, *highest);
+            let (stem_x, from, to) = match lowest + highest < 2 * (BOTTOM_LINE_STEP + 4) {
+                true => (head_x + 5, low_y, high_y - 3 * LINE_GAP),
+                false => (head_x - 5, high_y, low_y + 3 * LINE_GAP),
+            };
+            self.line(stem_x, from, stem_x, to, None)?;
+            if event.len == 1 {
+                let flag_to = if to < from { to + LINE_GAP } else { to - LINE_GAP };
+                writeln!(
+                    self.body,
+                    r#"<path d="M{stem_x} {to} Q{} {} {} {flag_to}" fill="none"/>"#,
+                    stem_x + 8,
+                    (to + flag_to) / 2,
+                    stem_x + 6
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use crate::backend::svg::{SvgBackend, SvgSettings};
+use crate::backend::Backend;
+
+fn render(input: &str, settings: SvgSettings) -> String {
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut out = vec![];
+    let res = SvgBackend::process(&lines, &mut out, settings);
+    assert!(res.err.is_none(), "{:?}", res.err);
+    String::from_utf8(out).unwrap()
+}
+
+const RIFF: &str = "
+e|-----------|--------|
+B|-----------|--------|
+G|---------7~|~~------|
+D|-5h7-5/7---|--------|
+A|-----------|-x------|
+E|-0---------|--------|";
+
+#[test]
+fn test_svg_tab() {
+    insta::assert_snapshot!(render(RIFF, SvgSettings::default()));
+}
+
+#[test]
+fn test_svg_notation() {
+    let settings = SvgSettings {
+        notation: true,
+        title: Some("Riff & Fill".to_string()),
+        ..Default::default()
+    };
+    insta::assert_snapshot!(render(RIFF, settings));
+}
+
+#[test]
+fn test_svg_wrapping() {
+    let input = "
+e|--------|--------|--------|--------|
+B|--------|--------|--------|--------|
+G|--------|--------|--------|--------|
+D|-5------|-7------|-9------|-10-----|
+A|--------|--------|--------|--------|
+E|--------|--------|--------|--------|";
+    // every measure takes 8 ticks and its padding, and two of them fit next to the names
+    let settings = SvgSettings { width: 320, ..Default::default() };
+    let svg = render(input, settings.clone());
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="320""#));
+    assert_eq!(svg.matches(r#"class="number""#).count(), 2);
+    assert!(svg.contains(r#"class="number">3</text>"#));
+    // nothing is drawn past the right margin
+    let max_x = svg
+        .split(['"', ' '])
+        .collect::<Vec<_>>()
+        .windows(2)
+        .filter(|x| matches!(x[0], "x=" | "x1=" | "x2="))
+        .map(|x| x[1].parse::<u32>().unwrap())
+        .max()
+        .unwrap();
+    assert!(max_x <= 300, "{max_x}");
+    // the output only depends on the input
+    assert_eq!(render(input, settings), svg);
+}
@@ -0,0 +1,107 @@
+//! Transposes a tab by a number of semitones, keeping its tuning.
+
+use super::{
+    format::process_rewrite,
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::fingering::refret::{refret, RefretSettings};
+
+pub struct TransposeBackend();
+
+#[derive(Clone, Debug)]
+pub struct TransposeSettings {
+    /// Semitones to transpose by, negative values go down
+    pub semitones: i16,
+    /// Notes that would go above this fret are moved to a lower string
+    pub max_fret: u8,
+}
+
+impl Backend for TransposeBackend {
+    type BackendSettings = TransposeSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let refret_settings = RefretSettings {
+            shift: settings.semitones,
+            max_fret: settings.max_fret,
+            ..Default::default()
+        };
+        process_rewrite(input, out, None, |parsed, diagnostics| {
+            refret(parsed, &refret_settings, diagnostics)
+        })
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("semitones", "Semitones to transpose by, negative values go down. Required."),
+        ("max-fret", "The highest fret a note may be placed on, 24 by default"),
+    ];
+    registry.register(SelectorBackend::new(
+        "transpose",
+        "Transposes a tab, keeping its tuning",
+        &[],
+        options,
+        |o| {
+            Ok(BackendSelector::Transpose(TransposeSettings {
+                semitones: o.require("semitones")?,
+                max_fret: o.get_or("max-fret", 24)?,
+            }))
+        },
+    ));
+}
+
+#[test]
+fn test_transpose() {
+    let input = "
+// riff
+e|-----------|
+B|-----------|
+G|-----------|
+D|-----------|
+A|-0h2-3b5r3-|
+E|-----------|";
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let transpose = |semitones| {
+        let mut out = vec![];
+        let settings = TransposeSettings { semitones, max_fret: 24 };
+        let res = TransposeBackend::process(&lines, &mut out, settings);
+        assert!(res.err.is_none());
+        (String::from_utf8(out).unwrap(), res.diagnostics.len())
+    };
+    assert_eq!(transpose(60).1, 2);
+    // the columns widen where the frets get two digits
+    assert_eq!(
+        transpose(9),
+        (
+            "// riff
+e|---------------|
+B|---------------|
+G|---------------|
+D|---------------|
+A|-9h11-12b14r12-|
+E|---------------|
+"
+            .to_string(),
+            0
+        )
+    );
+    // the open A can't go lower on its string, so the whole phrase moves to the low E
+    assert_eq!(
+        transpose(-1),
+        (
+            "// riff
+e|-----------|
+B|-----------|
+G|-----------|
+D|-----------|
+A|-----2b4r2-|
+E|-4h6-------|
+"
+            .to_string(),
+            0
+        )
+    );
+}
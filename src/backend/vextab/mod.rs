@@ -0,0 +1,271 @@
+//! Writes VexTab, the text format of [VexFlow](https://vexflow.com)'s tab renderer.
+//!
+//! Every line of the tab becomes a `tabstave`. The rhythm is read like in [super::lilypond].
+//! VexTab writes techniques inside a single token of notes of the same length, like `5h7/4`, so
+//! notes of different lengths which are connected by a technique are written as tied eighths,
+//! like `:8 5T5h7/4`. Ties never cross a barline.
+
+#[cfg(test)]
+mod vextab_tests;
+
+use std::{fmt::Write, time::Instant};
+
+use super::{
+    lilypond::{note_lengths, track_events},
+    registry::{Registry, SelectorBackend},
+    Backend, BackendResult, BackendSelector,
+};
+use crate::{
+    fingering::{refret::part_tunings, Tuning},
+    parser::{parser::parse, parser::ParseResult, tab_element::TabElement},
+    rlen, time,
+};
+
+pub struct VexTabBackend();
+
+#[derive(Clone, Debug, Default)]
+pub struct VexTabSettings {
+    /// Only draw the tab, without a staff in standard notation above it
+    pub tab_only: bool,
+}
+
+impl Backend for VexTabBackend {
+    type BackendSettings = VexTabSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let (parse_time, parsed) = time(|| parse(input));
+        if let Some(e) = parsed.error {
+            return BackendResult::new(vec![], Some(e), Some(parse_time), None);
+        }
+        let gen_start = Instant::now();
+        let mut tab = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        if let Err(e) = write_vextab(&parsed, &mut tab, &settings) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(tab.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    registry.register(SelectorBackend::new(
+        "vextab",
+        "Writes VexTab, for rendering with VexFlow",
+        &["vextab"],
+        &[("tab-only", "Only draw the tab, without a staff")],
+        |o| Ok(BackendSelector::VexTab(VexTabSettings { tab_only: o.flag("tab-only")? })),
+    ));
+}
+
+/// The VexTab duration of one of the [note_lengths]
+fn duration(eighths: u32) -> &'static str {
+    match eighths {
+        8 => "w",
+        6 => "hd",
+        4 => "h",
+        3 => "qd",
+        2 => "q",
+        _ => "8",
+    }
+}
+
+/// What is played on a beat
+#[derive(PartialEq)]
+enum Beat {
+    Rest,
+    /// A single note, as its fret (or `X`) and string
+    Note(String, usize),
+    /// The notes of a chord, like `5/2.5/3`
+    Chord(String),
+}
+
+/// A beat and the technique that connects it to the next one, like `h` or `T` for a tie
+struct Piece {
+    len: u32,
+    beat: Beat,
+    join: Option<char>,
+}
+
+/// The technique leading from the note at `idx` to the next note on its string, if that note is
+/// the next thing played
+fn technique(parsed: &ParseResult, idx: usize, measure_end: usize) -> Option<char> {
+    if idx + 12 >= measure_end || !matches!(parsed.tick_stream[idx + 12], TabElement::Fret(_)) {
+        return None;
+    }
+    match parsed.tick_stream[idx + 6] {
+        TabElement::HammerOn => Some('h'),
+        TabElement::Pull => Some('p'),
+        TabElement::Slide => Some('s'),
+        TabElement::Bend | TabElement::Release => Some('b'),
+        _ => None,
+    }
+}
+
+/// Joins the pieces of a measure into tokens, like `:q 5h7/4`
+fn write_measure(buf: &mut String, pieces: &[Piece], last_len: &mut Option<u32>) {
+    let mut idx = 0;
+    while idx < pieces.len() {
+        let piece = &pieces[idx];
+        if *last_len != Some(piece.len) {
+            write!(buf, ":{} ", duration(piece.len)).unwrap();
+            *last_len = Some(piece.len);
+        }
+        // the pieces joined into this token
+        let mut end = idx + 1;
+        while end < pieces.len() && pieces[end].len == piece.len {
+            let joins = match (&pieces[end - 1], &pieces[end].beat) {
+                (Piece { beat: Beat::Note(_, a), join: Some(_), .. }, Beat::Note(_, b)) => a == b,
+                (Piece { beat: Beat::Chord(a), join: Some('T'), .. }, Beat::Chord(b)) => a == b,
+                _ => false,
+            };
+            if !joins {
+                break;
+            }
+            end += 1;
+        }
+        for (offset, piece) in pieces[idx..end].iter().enumerate() {
+            if offset > 0 {
+                buf.push(pieces[idx + offset - 1].join.unwrap());
+            }
+            match &piece.beat {
+                Beat::Rest => buf.push_str("##"),
+                Beat::Note(fret, _) => buf.push_str(fret),
+                Beat::Chord(notes) => write!(buf, "({notes})").unwrap(),
+            }
+        }
+        if let Beat::Note(_, string) = piece.beat {
+            write!(buf, "/{string}").unwrap();
+        }
+        buf.push(' ');
+        idx = end;
+    }
+}
+
+/// Writes the whole document into `buf`
+pub fn write_vextab(
+    parsed: &ParseResult, buf: &mut String, settings: &VexTabSettings,
+) -> std::fmt::Result {
+    let tunings = part_tunings(parsed);
+    let part_of = |stream_idx: usize| {
+        parsed.offsets.partition_point(|x| x.1 as usize <= stream_idx).saturating_sub(1)
+    };
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        if track_idx > 0 {
+            buf.push('\n');
+        }
+        let measures = track_events(parsed, track);
+        let mut last_len = None;
+        let mut current_part = None;
+        for (measure_number, events) in measures.iter().enumerate() {
+            let range = &parsed.measures[track.measures[measure_number] as usize].data_range;
+            let measure_start = *range.start() as usize;
+            let measure_end = *range.end() as usize + 1;
+            let part = part_of(measure_start);
+            if current_part != Some(part) {
+                if current_part.is_some() {
+                    buf.truncate(buf.trim_end().len());
+                    buf.push_str("\n\n");
+                }
+                current_part = Some(part);
+                last_len = None;
+                write!(buf, "tabstave notation={}", !settings.tab_only)?;
+                let tuning = tunings.get(part).copied().unwrap_or_default();
+                if tuning != Tuning::STANDARD {
+                    const NAMES: [&str; 12] =
+                        ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+                    // written an octave above the sounding pitch, like E/5 for the high e
+                    let strings = tuning
+                        .strings
+                        .iter()
+                        .map(|x| format!("{}/{}", NAMES[*x as usize % 12], x / 12));
+                    write!(buf, " tuning={}", strings.collect::<Vec<_>>().join(","))?;
+                }
+                if !settings.tab_only {
+                    write!(buf, " time={}/8", rlen(range) / 6)?;
+                }
+                buf.push_str("\nnotes ");
+            }
+
+            let notes = events
+                .iter()
+                .map(|event| {
+                    let tick = event.tick?;
+                    let notes =
+                        (0..6).filter_map(|string| match &parsed.tick_stream[tick + string] {
+                            TabElement::Fret(fret) => Some((fret.to_string(), string + 1)),
+                            TabElement::DeadNote => Some(("X".to_string(), string + 1)),
+                            _ => None,
+                        });
+                    Some(notes.collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>();
+            // the technique leading from every event to the next one
+            let techniques = events
+                .iter()
+                .zip(&notes)
+                .map(|(event, notes)| match (event.tick, notes.as_deref()) {
+                    (Some(tick), Some([(_, string)])) if !event.tied => {
+                        technique(parsed, tick + string - 1, measure_end)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            // a chain of notes connected by techniques is written in eighths if its notes have
+            // different lengths, tying the longer ones together
+            let mut in_eighths = vec![false; events.len()];
+            let mut chain_start = 0;
+            for idx in 0..events.len() {
+                if techniques[idx].is_some() {
+                    continue;
+                }
+                let chain = chain_start..idx + 1;
+                if events[chain.clone()].iter().any(|x| x.len != events[idx].len) {
+                    in_eighths[chain].fill(true);
+                }
+                chain_start = idx + 1;
+            }
+
+            let mut pieces = vec![];
+            for (event_idx, event) in events.iter().enumerate() {
+                let lengths = match in_eighths[event_idx] {
+                    true => vec![1; event.len as usize],
+                    false => note_lengths(event.len),
+                };
+                let Some(notes) = &notes[event_idx] else {
+                    pieces.extend(lengths.into_iter().map(|len| Piece {
+                        len,
+                        beat: Beat::Rest,
+                        join: None,
+                    }));
+                    continue;
+                };
+                let beat = || match notes.as_slice() {
+                    [(fret, string)] => Beat::Note(fret.clone(), *string),
+                    notes => Beat::Chord(
+                        notes.iter().map(|(f, s)| format!("{f}/{s}")).collect::<Vec<_>>().join("."),
+                    ),
+                };
+                let count = lengths.len();
+                for (idx, len) in lengths.into_iter().enumerate() {
+                    let join = match idx + 1 < count {
+                        true => Some('T'),
+                        false => techniques[event_idx],
+                    };
+                    pieces.push(Piece { len, beat: beat(), join });
+                }
+            }
+            write_measure(buf, &pieces, &mut last_len);
+            buf.push_str("| ");
+        }
+        buf.truncate(buf.trim_end().len());
+        buf.push('\n');
+    }
+    Ok(())
+}
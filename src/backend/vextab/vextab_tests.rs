@@ -0,0 +1,16 @@
+use crate::backend::vextab::{VexTabBackend, VexTabSettings};
+use crate::backend::{process_input_file, INPUT_TABS};
+
+#[test]
+fn test_vextab() {
+    for name in INPUT_TABS {
+        let out = process_input_file::<VexTabBackend>(name, VexTabSettings::default());
+        insta::assert_snapshot!(name, out);
+    }
+}
+
+#[test]
+fn test_vextab_tab_only() {
+    let out = process_input_file::<VexTabBackend>("ties", VexTabSettings { tab_only: true });
+    assert!(out.starts_with("tabstave notation=false"), "{out}");
+}
@@ -1,9 +1,28 @@
 use std::fmt::Display;
 
+use anyhow::{anyhow, bail};
 use clap::{Parser, Subcommand};
-use scoreman::backend::{
-    fixup::{FixupBackendSettings, FixupDumpOptions},
-    muxml, BackendSelector,
+use scoreman::{
+    backend::{
+        abc::AbcSettings,
+        alphatex::AlphaTexSettings,
+        audio::AudioSettings,
+        fixup::{FixupBackendSettings, FixupDumpOptions},
+        format::FormatSettings,
+        gp5::Gp5Settings,
+        html::HtmlSettings,
+        lilypond::LilypondSettings,
+        muxml,
+        registry::{DynBackend, Options, Registry},
+        retune::RetuneSettings,
+        svg::SvgSettings,
+        transpose::TransposeSettings,
+        vextab::VexTabSettings,
+        BackendSelector,
+    },
+    fingering::{optimize::FingeringSettings, Tuning},
+    import::{ir::JsonImportSettings, midi::MidiImportSettings, ImportSettings},
+    tab_writer::TabWriterSettings,
 };
 
 #[derive(Parser)]
@@ -39,11 +58,273 @@ pub enum Commands {
         #[arg(short = 't', long)]
         /// Simplify time signature, e.g. 8/8 -> 4/4
         simplify_time_signature: bool,
+        /// Key signature, as a name (`F`, `Bb`, `F#m`) or number of fifths (`-1`). Detected from
+        /// the notes if not given.
+        #[arg(short = 'k', long)]
+        key: Option<muxml::key::Key>,
+        /// The clef to write the score in. Use `bass` for bass tabs.
+        #[arg(value_enum, short = 'c', long, default_value_t)]
+        clef: muxml::settings::Clef,
+        /// Guitars sound an octave below the written pitch. `sounding` writes the real pitch with
+        /// an octave clef, `transposing` writes the usual pitch and marks the transposition, so
+        /// that playback in notation software is correct.
+        #[arg(value_enum, short = 'o', long, default_value_t)]
+        octave: muxml::settings::OctaveMode,
+        /// Group consecutive empty measures into a single multi-measure rest
+        #[arg(short = 'r', long)]
+        multi_measure_rests: bool,
+        /// The time signature of the piece, like 3/4. Detected as the most common measure length
+        /// if not given. Shorter measures are filled up with rests, longer ones get their own
+        /// time signature.
+        #[arg(long)]
+        meter: Option<muxml::settings::Meter>,
+        /// Split the staff into two voices, for fingerstyle tabs with a bass line under a melody.
+        /// Either a string number (`4` puts strings 4-6 into the lower voice), or a written pitch
+        /// (`G3` puts every note below it into the lower voice).
+        #[arg(short = 'v', long)]
+        voice_split: Option<muxml::settings::VoiceSplit>,
+        input_path: String,
+        output_path: String,
+    },
+    /// Writes a LilyPond file with a staff in standard notation above a tab staff, which can be
+    /// engraved with `lilypond`.
+    #[command(visible_alias = "ly")]
+    Lilypond {
         input_path: String,
         output_path: String,
+        /// Key signature, as a name (`F`, `Bb`, `F#m`) or number of fifths (`-1`). Detected from
+        /// the notes if not given.
+        #[arg(short = 'k', long)]
+        key: Option<muxml::key::Key>,
+        /// Simplify time signature, e.g. 8/8 -> 4/4
+        #[arg(short = 't', long)]
+        simplify_time_signature: bool,
+    },
+    /// Writes a tune in ABC notation. The string and fret of every note are kept in `%%tab`
+    /// lines above the music.
+    Abc {
+        input_path: String,
+        output_path: String,
+        /// The title of the tune. The name of the first track is used if not given.
+        #[arg(short = 'T', long)]
+        title: Option<String>,
+        /// Key signature, as a name (`F`, `Bb`, `F#m`) or number of fifths (`-1`). Detected from
+        /// the notes if not given.
+        #[arg(short = 'k', long)]
+        key: Option<muxml::key::Key>,
+        /// The time signature of the tune, like 3/4. Detected as the most common measure length
+        /// if not given.
+        #[arg(long)]
+        meter: Option<muxml::settings::Meter>,
+        /// Simplify time signature, e.g. 8/8 -> 4/4
+        #[arg(short = 't', long)]
+        simplify_time_signature: bool,
+    },
+    /// Writes alphaTex, which alphaTab renders as a tab with standard notation in the browser.
+    #[command(name = "alphatex", visible_alias = "alphatab")]
+    AlphaTex {
+        input_path: String,
+        output_path: String,
+        /// The title of the song
+        #[arg(short = 'T', long)]
+        title: Option<String>,
+    },
+    /// Writes VexTab, which VexFlow renders as a tab with standard notation in the browser.
+    #[command(name = "vextab")]
+    VexTab {
+        input_path: String,
+        output_path: String,
+        /// Only draw the tab, without a staff in standard notation above it
+        #[arg(long)]
+        tab_only: bool,
+    },
+    /// Renders the tab to a 16-bit WAV file with a plucked string synth.
+    #[command(visible_alias = "wav")]
+    Audio {
+        input_path: String,
+        output_path: String,
+        /// In quarter notes per minute, from 1 to 1000
+        #[arg(long, default_value_t = 80)]
+        tempo: u32,
+        /// In samples per second, from 8000 to 192000
+        #[arg(long, default_value_t = 44100)]
+        sample_rate: u32,
+    },
+    /// Draws the tab as an SVG image, optionally with a staff in standard notation above it.
+    Svg {
+        input_path: String,
+        output_path: String,
+        /// The width of the image in pixels. Measures are wrapped to fit into it.
+        #[arg(short = 'w', long, default_value_t = 800)]
+        width: u32,
+        /// Draw a simplified staff in standard notation above the tab
+        #[arg(short = 'n', long)]
+        notation: bool,
+        /// The title of the song
+        #[arg(short = 'T', long)]
+        title: Option<String>,
+    },
+    /// Writes a Guitar Pro 5 (.gp5) file, which Guitar Pro and TuxGuitar can open.
+    #[command(name = "gp5", visible_alias = "guitar-pro")]
+    Gp5 {
+        input_path: String,
+        output_path: String,
+        /// The title of the song
+        #[arg(short = 'T', long)]
+        title: Option<String>,
+        /// In quarter notes per minute, from 1 to 1000
+        #[arg(long, default_value_t = 80)]
+        tempo: u32,
+    },
+    /// Writes a single HTML page with the tab and a player which highlights the notes it plays.
+    Html {
+        input_path: String,
+        output_path: String,
+        /// The title of the page. The name of the first track is used if not given.
+        #[arg(short = 'T', long)]
+        title: Option<String>,
+        /// The tempo the player starts with, in quarter notes per minute, from 1 to 1000
+        #[arg(long, default_value_t = 80)]
+        tempo: u32,
     },
     /// The simplest backend, used mainly for playback in interactive applications. Produces a .smf file.
     Midi { input_path: String, output_path: String },
+    /// Writes the parsed score as JSON, with the string, fret, pitch and source location of every
+    /// note, for other tools to read.
+    Json { input_path: String, output_path: String },
+
+    /// Reads a .musicxml file and writes it as a tab. Notes without a string and fret in the
+    /// file are placed on the fretboard automatically.
+    #[command(visible_alias = "from-musicxml")]
+    FromMuxml {
+        input_path: String,
+        output_path: String,
+        /// The highest fret a note may be placed on
+        #[arg(short = 'f', long, default_value_t = 24)]
+        max_fret: u8,
+        /// How many measures to write on a line
+        #[arg(short = 'm', long, default_value_t = 4)]
+        measures_per_line: usize,
+        /// The maximum number of characters on a line. A measure is never split, so one that is
+        /// wider than this gets a line of its own.
+        #[arg(short = 'w', long)]
+        width: Option<usize>,
+    },
+
+    /// Reads a JSON file written by the json backend and writes it as a tab.
+    FromJson {
+        input_path: String,
+        output_path: String,
+        /// Re-wrap the measures into Parts of this many measures. Every Part of the JSON is kept
+        /// on a line of its own if not given.
+        #[arg(short = 'm', long)]
+        measures_per_line: Option<usize>,
+        /// Re-wrap the measures into Parts of at most this many characters per line. A measure
+        /// is never split, so one that is wider than this gets a line of its own.
+        #[arg(short = 'w', long)]
+        width: Option<usize>,
+    },
+
+    /// Reads a MIDI file and writes one of its channels as a tab. Note onsets are quantized to
+    /// the columns of the tab.
+    #[command(visible_alias = "from-smf")]
+    FromMidi {
+        input_path: String,
+        output_path: String,
+        /// Only read this track, counting from 1. Every track is read by default.
+        #[arg(short = 't', long)]
+        track: Option<usize>,
+        /// Only read this channel, counting from 1. By default, the channel of the first note is
+        /// read, skipping percussion.
+        #[arg(short = 'c', long, value_parser = clap::value_parser!(u8).range(1..=16))]
+        channel: Option<u8>,
+        /// How many columns of the tab a quarter note takes
+        #[arg(short = 'r', long, default_value_t = 2)]
+        columns_per_quarter: u32,
+        /// The highest fret a note may be placed on
+        #[arg(short = 'f', long, default_value_t = 24)]
+        max_fret: u8,
+        /// How many measures to write on a line
+        #[arg(short = 'm', long, default_value_t = 4)]
+        measures_per_line: usize,
+        /// The maximum number of characters on a line. A measure is never split, so one that is
+        /// wider than this gets a line of its own.
+        #[arg(short = 'w', long)]
+        width: Option<usize>,
+    },
+
+    /// Rewrites a tab in a canonical layout: columns are aligned to their widest element, and
+    /// comments and other lines outside of Parts are kept.
+    #[command(visible_alias = "format")]
+    Fmt {
+        input_path: String,
+        #[arg(default_value = "-")]
+        output_path: String,
+        /// Don't write the formatted tab, only fail if it differs from the input. Useful in CI.
+        #[arg(long)]
+        check: bool,
+        /// Re-wrap the measures into Parts of this many measures. Parts that are separated by
+        /// anything other than blank lines are never joined. Parts are kept as they are if not
+        /// given.
+        #[arg(short = 'm', long)]
+        measures_per_line: Option<usize>,
+        /// Re-wrap the measures into Parts of at most this many characters per line. A measure
+        /// is never split, so one that is wider than this gets a line of its own.
+        #[arg(short = 'w', long)]
+        width: Option<usize>,
+    },
+
+    /// Transposes a tab by a number of semitones, keeping its tuning. Notes that would fall off
+    /// the fretboard are moved to another string, together with their bends and slides.
+    Transpose {
+        input_path: String,
+        output_path: String,
+        /// Semitones to transpose by, negative values go down
+        #[arg(short = 's', long, allow_negative_numbers = true)]
+        semitones: i16,
+        /// The highest fret a note may be placed on
+        #[arg(short = 'f', long, default_value_t = 24)]
+        max_fret: u8,
+    },
+
+    /// Rewrites a tab for another tuning. Notes keep their pitch, and move to another string
+    /// if they fall off the fretboard. Notes below the lowest string are left out.
+    Retune {
+        input_path: String,
+        output_path: String,
+        /// The tuning the tab is written in, as a preset (`drop-d`, `half-step-down`) or six
+        /// notes from the lowest string (`DADGBE`, `Eb Ab Db Gb Bb Eb`). Read from the string
+        /// names if not given.
+        #[arg(long)]
+        from: Option<Tuning>,
+        /// The tuning to write the tab in, like `--from`, but without sharps or flats
+        #[arg(long)]
+        to: Tuning,
+        /// The highest fret a note may be placed on
+        #[arg(short = 'f', long, default_value_t = 24)]
+        max_fret: u8,
+    },
+
+    /// Moves the notes of a tab to the strings that are easiest to play, keeping the hand in as
+    /// few positions as possible. Notes with techniques like hammer-ons or bends stay where they
+    /// are.
+    Refinger {
+        input_path: String,
+        output_path: String,
+        /// The most frets the hand may stretch over in a chord
+        #[arg(short = 's', long, default_value_t = 4)]
+        max_span: u8,
+        /// Play the tab with a capo on this fret. The frets of the input are counted from the
+        /// nut, the frets of the output from the capo.
+        #[arg(short = 'c', long, default_value_t = 0)]
+        capo: u8,
+        /// Avoid open strings when a note can be fretted instead
+        #[arg(long)]
+        no_open_strings: bool,
+        /// The highest fret a note may be placed on
+        #[arg(short = 'f', long, default_value_t = 24)]
+        max_fret: u8,
+    },
 
     /// Tries to fix errors in the score, until it can be parsed.
     Fixup {
@@ -53,13 +334,46 @@ pub enum Commands {
         #[arg(value_enum, short = 'd', long)]
         dump: Option<FixupDumpOptions>,
     },
+
+    /// Runs any registered backend, picked by name or by the extensions of the input and output
+    /// files, with its settings given as options. Lists the backends and their options if the
+    /// backend can't be found.
+    Convert {
+        input_path: String,
+        output_path: String,
+        /// The name of the backend, like `midi`. Picked from the file extensions if not given.
+        #[arg(short = 'b', long)]
+        backend: Option<String>,
+        /// A setting of the backend as `key=value`, like `-o tempo=120`. A key on its own turns
+        /// on a flag.
+        #[arg(short = 'o', long = "option")]
+        options: Vec<String>,
+    },
 }
 
 impl Commands {
     pub fn input_path(&self) -> &str {
         match self {
-            Commands::Muxml { input_path, .. } | Commands::Midi { input_path, .. } => input_path,
-            Commands::Fixup { input_path, .. } => input_path,
+            Commands::Muxml { input_path, .. }
+            | Commands::Lilypond { input_path, .. }
+            | Commands::Abc { input_path, .. }
+            | Commands::AlphaTex { input_path, .. }
+            | Commands::VexTab { input_path, .. }
+            | Commands::Gp5 { input_path, .. }
+            | Commands::Html { input_path, .. }
+            | Commands::Svg { input_path, .. }
+            | Commands::Audio { input_path, .. }
+            | Commands::Json { input_path, .. }
+            | Commands::Midi { input_path, .. } => input_path,
+            Commands::Fixup { input_path, .. }
+            | Commands::FromMuxml { input_path, .. }
+            | Commands::FromJson { input_path, .. }
+            | Commands::FromMidi { input_path, .. }
+            | Commands::Fmt { input_path, .. }
+            | Commands::Transpose { input_path, .. }
+            | Commands::Retune { input_path, .. }
+            | Commands::Refinger { input_path, .. }
+            | Commands::Convert { input_path, .. } => input_path,
         }
     }
 
@@ -67,29 +381,200 @@ impl Commands {
         match self {
             Commands::Muxml { output_path, .. }
             //| Commands::Muxml { output_path, .. }
+            | Commands::Lilypond { output_path, .. }
+            | Commands::Abc { output_path, .. }
+            | Commands::AlphaTex { output_path, .. }
+            | Commands::VexTab { output_path, .. }
+            | Commands::Gp5 { output_path, .. }
+            | Commands::Html { output_path, .. }
+            | Commands::Svg { output_path, .. }
+            | Commands::Audio { output_path, .. }
+            | Commands::Json { output_path, .. }
             | Commands::Midi { output_path, .. } => output_path,
               | Commands::Fixup { output_path, .. } => output_path,
+            Commands::FromMuxml { output_path, .. }
+            | Commands::FromJson { output_path, .. }
+            | Commands::FromMidi { output_path, .. }
+            | Commands::Fmt { output_path, .. }
+            | Commands::Transpose { output_path, .. }
+            | Commands::Retune { output_path, .. }
+            | Commands::Refinger { output_path, .. }
+            | Commands::Convert { output_path, .. } => output_path,
         }
     }
 
-    pub fn to_backend_selector(&self) -> BackendSelector {
-        match self {
+    /// The backend to run, with its options. The backend of [Commands::Convert] is looked up in
+    /// `registry`, the others are built from their arguments.
+    pub fn to_backend<'a>(
+        &self, registry: &'a Registry,
+    ) -> anyhow::Result<(Box<dyn DynBackend + 'a>, Options)> {
+        let Commands::Convert { input_path, output_path, backend, options } = self else {
+            return Ok((Box::new(self.to_backend_selector().unwrap()), Options::new()));
+        };
+        let options = Options::parse(options).map_err(|e| anyhow!(e.kind.desc().1))?;
+        let found = match backend {
+            Some(name) => registry.get(name),
+            None => registry.for_paths(input_path, output_path),
+        };
+        let Some(found) = found else {
+            let reason = match backend {
+                Some(name) => format!("There is no backend named {name}"),
+                None => format!("No backend converts {input_path} into {output_path}"),
+            };
+            bail!("{reason}, pick one with --backend:\n{}", describe(registry));
+        };
+        Ok((Box::new(found), options))
+    }
+
+    /// None for [Commands::Convert], see [Commands::to_backend]
+    pub fn to_backend_selector(&self) -> Option<BackendSelector> {
+        Some(match self {
             Commands::Muxml {
                 trim_measure,
                 remove_rest_between_notes,
                 simplify_time_signature,
+                key,
+                clef,
+                octave,
+                multi_measure_rests,
+                meter,
+                voice_split,
                 ..
             } => BackendSelector::Muxml(muxml::settings::Settings {
                 remove_rest_between_notes: *remove_rest_between_notes,
                 trim_measure: *trim_measure,
                 simplify_time_signature: *simplify_time_signature,
+                key: *key,
+                clef: *clef,
+                octave: *octave,
+                multi_measure_rests: *multi_measure_rests,
+                meter: *meter,
+                voice_split: *voice_split,
             }),
+            Commands::Lilypond { key, simplify_time_signature, .. } => {
+                BackendSelector::Lilypond(LilypondSettings {
+                    key: *key,
+                    simplify_time_signature: *simplify_time_signature,
+                })
+            }
+            Commands::Abc { title, key, meter, simplify_time_signature, .. } => {
+                BackendSelector::Abc(AbcSettings {
+                    title: title.clone(),
+                    key: *key,
+                    meter: *meter,
+                    simplify_time_signature: *simplify_time_signature,
+                })
+            }
+            Commands::AlphaTex { title, .. } => {
+                BackendSelector::AlphaTex(AlphaTexSettings { title: title.clone() })
+            }
+            Commands::VexTab { tab_only, .. } => {
+                BackendSelector::VexTab(VexTabSettings { tab_only: *tab_only })
+            }
+            Commands::Gp5 { title, tempo, .. } => {
+                BackendSelector::Gp5(Gp5Settings { title: title.clone(), tempo: *tempo })
+            }
+            Commands::Html { title, tempo, .. } => {
+                BackendSelector::Html(HtmlSettings { title: title.clone(), tempo: *tempo })
+            }
+            Commands::Svg { width, notation, title, .. } => BackendSelector::Svg(SvgSettings {
+                width: *width,
+                notation: *notation,
+                title: title.clone(),
+            }),
+            Commands::Audio { tempo, sample_rate, .. } => {
+                BackendSelector::Audio(AudioSettings { tempo: *tempo, sample_rate: *sample_rate })
+            }
             Commands::Midi { .. } => BackendSelector::Midi,
+            Commands::Json { .. } => BackendSelector::Json,
             Commands::Fixup { dump, .. } => {
                 BackendSelector::Fixup(FixupBackendSettings { dump: dump.clone() })
             }
+            Commands::Fmt { check, measures_per_line, width, .. } => {
+                BackendSelector::Format(FormatSettings {
+                    measures_per_line: *measures_per_line,
+                    max_width: *width,
+                    check: *check,
+                })
+            }
+            Commands::Transpose { semitones, max_fret, .. } => {
+                BackendSelector::Transpose(TransposeSettings {
+                    semitones: *semitones,
+                    max_fret: *max_fret,
+                })
+            }
+            Commands::Retune { from, to, max_fret, .. } => {
+                BackendSelector::Retune(RetuneSettings {
+                    from: *from,
+                    to: *to,
+                    max_fret: *max_fret,
+                })
+            }
+            Commands::Refinger { max_span, capo, no_open_strings, max_fret, .. } => {
+                BackendSelector::Refinger(FingeringSettings {
+                    max_fret: *max_fret,
+                    max_span: *max_span,
+                    prefer_open_strings: !*no_open_strings,
+                    capo: *capo,
+                    ..Default::default()
+                })
+            }
+            Commands::FromMuxml { max_fret, measures_per_line, width, .. } => {
+                BackendSelector::MuxmlImport(ImportSettings {
+                    max_fret: *max_fret,
+                    writer: TabWriterSettings {
+                        measures_per_line: *measures_per_line,
+                        max_width: *width,
+                    },
+                    ..Default::default()
+                })
+            }
+            Commands::FromJson { measures_per_line, width, .. } => {
+                BackendSelector::JsonImport(JsonImportSettings {
+                    measures_per_line: *measures_per_line,
+                    max_width: *width,
+                })
+            }
+            Commands::FromMidi {
+                track,
+                channel,
+                columns_per_quarter,
+                max_fret,
+                measures_per_line,
+                width,
+                ..
+            } => BackendSelector::MidiImport(MidiImportSettings {
+                track: track.and_then(|x| x.checked_sub(1)),
+                channel: channel.map(|x| x - 1),
+                columns_per_quarter: *columns_per_quarter,
+                import: ImportSettings {
+                    max_fret: *max_fret,
+                    writer: TabWriterSettings {
+                        measures_per_line: *measures_per_line,
+                        max_width: *width,
+                    },
+                    ..Default::default()
+                },
+            }),
+            Commands::Convert { .. } => return None,
+        })
+    }
+}
+
+/// Every backend of `registry`, with its extensions and options
+fn describe(registry: &Registry) -> String {
+    let mut r = String::new();
+    for backend in registry.backends() {
+        let extensions = match backend.extensions() {
+            [] => String::new(),
+            x => format!(" (.{})", x.join(", .")),
+        };
+        r += &format!("  {}{extensions}: {}\n", backend.name(), backend.description());
+        for (key, description) in backend.options() {
+            r += &format!("      {key}: {description}\n");
         }
     }
+    r
 }
 
 impl Display for Commands {
@@ -99,8 +584,25 @@ impl Display for Commands {
             "{}",
             match self {
                 Commands::Muxml { .. } => "muxml2",
+                Commands::Lilypond { .. } => "lilypond",
+                Commands::Abc { .. } => "abc",
+                Commands::AlphaTex { .. } => "alphatex",
+                Commands::VexTab { .. } => "vextab",
+                Commands::Gp5 { .. } => "gp5",
+                Commands::Html { .. } => "html",
+                Commands::Svg { .. } => "svg",
+                Commands::Audio { .. } => "audio",
                 Commands::Fixup { .. } => "fixup",
                 Commands::Midi { .. } => "midi",
+                Commands::Json { .. } => "json",
+                Commands::FromMuxml { .. } => "from-muxml",
+                Commands::FromJson { .. } => "from-json",
+                Commands::FromMidi { .. } => "from-midi",
+                Commands::Fmt { .. } => "fmt",
+                Commands::Transpose { .. } => "transpose",
+                Commands::Retune { .. } => "retune",
+                Commands::Refinger { .. } => "refinger",
+                Commands::Convert { .. } => "convert",
             }
         )
     }
@@ -0,0 +1,67 @@
+use clap::{CommandFactory, Parser};
+use itertools::Itertools;
+use scoreman::backend::registry::{DynBackend, Options, Registry, TAB_EXTENSIONS};
+
+use crate::cli_args::Cli;
+
+const TAB: &str = "
+e|-------|
+B|-------|
+G|-----9-|
+D|-5h7/--|
+A|-------|
+E|-0-----|";
+
+/// The options without which a backend fails
+const REQUIRED: &[(&str, &[&str])] =
+    &[("transpose", &["semitones=-2"]), ("retune", &["to=drop-c"])];
+
+/// What `backend` reads: [TAB], or [TAB] written by the backend which writes its input
+fn input(registry: &Registry, backend: &dyn DynBackend) -> Vec<u8> {
+    let extension = backend.input_extensions()[0];
+    let mut writers = registry.backends().filter(|x| x.input_extensions() == TAB_EXTENSIONS);
+    let Some(writer) = writers.find(|x| x.extensions().contains(&extension)) else {
+        return TAB.as_bytes().to_vec();
+    };
+    let lines = TAB.lines().map(|x| x.to_string()).collect_vec();
+    let mut out = vec![];
+    assert!(writer.process(&lines, &mut out, &Options::new()).err.is_none());
+    out
+}
+
+/// Every built-in backend has a subcommand with the same flags and defaults
+#[test]
+fn test_registry_matches_cli() {
+    let registry = Registry::with_builtins();
+    let cli = Cli::command();
+    for backend in registry.backends() {
+        let name = backend.name();
+        let command = cli.find_subcommand(name).unwrap_or_else(|| panic!("no subcommand {name}"));
+        let args = command.get_arguments().filter(|x| !x.is_positional() && x.get_id() != "help");
+        let args = args.collect_vec();
+        let flags = args.iter().filter_map(|x| x.get_long()).sorted().collect_vec();
+        let options = backend.options().iter().map(|x| x.0).sorted().collect_vec();
+        assert_eq!(flags, options, "{name}");
+
+        for arg in args.iter().filter(|x| x.get_action().takes_values()) {
+            let Some(default) = arg.get_default_values().first() else { continue };
+            let key = arg.get_long().unwrap();
+            let description = backend.options().iter().find(|x| x.0 == key).unwrap().1;
+            let default = format!("{} by default", default.to_string_lossy());
+            assert!(description.contains(&default), "{name} {key}: {description}");
+        }
+
+        let required = REQUIRED.iter().find(|x| x.0 == name).map_or(&[][..], |x| x.1);
+        let cli_args = ["scoreman", name, "-", "-"].map(String::from);
+        let cli_args = cli_args.into_iter().chain(required.iter().map(|x| format!("--{x}")));
+        let selector = Cli::try_parse_from(cli_args).unwrap().command.to_backend_selector();
+        let input = input(&registry, backend);
+        let mut expected = vec![];
+        let res = selector.unwrap().process_bytes(&input, &mut expected);
+        assert!(res.err.is_none(), "{name}");
+        let mut out = vec![];
+        let res = backend.process_bytes(&input, &mut out, &Options::parse(required).unwrap());
+        assert!(res.err.is_none(), "{name}");
+        assert_eq!(out, expected, "{name}");
+    }
+}
@@ -0,0 +1,278 @@
+//! Places pitches on the fretboard, for formats which only know about pitches (like MusicXML or
+//! MIDI), or when the notes of a tab have to be moved to other strings.
+//!
+//! Pitches use the same numbering as [crate::backend::muxml::fretboard::MuxmlNote2::step], which
+//! is also the sounding MIDI key of the note: the open low E string is 40.
+//!
+//! Strings are numbered like in the tick stream: string 0 is the highest one.
+
+use std::{fmt::Display, str::FromStr};
+
+pub mod optimize;
+pub mod refinger;
+pub mod refret;
+
+/// The open pitch of every string, highest string first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tuning {
+    pub strings: [u8; 6],
+}
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+impl Tuning {
+    pub const STANDARD: Tuning = Tuning { strings: [64, 59, 55, 50, 45, 40] };
+
+    /// Resolves the string names of a part (like `eBGDAD`) into pitches.
+    ///
+    /// String names don't carry an octave, so every string gets the pitch closest to the string
+    /// in the same position of the standard tuning: the `D` of Drop D is a whole step below the
+    /// low E, and not a seventh above it.
+    ///
+    /// Returns [None] if a name is not a note name.
+    pub fn from_base_notes(names: &[char]) -> Option<Tuning> {
+        let mut strings = [0; 6];
+        for (idx, name) in names.iter().take(6).enumerate() {
+            let pitch_class = match name.to_ascii_uppercase() {
+                'C' => 0,
+                'D' => 2,
+                'E' => 4,
+                'F' => 5,
+                'G' => 7,
+                'A' => 9,
+                'B' => 11,
+                _ => return None,
+            };
+            let standard = Tuning::STANDARD.strings[idx] as i16;
+            // the closest pitch of this pitch class is at most 6 semitones away
+            let diff = (pitch_class - standard).rem_euclid(12);
+            let diff = if diff > 6 { diff - 12 } else { diff };
+            strings[idx] = (standard + diff) as u8;
+        }
+        Some(Tuning { strings })
+    }
+
+    /// The labels of the strings in a tab. The highest string is written as `e` if it is an E,
+    /// like in `eBGDAE`.
+    ///
+    /// Labels are a single letter without an octave, so this returns [None] for tunings with
+    /// sharps or flats, and for the ones which [Tuning::from_base_notes] would read back in
+    /// another octave.
+    pub fn names(&self) -> Option<[char; 6]> {
+        let mut names = self.strings.map(|x| NOTE_NAMES[x as usize % 12].chars().next().unwrap());
+        if names[0] == 'E' {
+            names[0] = 'e';
+        }
+        (Tuning::from_base_notes(&names) == Some(*self)).then_some(names)
+    }
+
+    /// The fret at which `pitch` can be played on `string`, if it can be played there at all
+    pub fn fret(&self, string: usize, pitch: u8, max_fret: u8) -> Option<u8> {
+        pitch.checked_sub(self.strings[string]).filter(|x| *x <= max_fret)
+    }
+}
+
+impl FromStr for Tuning {
+    type Err = String;
+
+    /// Accepts a preset (`standard`, `drop-d`, `half-step-down`, `d-standard`, `drop-c`,
+    /// `open-g`, `open-d`, `dadgad`), or six notes from the lowest string, with or without
+    /// octaves: `DADGBE`, `Eb Ab Db Gb Bb Eb` or `D2A2D3G3B3E4`. Notes without an octave get the
+    /// one closest to the standard tuning.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let preset = match s.to_ascii_lowercase().replace(['_', ' '], "-").as_str() {
+            "standard" | "e-standard" => Some("E2A2D3G3B3E4"),
+            "drop-d" => Some("D2A2D3G3B3E4"),
+            "half-step-down" | "eb-standard" => Some("Eb2Ab2Db3Gb3Bb3Eb4"),
+            "d-standard" | "whole-step-down" => Some("D2G2C3F3A3D4"),
+            "drop-c" => Some("C2G2C3F3A3D4"),
+            "open-g" => Some("D2G2D3G3B3D4"),
+            "open-d" => Some("D2A2D3F#3A3D4"),
+            "dadgad" => Some("D2A2D3G3A3D4"),
+            _ => None,
+        };
+        let err = || {
+            format!("Invalid tuning {s}, expected six notes from the lowest string (DADGBE) or a preset (drop-d)")
+        };
+        let mut chars = preset.unwrap_or(s).chars().filter(|x| !matches!(x, ' ' | ',')).peekable();
+        let mut notes = vec![];
+        while let Some(name) = chars.next() {
+            let pitch_class: i16 = match name.to_ascii_uppercase() {
+                'C' => 0,
+                'D' => 2,
+                'E' => 4,
+                'F' => 5,
+                'G' => 7,
+                'A' => 9,
+                'B' => 11,
+                _ => return Err(err()),
+            };
+            let alter = match chars.peek() {
+                Some('#') => 1,
+                Some('b') => -1,
+                _ => 0,
+            };
+            if alter != 0 {
+                chars.next();
+            }
+            let octave = chars.next_if(|x| x.is_ascii_digit()).map(|x| x as i16 - '0' as i16);
+            notes.push((pitch_class + alter, octave));
+        }
+        if notes.len() != 6 || notes.iter().any(|x| x.1.is_some() != notes[0].1.is_some()) {
+            return Err(err());
+        }
+        let mut strings = [0; 6];
+        // the lowest string is given first, but it is the last one in the tick stream
+        for (idx, (pitch_class, octave)) in notes.into_iter().rev().enumerate() {
+            let pitch = match octave {
+                Some(octave) => (octave + 1) * 12 + pitch_class,
+                None => {
+                    let standard = Tuning::STANDARD.strings[idx] as i16;
+                    let diff = (pitch_class - standard).rem_euclid(12);
+                    standard + if diff > 6 { diff - 12 } else { diff }
+                }
+            };
+            strings[idx] = u8::try_from(pitch).map_err(|_| err())?;
+        }
+        Ok(Tuning { strings })
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning::STANDARD
+    }
+}
+
+impl Display for Tuning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for pitch in self.strings.iter().rev() {
+            write!(f, "{}{}", NOTE_NAMES[*pitch as usize % 12], pitch / 12 - 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where a note is played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub string: u8,
+    pub fret: u8,
+}
+
+/// Places a chord (or a single note) on the fretboard.
+///
+/// Tries every combination of strings and picks the one with the smallest stretch, closest to
+/// `hand`, the fret the hand was at for the previous chord. Open strings don't need the hand, so
+/// they are preferred.
+///
+/// `fixed` holds the strings that are already taken in this tick. The result is in the order of
+/// `pitches`, with [None] for the notes that could not be placed.
+pub fn place_chord(
+    pitches: &[u8], fixed: [bool; 6], tuning: &Tuning, max_fret: u8, hand: Option<u8>,
+) -> Vec<Option<Position>> {
+    // notes which can't be played anywhere are left out, the rest are placed from the highest
+    let mut order: Vec<usize> = (0..pitches.len())
+        .filter(|x| (0..6).any(|s| !fixed[s] && tuning.fret(s, pitches[*x], max_fret).is_some()))
+        .collect();
+    order.sort_by_key(|x| std::cmp::Reverse(pitches[*x]));
+    order.truncate(fixed.iter().filter(|x| !**x).count());
+
+    let mut best: Option<(u32, Vec<Option<Position>>)> = None;
+    let mut current = vec![None; pitches.len()];
+    search(pitches, &order, fixed, tuning, max_fret, hand, &mut current, &mut best);
+    match best {
+        Some((_, positions)) => positions,
+        // there are more notes than strings, drop the lowest one
+        None if !order.is_empty() => {
+            let mut pitches = pitches.to_vec();
+            let lowest = *order.last().unwrap();
+            pitches[lowest] = u8::MAX;
+            place_chord(&pitches, fixed, tuning, max_fret, hand)
+        }
+        None => vec![None; pitches.len()],
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn search(
+    pitches: &[u8], order: &[usize], used: [bool; 6], tuning: &Tuning, max_fret: u8,
+    hand: Option<u8>, current: &mut Vec<Option<Position>>,
+    best: &mut Option<(u32, Vec<Option<Position>>)>,
+) {
+    let Some((note, rest)) = order.split_first() else {
+        let cost = chord_cost(current, hand);
+        if best.as_ref().map(|x| cost < x.0).unwrap_or(true) {
+            *best = Some((cost, current.clone()));
+        }
+        return;
+    };
+    for string in 0..6 {
+        if used[string] {
+            continue;
+        }
+        let Some(fret) = tuning.fret(string, pitches[*note], max_fret) else {
+            continue;
+        };
+        let mut used = used;
+        used[string] = true;
+        current[*note] = Some(Position { string: string as u8, fret });
+        search(pitches, rest, used, tuning, max_fret, hand, current, best);
+        current[*note] = None;
+    }
+}
+
+fn chord_cost(positions: &[Option<Position>], hand: Option<u8>) -> u32 {
+    let fretted = positions.iter().flatten().filter(|x| x.fret > 0).map(|x| x.fret as u32);
+    let (min, max) = fretted.clone().fold((u32::MAX, 0), |(lo, hi), x| (lo.min(x), hi.max(x)));
+    if max == 0 {
+        // only open strings
+        return 0;
+    }
+    let span = max - min;
+    let movement = hand.map(|hand| (hand as u32).abs_diff(min)).unwrap_or(0);
+    // a stretch is much harder than moving the hand, and lower positions are easier to read
+    span * span * 4 + movement * 2 + min + fretted.count() as u32
+}
+
+/// The fret the hand is at after playing these positions, if any of them needs it
+pub fn hand_position(positions: &[Option<Position>]) -> Option<u8> {
+    positions.iter().flatten().filter(|x| x.fret > 0).map(|x| x.fret).min()
+}
+
+#[test]
+fn test_place_chord() {
+    let tuning = Tuning::STANDARD;
+    // an open E major chord
+    let chord = [40, 47, 52, 56, 59, 64];
+    let positions = place_chord(&chord, [false; 6], &tuning, 24, None);
+    let frets = positions.iter().map(|x| x.unwrap().fret).collect::<Vec<_>>();
+    assert_eq!(frets, [0, 2, 2, 1, 0, 0]);
+    // a C with the hand at the third fret stays on the A string
+    let positions = place_chord(&[48], [false; 6], &tuning, 24, Some(3));
+    assert_eq!(positions, [Some(Position { string: 4, fret: 3 })]);
+    // but it moves up the neck if the hand is there
+    let positions = place_chord(&[48], [false; 6], &tuning, 24, Some(8));
+    assert_eq!(positions, [Some(Position { string: 5, fret: 8 })]);
+    // too low to play
+    assert_eq!(place_chord(&[30], [false; 6], &tuning, 24, None), [None]);
+    // more notes than free strings
+    let positions =
+        place_chord(&[64, 65], [true, true, true, true, true, false], &tuning, 24, None);
+    assert_eq!(positions.iter().flatten().count(), 1);
+
+    let drop_d = Tuning::from_base_notes(&['e', 'B', 'G', 'D', 'A', 'D']).unwrap();
+    assert_eq!(drop_d.strings, [64, 59, 55, 50, 45, 38]);
+    assert_eq!(drop_d.names(), Some(['e', 'B', 'G', 'D', 'A', 'D']));
+    assert_eq!(Tuning::STANDARD.names(), Some(['e', 'B', 'G', 'D', 'A', 'E']));
+    assert_eq!(Tuning::STANDARD.to_string(), "E2A2D3G3B3E4");
+    assert_eq!("drop-d".parse(), Ok(drop_d));
+    assert_eq!("DADGBE".parse(), Ok(drop_d));
+    assert_eq!("D2 A2 D3 G3 B3 E4".parse(), Ok(drop_d));
+    let half_step_down: Tuning = "Eb Ab Db Gb Bb Eb".parse().unwrap();
+    assert_eq!(half_step_down.strings, [63, 58, 54, 49, 44, 39]);
+    assert_eq!(half_step_down.names(), None);
+    // an E an octave above the low E would be read back as the low E
+    assert_eq!("E3A2D3G3B3E4".parse::<Tuning>().unwrap().names(), None);
+    assert!("DADGB".parse::<Tuning>().is_err());
+}
@@ -0,0 +1,271 @@
+//! Places a whole sequence of notes on the fretboard at once.
+//!
+//! [super::place_chord] only looks at one chord and the hand position before it. [optimize]
+//! finds the cheapest path through every tick instead (a Viterbi search), so a note can be
+//! placed a bit worse when that saves a jump across the neck later.
+
+use super::{chord_cost, hand_position, Position, Tuning};
+
+/// How many placements of every tick are kept for the search
+const BEAM_WIDTH: usize = 32;
+/// The cost of a placement which breaks a constraint. It is only chosen if nothing else fits.
+const IMPOSSIBLE: u32 = 10_000;
+
+#[derive(Clone, Debug)]
+pub struct FingeringSettings {
+    /// The highest fret a note may be placed on, counted from the nut
+    pub max_fret: u8,
+    /// The most frets the hand can stretch over in a chord
+    pub max_span: u8,
+    /// Open strings don't need the hand, so they are preferred. If not set, they are avoided
+    /// when the note can be fretted instead, which sounds more even.
+    pub prefer_open_strings: bool,
+    /// The fret the capo is on, 0 for no capo. Frets are counted from the capo, both in the
+    /// input and in the result.
+    pub capo: u8,
+    /// Avoid starting notes on the same string in consecutive ticks. A tab reads `24` as a
+    /// single fret, so this is needed when the result is written as a tab.
+    pub separate_adjacent: bool,
+}
+
+impl Default for FingeringSettings {
+    fn default() -> Self {
+        Self {
+            max_fret: 24,
+            max_span: 4,
+            prefer_open_strings: true,
+            capo: 0,
+            separate_adjacent: false,
+        }
+    }
+}
+
+/// A note to place, see [optimize]
+#[derive(Clone, Debug)]
+pub struct FingeringNote {
+    pub pitch: u8,
+    /// The position the note has to be played at, if it can't move
+    pub fixed: Option<Position>,
+    /// How many ticks the note holds its string for, at least 1
+    pub length: usize,
+}
+
+impl FingeringNote {
+    pub fn new(pitch: u8) -> Self {
+        Self { pitch, fixed: None, length: 1 }
+    }
+}
+
+/// The notes starting on a tick
+#[derive(Clone, Debug, Default)]
+pub struct FingeringTick {
+    pub notes: Vec<FingeringNote>,
+    /// Strings which are taken by something else in this tick, like a dead note
+    pub blocked: [bool; 6],
+}
+
+/// A placement of the notes of one tick, and the best way to get there
+struct State {
+    positions: Vec<Option<Position>>,
+    cost: u32,
+    /// The index of the state of the previous tick with notes
+    prev: Option<usize>,
+    hand: Option<u8>,
+    /// The first tick at which every string is free again
+    busy_until: [usize; 6],
+    /// The tick of the last note that started on every string
+    last_start: [Option<usize>; 6],
+}
+
+/// Places every note of `ticks`, minimizing the stretch of the chords and the movement of the
+/// hand between them. The result has the position of every note, in the order of
+/// [FingeringTick::notes], or [None] for the notes that can't be played in this tuning.
+pub fn optimize(
+    ticks: &[FingeringTick], tuning: &Tuning, settings: &FingeringSettings,
+) -> Vec<Vec<Option<Position>>> {
+    // with a capo, the strings start higher and frets are counted from the capo
+    let tuning = Tuning { strings: tuning.strings.map(|x| x.saturating_add(settings.capo)) };
+    let max_fret = settings.max_fret.saturating_sub(settings.capo);
+    // the states of every tick with notes
+    let mut layers: Vec<(usize, Vec<State>)> = vec![];
+    for (tick_idx, tick) in ticks.iter().enumerate() {
+        if tick.notes.is_empty() {
+            continue;
+        }
+        let candidates = candidates(tick, &tuning, max_fret, settings);
+        let prev_layer = layers.last().map(|x| &x.1);
+        let states = candidates
+            .into_iter()
+            .map(|(positions, node_cost)| {
+                let placed = |string: usize| {
+                    positions
+                        .iter()
+                        .zip(&tick.notes)
+                        .find(|x| x.0.map(|p| p.string as usize) == Some(string))
+                };
+                let transition = |prev: &State| {
+                    let mut cost = prev.cost;
+                    for string in 0..6 {
+                        if placed(string).is_none() {
+                            continue;
+                        }
+                        if prev.busy_until[string] > tick_idx {
+                            cost += IMPOSSIBLE;
+                        }
+                        if settings.separate_adjacent
+                            && prev.last_start[string] == tick_idx.checked_sub(1)
+                        {
+                            cost += IMPOSSIBLE;
+                        }
+                    }
+                    let hand = hand_position(&positions);
+                    if let Some((from, to)) = prev.hand.zip(hand) {
+                        cost += from.abs_diff(to) as u32 * 2;
+                    }
+                    cost
+                };
+                let best_prev = prev_layer.and_then(|layer| {
+                    layer.iter().enumerate().min_by_key(|(_, prev)| transition(prev))
+                });
+                let (cost, prev, hand, mut busy_until, mut last_start) = match best_prev {
+                    Some((idx, prev)) => (
+                        transition(prev) + node_cost,
+                        Some(idx),
+                        hand_position(&positions).or(prev.hand),
+                        prev.busy_until,
+                        prev.last_start,
+                    ),
+                    None => (node_cost, None, hand_position(&positions), [0; 6], [None; 6]),
+                };
+                for string in 0..6 {
+                    if let Some((_, note)) = placed(string) {
+                        busy_until[string] = tick_idx + note.length.max(1);
+                        last_start[string] = Some(tick_idx);
+                    }
+                }
+                State { positions, cost, prev, hand, busy_until, last_start }
+            })
+            .collect();
+        layers.push((tick_idx, states));
+    }
+
+    let mut result = ticks.iter().map(|x| vec![None; x.notes.len()]).collect::<Vec<_>>();
+    let mut state = layers
+        .last()
+        .and_then(|x| x.1.iter().enumerate().min_by_key(|(_, state)| state.cost))
+        .map(|x| x.0);
+    for (tick_idx, states) in layers.iter().rev() {
+        let Some(idx) = state else {
+            break;
+        };
+        result[*tick_idx] = states[idx].positions.clone();
+        state = states[idx].prev;
+    }
+    result
+}
+
+/// The cheapest placements of a tick on its own, with their costs
+fn candidates(
+    tick: &FingeringTick, tuning: &Tuning, max_fret: u8, settings: &FingeringSettings,
+) -> Vec<(Vec<Option<Position>>, u32)> {
+    let mut used = tick.blocked;
+    let mut current = vec![None; tick.notes.len()];
+    for (note, position) in tick.notes.iter().zip(current.iter_mut()) {
+        if let Some(fixed) = note.fixed.filter(|x| x.string < 6) {
+            used[fixed.string as usize] = true;
+            *position = Some(fixed);
+        }
+    }
+    let mut all = vec![];
+    collect(tick, 0, used, tuning, max_fret, &mut current, &mut all);
+    // if some placements can play more notes, the others are not considered
+    let most = all.iter().map(|x: &Vec<Option<Position>>| x.iter().flatten().count()).max();
+    all.retain(|x| Some(x.iter().flatten().count()) == most);
+
+    let mut scored = all
+        .into_iter()
+        .map(|positions| {
+            let fretted = positions.iter().flatten().filter(|x| x.fret > 0).map(|x| x.fret);
+            let (min, max) = fretted.fold((u8::MAX, 0), |(lo, hi), x| (lo.min(x), hi.max(x)));
+            let mut cost = chord_cost(&positions, None);
+            if max > 0 && max - min > settings.max_span {
+                cost += IMPOSSIBLE;
+            }
+            if !settings.prefer_open_strings {
+                cost += positions.iter().flatten().filter(|x| x.fret == 0).count() as u32 * 3;
+            }
+            (positions, cost)
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by_key(|x| x.1);
+    scored.truncate(BEAM_WIDTH);
+    scored
+}
+
+/// Every way to put the notes from `note_idx` on the free strings. A note is only left out if
+/// there is no free string for it.
+fn collect(
+    tick: &FingeringTick, note_idx: usize, used: [bool; 6], tuning: &Tuning, max_fret: u8,
+    current: &mut Vec<Option<Position>>, all: &mut Vec<Vec<Option<Position>>>,
+) {
+    let Some(note) = tick.notes.get(note_idx) else {
+        all.push(current.clone());
+        return;
+    };
+    if note.fixed.is_some() {
+        return collect(tick, note_idx + 1, used, tuning, max_fret, current, all);
+    }
+    let mut placed = false;
+    for string in (0..6).filter(|x| !used[*x]) {
+        let Some(fret) = tuning.fret(string, note.pitch, max_fret) else {
+            continue;
+        };
+        placed = true;
+        let mut used = used;
+        used[string] = true;
+        current[note_idx] = Some(Position { string: string as u8, fret });
+        collect(tick, note_idx + 1, used, tuning, max_fret, current, all);
+    }
+    current[note_idx] = None;
+    if !placed {
+        collect(tick, note_idx + 1, used, tuning, max_fret, current, all);
+    }
+}
+
+#[test]
+fn test_optimize() {
+    let tuning = Tuning::STANDARD;
+    let settings = FingeringSettings::default();
+    let melody = |pitches: &[u8]| {
+        let ticks = pitches
+            .iter()
+            .map(|x| FingeringTick { notes: vec![FingeringNote::new(*x)], ..Default::default() })
+            .collect::<Vec<_>>();
+        let placed = optimize(&ticks, &tuning, &settings);
+        placed.into_iter().map(|x| x[0].map(|x| (x.string, x.fret))).collect::<Vec<_>>()
+    };
+    // a G major scale from the third fret stays in one position
+    assert_eq!(
+        melody(&[43, 45, 47, 48, 50, 52, 54, 55]),
+        [(5, 3), (4, 0), (4, 2), (4, 3), (3, 0), (3, 2), (3, 4), (2, 0)].map(Some)
+    );
+    // high up the neck, the hand moves as little as possible
+    assert_eq!(melody(&[69, 71, 72, 74]), [(0, 5), (0, 7), (0, 8), (0, 10)].map(Some));
+    assert_eq!(melody(&[30]), [None]);
+
+    // a held note keeps its string
+    let ticks = [
+        FingeringTick {
+            notes: vec![FingeringNote { pitch: 45, fixed: None, length: 2 }],
+            ..Default::default()
+        },
+        FingeringTick { notes: vec![FingeringNote::new(45)], ..Default::default() },
+    ];
+    let placed = optimize(&ticks, &tuning, &settings);
+    assert_ne!(placed[0][0].unwrap().string, placed[1][0].unwrap().string);
+
+    // with a capo on the second fret, an open A string sounds like a B
+    let settings = FingeringSettings { capo: 2, ..Default::default() };
+    let ticks = [FingeringTick { notes: vec![FingeringNote::new(47)], ..Default::default() }];
+    assert_eq!(optimize(&ticks, &tuning, &settings), [[Some(Position { string: 4, fret: 0 })]]);
+}
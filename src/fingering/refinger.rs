@@ -0,0 +1,169 @@
+//! Moves the notes of a parsed tab to the strings that are easiest to play, in place.
+//!
+//! Only notes that are held plainly (like `5` or `5~~~`) are moved, with [optimize] choosing the
+//! strings for a whole track at once. Notes with techniques like `5h7` or `7b9r7` are kept
+//! where they are, because they only work on the string they are written on.
+
+use super::{
+    optimize::{optimize, FingeringNote, FingeringSettings, FingeringTick},
+    refret::{find_phrases, fret_of, part_tunings, phrase_pitches, warn_dropped, Columns, Phrase},
+    Position,
+};
+use crate::{
+    backend::errors::diagnostic::Diagnostic,
+    parser::{parser::ParseResult, tab_element::TabElement},
+    traceln,
+};
+
+/// Rewrites the strings and frets of the notes of `parsed` with [optimize]. Notes which can't be
+/// played under the constraints of `settings` are left out, with a diagnostic.
+///
+/// With a [FingeringSettings::capo], the frets of the input are counted from the nut, and the
+/// frets of the result from the capo. Notes below the capo are left out.
+pub fn refinger(
+    parsed: &mut ParseResult, settings: &FingeringSettings, diagnostics: &mut Vec<Diagnostic>,
+) {
+    // in a tab, `2` and `4` in consecutive ticks would be read as `24`
+    let settings = FingeringSettings { separate_adjacent: true, ..settings.clone() };
+    let tunings = part_tunings(parsed);
+    let original = parsed.tick_stream.clone();
+    let mut dropped = vec![];
+    for track in &parsed.tracks {
+        let columns = Columns::of_track(parsed, track);
+        let mut phrases = find_phrases(&parsed.tick_stream, &columns.starts);
+        for phrase in &mut phrases {
+            phrase.pitches = phrase_pitches(parsed, &columns, phrase, &tunings).collect();
+        }
+        let stream = &mut parsed.tick_stream;
+        let (movable, fixed): (Vec<Phrase>, Vec<Phrase>) = phrases
+            .into_iter()
+            .partition(|x| x.elements[1..].iter().all(|x| *x == TabElement::Vibrato));
+
+        // the frets of the notes that stay are counted from the capo
+        let mut kept = vec![];
+        for phrase in fixed {
+            let lowest = phrase.elements.iter().filter_map(fret_of).min().unwrap_or_default();
+            if lowest < settings.capo {
+                for c in phrase.columns.clone() {
+                    stream[columns.starts[c] + phrase.string] = TabElement::Rest;
+                }
+                dropped.push(columns.starts[phrase.columns.start] + phrase.string);
+                continue;
+            }
+            for c in phrase.columns.clone() {
+                if let TabElement::Fret(fret) = &mut stream[columns.starts[c] + phrase.string] {
+                    *fret -= settings.capo;
+                }
+            }
+            kept.push(phrase);
+        }
+        // cleared first, so that the notes can take each other's strings
+        for phrase in &movable {
+            for c in phrase.columns.clone() {
+                stream[columns.starts[c] + phrase.string] = TabElement::Rest;
+            }
+        }
+
+        // the optimizer needs a single tuning, so the track is split where the tuning changes
+        let mut segment_start = 0;
+        while segment_start < columns.starts.len() {
+            let tuning = tunings[columns.parts[segment_start]];
+            let segment_end = (segment_start..columns.starts.len())
+                .find(|c| tunings[columns.parts[*c]] != tuning)
+                .unwrap_or(columns.starts.len());
+            let segment = segment_start..segment_end;
+            traceln!("refinger: placing columns {segment:?} in {tuning}");
+            segment_start = segment_end;
+
+            let mut ticks = segment
+                .clone()
+                .map(|c| {
+                    let start = columns.starts[c];
+                    let blocked = std::array::from_fn(|s| stream[start + s] != TabElement::Rest);
+                    FingeringTick { notes: vec![], blocked }
+                })
+                .collect::<Vec<_>>();
+            let in_segment = |x: &&Phrase| segment.contains(&x.columns.start);
+            // (is movable, phrase) in the order of the notes of every tick
+            let mut notes: Vec<Vec<(bool, &Phrase)>> = vec![vec![]; ticks.len()];
+            for (is_movable, phrase) in movable
+                .iter()
+                .filter(in_segment)
+                .map(|x| (true, x))
+                .chain(kept.iter().filter(in_segment).map(|x| (false, x)))
+            {
+                let Some(pitch) = phrase.pitches[0].and_then(|x| u8::try_from(x).ok()) else {
+                    continue;
+                };
+                let tick = phrase.columns.start - segment.start;
+                let start = columns.starts[phrase.columns.start] + phrase.string;
+                let fixed = match is_movable {
+                    true => None,
+                    false => fret_of(&stream[start])
+                        .map(|fret| Position { string: phrase.string as u8, fret }),
+                };
+                // the last fret of a phrase can't be followed by another one either
+                let trailing = !is_movable && fret_of(phrase.elements.last().unwrap()).is_some();
+                let length = phrase.columns.len() + trailing as usize;
+                ticks[tick].notes.push(FingeringNote { pitch, fixed, length });
+                ticks[tick].blocked[phrase.string] = false;
+                notes[tick].push((is_movable, phrase));
+            }
+
+            let placed = optimize(&ticks, &tuning, &settings);
+            for (tick_notes, positions) in notes.iter().zip(placed) {
+                for ((is_movable, phrase), position) in tick_notes.iter().zip(positions) {
+                    if !is_movable {
+                        continue;
+                    }
+                    let Some(Position { string, fret }) = position else {
+                        dropped.push(columns.starts[phrase.columns.start] + phrase.string);
+                        continue;
+                    };
+                    for (idx, c) in phrase.columns.clone().enumerate() {
+                        stream[columns.starts[c] + string as usize] = match idx {
+                            0 => TabElement::Fret(fret),
+                            _ => TabElement::Vibrato,
+                        };
+                    }
+                }
+            }
+        }
+    }
+    warn_dropped(parsed, original, &dropped, diagnostics);
+}
+
+#[test]
+fn test_refinger() {
+    use crate::parser::parser::parse;
+    let input = "
+e|----------------|
+B|----------------|
+G|----------------|
+D|----------------|
+A|-3-5-7-8-10-12~-|
+E|----------------|";
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut parsed = parse(&lines);
+    let mut diagnostics = vec![];
+    refinger(&mut parsed, &FingeringSettings::default(), &mut diagnostics);
+    // a C major scale run up a single string is played across the strings, in one position
+    assert_eq!(
+        parsed.dump_tracks(),
+        "--------------
+--------------
+---------0-2~-
+---0-2-3------
+-3------------
+--------------
+"
+    );
+    assert!(diagnostics.is_empty());
+
+    // with a capo on the third fret, the frets are counted from the capo
+    let mut parsed = parse(&lines);
+    let settings = FingeringSettings { capo: 3, ..Default::default() };
+    refinger(&mut parsed, &settings, &mut diagnostics);
+    assert!(diagnostics.is_empty());
+    assert!(parsed.tick_stream.iter().all(|x| !matches!(x, TabElement::Fret(f) if *f > 9)));
+}
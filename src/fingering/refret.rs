@@ -0,0 +1,328 @@
+//! Changes the pitch or the tuning of a parsed tab, in place.
+//!
+//! Notes are moved together with the techniques attached to them: a phrase like `5h7p5` or
+//! `7b9r7~~` is one unit, which either keeps its string or moves to another one as a whole. A
+//! phrase only moves if one of its frets would fall off the fretboard, so chord shapes and the
+//! rhythm of the tab are kept.
+
+use super::{chord_cost, Position, Tuning};
+use crate::{
+    backend::errors::{
+        diagnostic::Diagnostic, diagnostic_kind::DiagnosticKind, error_location::ErrorLocation,
+    },
+    parser::{
+        parser::{source_location_from_stream, ParseResult, Track},
+        tab_element::TabElement,
+    },
+    rlen, traceln,
+};
+
+#[derive(Clone, Debug)]
+pub struct RefretSettings {
+    /// Semitones to add to every note
+    pub shift: i16,
+    /// The tuning the tab is written in. Read from the string names of every Part if not set.
+    pub source: Option<Tuning>,
+    /// The tuning to write the result in. Every Part keeps its own tuning if not set. It has to
+    /// have string names, see [Tuning::names].
+    pub tuning: Option<Tuning>,
+    /// The highest fret a note may be placed on
+    pub max_fret: u8,
+}
+
+impl Default for RefretSettings {
+    fn default() -> Self {
+        Self { shift: 0, source: None, tuning: None, max_fret: 24 }
+    }
+}
+
+/// A note on one string, with everything attached to it
+pub(super) struct Phrase {
+    pub string: usize,
+    /// Indices into the columns of the track
+    pub columns: std::ops::Range<usize>,
+    pub elements: Vec<TabElement>,
+    /// The pitch of every fret in [Phrase::elements], after the shift
+    pub pitches: Vec<Option<i16>>,
+}
+
+/// The ticks of a track, in order
+pub(super) struct Columns {
+    /// The index of the first element of every tick in the tick stream
+    pub starts: Vec<usize>,
+    /// Whether the tick is the first of its measure
+    pub measure_starts: Vec<bool>,
+    /// The Part every tick is in
+    pub parts: Vec<usize>,
+}
+
+impl Columns {
+    pub fn of_track(parsed: &ParseResult, track: &Track) -> Self {
+        let mut columns = Columns { starts: vec![], measure_starts: vec![], parts: vec![] };
+        for measure_idx in &track.measures {
+            let range = &parsed.measures[*measure_idx as usize].data_range;
+            let tick_cnt = rlen(range) as usize / 6;
+            let start = *range.start() as usize;
+            let part = parsed.offsets.partition_point(|x| x.1 as usize <= start).saturating_sub(1);
+            columns.starts.extend((0..tick_cnt).map(|x| start + x * 6));
+            columns.measure_starts.extend((0..tick_cnt).map(|x| x == 0));
+            columns.parts.extend((0..tick_cnt).map(|_| part));
+        }
+        columns
+    }
+}
+
+/// The tuning of every Part, read from its string names
+pub fn part_tunings(parsed: &ParseResult) -> Vec<Tuning> {
+    (0..parsed.offsets.len())
+        .map(|part| Tuning::from_base_notes(&parsed.base_notes[part * 6..part * 6 + 6]))
+        .map(|x| x.unwrap_or_default())
+        .collect()
+}
+
+/// Shifts every note of `parsed` by [RefretSettings::shift] semitones and writes it in
+/// [RefretSettings::tuning]. Notes which can't be played on any string are left out, with a
+/// diagnostic.
+///
+/// String names which are not note names are read as standard tuning.
+pub fn refret(
+    parsed: &mut ParseResult, settings: &RefretSettings, diagnostics: &mut Vec<Diagnostic>,
+) {
+    let part_cnt = parsed.offsets.len();
+    let mut source = part_tunings(parsed);
+    if let Some(tuning) = settings.source {
+        source = vec![tuning; part_cnt];
+    }
+    let target: Vec<Tuning> = source.iter().map(|x| settings.tuning.unwrap_or(*x)).collect();
+
+    let mut tracks = vec![];
+    for track in &parsed.tracks {
+        let columns = Columns::of_track(parsed, track);
+        let mut phrases = find_phrases(&parsed.tick_stream, &columns.starts);
+        for phrase in &mut phrases {
+            phrase.pitches = phrase_pitches(parsed, &columns, phrase, &source)
+                .map(|x| x.map(|pitch| pitch + settings.shift))
+                .collect();
+        }
+        tracks.push((columns, phrases));
+    }
+    if let Some(names) = settings.tuning.and_then(|x| x.names()) {
+        parsed.base_notes = names.repeat(part_cnt);
+    }
+    let original = parsed.tick_stream.clone();
+
+    // the first element of every phrase that could not be placed
+    let mut dropped = vec![];
+    for (columns, phrases) in tracks {
+        let stream = &mut parsed.tick_stream;
+        let mut moved = vec![];
+        for phrase in phrases {
+            match place(&phrase, &columns, &target, phrase.string, settings.max_fret) {
+                Some(elements) => write(stream, &columns, &phrase, phrase.string, elements),
+                None => {
+                    // cleared first, so that the moved phrases can take each other's strings
+                    for c in phrase.columns.clone() {
+                        stream[columns.starts[c] + phrase.string] = TabElement::Rest;
+                    }
+                    moved.push(phrase);
+                }
+            }
+        }
+        for phrase in moved {
+            let mut best: Option<(usize, usize, Vec<TabElement>)> = None;
+            for string in (0..6).filter(|x| *x != phrase.string) {
+                if !is_free(stream, &columns, &phrase, string) {
+                    continue;
+                }
+                let Some(elements) = place(&phrase, &columns, &target, string, settings.max_fret)
+                else {
+                    continue;
+                };
+                // the phrase has to fit the hand with the notes it is played together with
+                let start = columns.starts[phrase.columns.start];
+                let chord = (0..6).map(|x| match (x == string, &stream[start + x]) {
+                    (true, _) => {
+                        fret_of(&elements[0]).map(|fret| Position { string: x as u8, fret })
+                    }
+                    (false, TabElement::Fret(fret)) => {
+                        Some(Position { string: x as u8, fret: *fret })
+                    }
+                    _ => None,
+                });
+                let cost = chord_cost(&chord.collect::<Vec<_>>(), None) as usize * 6
+                    + string.abs_diff(phrase.string);
+                if best.as_ref().map(|x| cost < x.0).unwrap_or(true) {
+                    best = Some((cost, string, elements));
+                }
+            }
+            match best {
+                Some((_, string, elements)) => {
+                    traceln!("refret: moving a phrase from string {} to {string}", phrase.string);
+                    write(stream, &columns, &phrase, string, elements)
+                }
+                None => dropped.push(columns.starts[phrase.columns.start] + phrase.string),
+            }
+        }
+    }
+    warn_dropped(parsed, original, &dropped, diagnostics);
+}
+
+/// The pitch of every element of the phrase which is a fret
+pub(super) fn phrase_pitches<'a>(
+    parsed: &'a ParseResult, columns: &'a Columns, phrase: &'a Phrase, tunings: &'a [Tuning],
+) -> impl Iterator<Item = Option<i16>> + 'a {
+    phrase.columns.clone().map(|c| match parsed.tick_stream[columns.starts[c] + phrase.string] {
+        TabElement::Fret(fret) => {
+            Some(tunings[columns.parts[c]].strings[phrase.string] as i16 + fret as i16)
+        }
+        _ => None,
+    })
+}
+
+/// Warns about the notes at the given indices of the tick stream, which were left out. The
+/// locations are found in the `original` tick stream, as the widths of the ticks can change.
+pub(super) fn warn_dropped(
+    parsed: &mut ParseResult, original: Vec<TabElement>, dropped: &[usize],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let modified = std::mem::replace(&mut parsed.tick_stream, original);
+    for idx in dropped {
+        let (line, char) = source_location_from_stream(parsed, *idx as u32);
+        diagnostics.push(Diagnostic::warn(
+            ErrorLocation::LineAndChar(line, char),
+            DiagnosticKind::NoStringForNote,
+        ));
+    }
+    parsed.tick_stream = modified;
+}
+
+pub(super) fn fret_of(elem: &TabElement) -> Option<u8> {
+    match elem {
+        TabElement::Fret(x) => Some(*x),
+        _ => None,
+    }
+}
+
+/// Splits every string of the track into phrases. A phrase starts with a fret, and goes on until
+/// a rest or a fret that is not connected to it by a technique.
+pub(super) fn find_phrases(stream: &[TabElement], columns: &[usize]) -> Vec<Phrase> {
+    let mut phrases = vec![];
+    for string in 0..6 {
+        let mut current: Option<Phrase> = None;
+        for (c, column) in columns.iter().enumerate() {
+            let elem = &stream[column + string];
+            let continues = current.as_ref().is_some_and(|x| {
+                let last = x.elements.last().unwrap();
+                match elem {
+                    TabElement::Rest | TabElement::DeadNote => false,
+                    // like the 7 of `5h7`, but not of `5~7`
+                    TabElement::Fret(_) => matches!(
+                        last,
+                        TabElement::HammerOn
+                            | TabElement::Pull
+                            | TabElement::Slide
+                            | TabElement::Bend
+                            | TabElement::Release
+                    ),
+                    _ => true,
+                }
+            });
+            if continues {
+                let phrase = current.as_mut().unwrap();
+                phrase.columns.end = c + 1;
+                phrase.elements.push(elem.clone());
+                continue;
+            }
+            phrases.extend(current.take());
+            if let TabElement::Fret(_) = elem {
+                current = Some(Phrase {
+                    string,
+                    columns: c..c + 1,
+                    elements: vec![elem.clone()],
+                    pitches: vec![],
+                });
+            }
+        }
+        phrases.extend(current);
+    }
+    phrases.sort_by_key(|x| (x.columns.start, x.string));
+    phrases
+}
+
+/// The elements of the phrase if it is played on `string`, or [None] if it doesn't fit there
+fn place(
+    phrase: &Phrase, columns: &Columns, target: &[Tuning], string: usize, max_fret: u8,
+) -> Option<Vec<TabElement>> {
+    let elements = phrase.columns.clone().zip(&phrase.elements).zip(&phrase.pitches);
+    elements
+        .map(|((c, elem), pitch)| match pitch {
+            Some(pitch) => {
+                let fret =
+                    u8::try_from(pitch - target[columns.parts[c]].strings[string] as i16).ok()?;
+                (fret <= max_fret).then_some(TabElement::Fret(fret))
+            }
+            None => Some(elem.clone()),
+        })
+        .collect()
+}
+
+/// Whether the phrase can be written on `string`: its ticks have to be free, and a fret right
+/// before or after it in the same measure would be read together with it, like `24`
+fn is_free(stream: &[TabElement], columns: &Columns, phrase: &Phrase, string: usize) -> bool {
+    let at = |c: usize| &stream[columns.starts[c] + string];
+    let before = phrase.columns.start;
+    let after = phrase.columns.end;
+    phrase.columns.clone().all(|c| *at(c) == TabElement::Rest)
+        && (before == 0
+            || columns.measure_starts[before]
+            || !matches!(at(before - 1), TabElement::Fret(_)))
+        && (after == columns.starts.len()
+            || columns.measure_starts[after]
+            || !matches!(at(after), TabElement::Fret(_))
+            || !matches!(phrase.elements.last(), Some(TabElement::Fret(_))))
+}
+
+fn write(
+    stream: &mut [TabElement], columns: &Columns, phrase: &Phrase, string: usize,
+    elements: Vec<TabElement>,
+) {
+    for (c, elem) in phrase.columns.clone().zip(elements) {
+        stream[columns.starts[c] + string] = elem;
+    }
+}
+
+#[test]
+fn test_refret() {
+    use crate::parser::parser::parse;
+    let input = "
+e|--0-|----|
+B|--0-|----|
+G|3~~~|~~--|
+D|----|----|
+A|----|----|
+E|0---|----|";
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut parsed = parse(&lines);
+    let mut diagnostics = vec![];
+    refret(&mut parsed, &RefretSettings { shift: -2, ..Default::default() }, &mut diagnostics);
+    // the held note stays on its string, the open strings move down, and the low E is left out
+    assert_eq!(
+        parsed.dump_tracks(),
+        "--------
+--3-----
+1~~~~~--
+--7-----
+--------
+--------
+"
+    );
+    assert_eq!(diagnostics.len(), 1);
+
+    // in Drop D, the open low E is on the second fret
+    let mut parsed = parse(&lines);
+    let drop_d = Tuning::from_base_notes(&['e', 'B', 'G', 'D', 'A', 'D']).unwrap();
+    let settings = RefretSettings { tuning: Some(drop_d), ..Default::default() };
+    refret(&mut parsed, &settings, &mut vec![]);
+    assert_eq!(&parsed.base_notes, &['e', 'B', 'G', 'D', 'A', 'D']);
+    assert_eq!(parsed.tick_stream[5], TabElement::Fret(2));
+}
@@ -0,0 +1,198 @@
+//! Reads the JSON written by [crate::backend::json] back into a [ParseResult].
+//!
+//! Only the fields which can't be derived from the others are read, see the schema in
+//! [crate::backend::json]. Files written by a newer version of the schema are rejected.
+
+use std::time::Instant;
+
+use super::json::{self, Value};
+use crate::{
+    backend::{
+        errors::backend_error::BackendError,
+        json::{FORMAT, TECHNIQUES, VERSION},
+        registry::{Registry, SelectorBackend},
+        Backend, BackendResult, BackendSelector,
+    },
+    parser::{
+        parser::{Measure, ParseResult, Track},
+        tab_element::TabElement,
+    },
+    tab_writer::{write_parts, write_tab, TabWriterSettings},
+    time,
+};
+
+pub struct JsonImportBackend();
+
+#[derive(Clone, Debug, Default)]
+pub struct JsonImportSettings {
+    /// Re-wrap the measures into Parts of this many measures. Every Part of the JSON is written
+    /// on a line of its own if neither this nor [JsonImportSettings::max_width] is set.
+    pub measures_per_line: Option<usize>,
+    /// Re-wrap the measures into Parts of at most this many characters per line
+    pub max_width: Option<usize>,
+}
+
+impl Backend for JsonImportBackend {
+    type BackendSettings = JsonImportSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let (parse_time, parsed) = time(|| read(input));
+        let parsed = match parsed {
+            Ok(x) => x,
+            Err(e) => return BackendResult::new(vec![], Some(e), Some(parse_time), None),
+        };
+        let gen_start = Instant::now();
+        let mut tab = String::new();
+        let mut r = BackendResult::new(vec![], None, Some(parse_time), None);
+        let written = match (settings.measures_per_line, settings.max_width) {
+            (None, None) => write_parts(&parsed, &mut tab),
+            (measures_per_line, max_width) => {
+                let measures_per_line = measures_per_line.unwrap_or(usize::MAX);
+                write_tab(&parsed, &mut tab, &TabWriterSettings { measures_per_line, max_width })
+            }
+        };
+        if let Err(e) = written {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(tab.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("measures-per-line", "Re-wrap the measures into Parts of this many measures"),
+        ("width", "Re-wrap the measures into Parts of at most this many characters per line"),
+    ];
+    registry.register(
+        SelectorBackend::new(
+            "from-json",
+            "Reads the JSON written by the json backend and writes a tab",
+            &["tab"],
+            options,
+            |o| {
+                Ok(BackendSelector::JsonImport(JsonImportSettings {
+                    measures_per_line: o.get("measures-per-line")?,
+                    max_width: o.get("width")?,
+                }))
+            },
+        )
+        .reads(&["json"], false),
+    );
+}
+
+/// Reads a JSON document written by [crate::backend::json]
+pub fn read(lines: &[String]) -> Result<ParseResult, BackendError> {
+    let root =
+        json::parse_document(lines).map_err(|e| BackendError::invalid_json(e.line, e.message))?;
+    read_parse_result(&root)
+}
+
+fn err(value: &Value, message: impl Into<String>) -> BackendError {
+    BackendError::invalid_json(value.line, message.into())
+}
+
+/// The member `key` of the object `value`
+fn member<'a>(value: &'a Value, key: &str) -> Result<&'a Value, BackendError> {
+    value.get(key).ok_or_else(|| err(value, format!("Expected an object with a member {key}")))
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], BackendError> {
+    let array = member(value, key)?;
+    array.as_array().ok_or_else(|| err(array, format!("{key} should be an array")))
+}
+
+/// The member `key` as an integer below `max`
+fn integer(value: &Value, key: &str, max: u64) -> Result<u64, BackendError> {
+    let number = member(value, key)?;
+    match number.as_u64() {
+        Some(x) if x < max => Ok(x),
+        _ => Err(err(number, format!("{key} should be an integer from 0 to {}", max - 1))),
+    }
+}
+
+/// Builds the [ParseResult] of a JSON document
+pub fn read_parse_result(root: &Value) -> Result<ParseResult, BackendError> {
+    if member(root, "format")?.as_str() != Some(FORMAT) {
+        return Err(err(root, format!("Expected a document with the format {FORMAT}")));
+    }
+    let version = integer(root, "version", u64::MAX)?;
+    if version == 0 || version > VERSION as u64 {
+        let message = format!("Version {version} is not supported, the latest is {VERSION}");
+        return Err(err(member(root, "version")?, message));
+    }
+
+    let mut r = ParseResult::new();
+    for track in array(root, "tracks")? {
+        let name = member(track, "name")?;
+        let Some(name) = name.as_str() else {
+            return Err(err(name, "The name of a track should be a string"));
+        };
+        let program = match member(track, "program")?.is_null() {
+            true => None,
+            false => Some(integer(track, "program", 128)? as u8),
+        };
+        r.tracks.push(Track::new(name.to_string(), program));
+    }
+
+    for part in array(root, "parts")? {
+        let track = integer(part, "track", r.tracks.len() as u64)? as usize;
+        let line = integer(part, "line", u32::MAX as u64)? as u32;
+        r.offsets.push((line, r.tick_stream.len() as u32));
+        let strings = array(part, "strings")?;
+        if strings.len() != 6 {
+            return Err(err(part, "A part should have 6 strings"));
+        }
+        for string in strings {
+            let mut chars = string.as_str().unwrap_or_default().chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_alphabetic() => r.base_notes.push(c),
+                _ => return Err(err(string, "The name of a string should be a single letter")),
+            }
+        }
+        for measure in array(part, "measures")? {
+            let measure_start = r.tick_stream.len() as u32;
+            let ticks = array(measure, "ticks")?;
+            // a tab can't have a measure without ticks
+            if ticks.is_empty() {
+                return Err(err(measure, "A measure should have at least one tick"));
+            }
+            for tick in ticks {
+                let tick_start = r.tick_stream.len();
+                r.tick_stream.extend([const { TabElement::Rest }; 6]);
+                let notes = array(tick, "notes")?.iter().map(|note| {
+                    let elem = match member(note, "dead").ok().and_then(|x| x.as_bool()) {
+                        Some(true) => TabElement::DeadNote,
+                        _ => TabElement::Fret(integer(note, "fret", 100)? as u8),
+                    };
+                    Ok((note, elem))
+                });
+                let techniques = array(tick, "techniques")?.iter().map(|technique| {
+                    let kind = member(technique, "kind")?;
+                    let elem = TECHNIQUES.iter().find(|x| kind.as_str() == Some(x.1));
+                    match elem {
+                        Some((elem, _)) => Ok((technique, elem.clone())),
+                        None => Err(err(kind, "Unknown technique")),
+                    }
+                });
+                for element in notes.chain(techniques) {
+                    let (value, elem) = element?;
+                    let string = integer(value, "string", 6)? as usize;
+                    if r.tick_stream[tick_start + string] != TabElement::Rest {
+                        return Err(err(value, "Two elements on the same string of a tick"));
+                    }
+                    r.tick_stream[tick_start + string] = elem;
+                }
+            }
+            r.tracks[track].measures.push(r.measures.len() as u32);
+            r.measures.push(Measure::from(measure_start..=r.tick_stream.len() as u32 - 1));
+        }
+    }
+    Ok(r)
+}
@@ -0,0 +1,189 @@
+use crate::backend::errors::backend_error_kind::BackendErrorKind;
+use crate::backend::{format::FormatBackend, json::JsonBackend, Backend};
+use crate::import::ir::{read, JsonImportBackend, JsonImportSettings};
+use crate::parser::parser::{parse, ParseResult};
+use itertools::Itertools;
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(|x| x.to_string()).collect_vec()
+}
+
+fn to_json(tab: &[String]) -> String {
+    let mut out = vec![];
+    let res = JsonBackend::process(tab, &mut out, ());
+    assert!(res.err.is_none(), "{:?}", res.err);
+    String::from_utf8(out).unwrap()
+}
+
+/// Everything the parser produces, except for the error
+fn summary(parsed: &ParseResult) -> String {
+    let measures = parsed.measures.iter().map(|x| &x.data_range).collect_vec();
+    let tracks = parsed.tracks.iter().map(|x| (&x.name, x.program, &x.measures)).collect_vec();
+    format!(
+        "{:?}\n{measures:?}\n{:?}\n{:?}\n{tracks:?}",
+        parsed.tick_stream, parsed.base_notes, parsed.offsets
+    )
+}
+
+const RIFF: &str = "Track: Lead program=30
+  e|--------|
+  B|--------|
+  G|-----12-|
+  D|-5h7/9--|
+  A|-------x|
+  E|-0------|";
+
+#[test]
+fn test_json_backend() {
+    insta::assert_snapshot!(to_json(&lines(RIFF)));
+}
+
+#[test]
+fn test_json_round_trip() {
+    for path in [
+        "input/c_major.tab",
+        "input/ties.tab",
+        "input/multi_track.tab",
+        "input/vibrato.tab",
+        "input/bends.tab",
+    ] {
+        let tab = lines(&std::fs::read_to_string(path).unwrap());
+        let parsed = parse(&tab);
+        assert!(parsed.error.is_none(), "{path}");
+        let loaded = read(&lines(&to_json(&tab))).unwrap();
+        assert_eq!(summary(&loaded), summary(&parsed), "{path}");
+    }
+}
+
+#[test]
+fn test_json_spans() {
+    let tab = lines(RIFF);
+    let json = to_json(&tab);
+    let root = crate::import::json::parse_document(&lines(&json)).unwrap();
+    let part = &root.get("parts").unwrap().as_array().unwrap()[0];
+    let ticks = part.get("measures").unwrap().as_array().unwrap()[0].get("ticks").unwrap();
+    // every note and technique points at its own text in the tab
+    let mut checked = 0;
+    for tick in ticks.as_array().unwrap() {
+        for kind in ["notes", "techniques"] {
+            for elem in tick.get(kind).unwrap().as_array().unwrap() {
+                let span = elem.get("span").unwrap();
+                let [line, start, end] = ["line", "start", "end"]
+                    .map(|x| span.get(x).unwrap().as_u64().unwrap() as usize);
+                let text = tab[line][start..end].trim_matches('-');
+                let expected = match elem.get("fret") {
+                    Some(fret) => fret.as_u64().unwrap().to_string(),
+                    None if kind == "notes" => "x".to_string(),
+                    None => match elem.get("kind").unwrap().as_str().unwrap() {
+                        "hammer-on" => "h",
+                        "slide" => "/",
+                        other => panic!("{other}"),
+                    }
+                    .to_string(),
+                };
+                assert_eq!(text, expected, "{line}:{start}");
+                checked += 1;
+            }
+        }
+    }
+    assert_eq!(checked, 8);
+}
+
+#[test]
+fn test_json_import() {
+    let mut out = vec![];
+    let res =
+        JsonImportBackend::process(&lines(&to_json(&lines(RIFF))), &mut out, Default::default());
+    assert!(res.err.is_none(), "{:?}", res.err);
+    let tab = String::from_utf8(out).unwrap();
+    assert_eq!(tab, RIFF.replace("  ", "") + "\n");
+}
+
+#[test]
+fn test_json_import_keeps_parts() {
+    let text = |res: crate::backend::BackendResult, out: Vec<u8>| {
+        assert!(res.err.is_none(), "{:?}", res.err);
+        String::from_utf8(out).unwrap()
+    };
+    let fmt = |tab: &[String]| {
+        let mut out = vec![];
+        text(FormatBackend::process(tab, &mut out, Default::default()), out)
+    };
+    let from_json = |tab: &[String]| {
+        let mut out = vec![];
+        let json = lines(&to_json(tab));
+        text(JsonImportBackend::process(&json, &mut out, Default::default()), out)
+    };
+    // Parts of 1, 5 and 2 measures, and a track that comes back after another one
+    let tab = lines(
+        "Track: Lead
+e|-0-|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+
+e|-1-|-2-|-3-|-4-|-5-|
+B|---|---|---|---|---|
+G|---|---|---|---|---|
+D|---|---|---|---|---|
+A|---|---|---|---|---|
+E|---|---|---|---|---|
+
+Track: Bass program=33
+e|---|
+B|---|
+G|---|
+D|---|
+A|---|
+E|-0-|
+
+Track: Lead
+e|-6-|-7-|
+B|---|---|
+G|---|---|
+D|---|---|
+A|---|---|
+E|---|---|",
+    );
+    assert_eq!(from_json(&tab), fmt(&tab));
+    for path in ["input/c_major.tab", "input/multi_track.tab", "input/bends.tab"] {
+        let tab = lines(&std::fs::read_to_string(path).unwrap());
+        assert_eq!(from_json(&tab), fmt(&tab), "{path}");
+    }
+
+    // the measures are only re-wrapped when asked to
+    let mut out = vec![];
+    let settings = JsonImportSettings { measures_per_line: Some(4), max_width: None };
+    JsonImportBackend::process(&lines(&to_json(&tab)), &mut out, settings);
+    let parsed = parse(&lines(&String::from_utf8(out).unwrap()));
+    assert_eq!(parsed.offsets.len(), 3);
+}
+
+#[test]
+fn test_json_import_errors() {
+    let err = |json: &str| {
+        let mut out = vec![];
+        JsonImportBackend::process(&lines(json), &mut out, Default::default()).err.unwrap()
+    };
+    let e = err("{\n\"format\": \"scoreman\",\n\"version\": 1,\n\"tracks\": [\n");
+    assert!(matches!(e.kind, BackendErrorKind::InvalidJson(_)));
+
+    let e = err("{\"format\": \"scoreman\",\n\"version\": 2, \"tracks\": [], \"parts\": []}");
+    assert!(matches!(e.kind, BackendErrorKind::InvalidJson(ref x) if x.contains("Version 2")));
+    assert_eq!(e.main_location.get_line_idx(), Some(1));
+
+    let json = to_json(&lines(RIFF));
+    assert!(read(&lines(&json.replace("\"string\": 3", "\"string\": 7"))).is_err());
+    assert!(read(&lines(&json.replace("hammer-on", "tapping"))).is_err());
+    // two elements on one string
+    assert!(read(&lines(&json.replace("\"string\": 2", "\"string\": 3"))).is_err());
+    assert!(read(&lines(&json.replace("\"scoreman\"", "\"other\""))).is_err());
+    // a measure without ticks
+    let empty = json.replace("{\"ticks\": [", "{\"ticks\": []}, {\"ticks\": [");
+    let e = read(&lines(&empty)).unwrap_err();
+    assert!(
+        matches!(e.kind, BackendErrorKind::InvalidJson(ref x) if x.contains("at least one tick"))
+    );
+}
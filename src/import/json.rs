@@ -0,0 +1,258 @@
+//! A small JSON reader. Numbers are kept as `f64`, which is exact for every integer in a tab.
+
+/// A JSON value, with the line index where it starts
+#[derive(Debug, PartialEq)]
+pub struct Value {
+    pub line: usize,
+    pub data: Data,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Data {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// The members in the order they were written
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The member `key` of an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match &self.data {
+            Data::Object(members) => members.iter().find(|x| x.0 == key).map(|x| &x.1),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match &self.data {
+            Data::Array(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.data {
+            Data::String(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.data {
+            Data::Bool(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The value as a non-negative integer
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.data {
+            Data::Number(x) if x >= 0.0 && x.fract() == 0.0 && x <= u64::MAX as f64 => {
+                Some(x as u64)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.data == Data::Null
+    }
+}
+
+/// A syntax error, with the line index where it happened
+#[derive(Debug)]
+pub struct JsonError {
+    pub line: usize,
+    pub message: String,
+}
+
+struct Reader<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl Reader<'_> {
+    fn err(&self, message: impl Into<String>) -> JsonError {
+        JsonError { line: self.line, message: message.into() }
+    }
+
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.line += self.src[self.pos..self.pos + len].matches('\n').count();
+        self.pos += len;
+    }
+
+    fn skip_whitespace(&mut self) {
+        let len = self.rest().len() - self.rest().trim_start().len();
+        self.advance(len);
+    }
+
+    /// Skips whitespace and the expected character
+    fn expect(&mut self, c: char) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        match self.rest().starts_with(c) {
+            true => {
+                self.advance(1);
+                Ok(())
+            }
+            false => Err(self.err(format!("Expected {c}"))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, JsonError> {
+        self.skip_whitespace();
+        let line = self.line;
+        let rest = self.rest();
+        let data = if let Some(literal) =
+            [("null", Data::Null), ("true", Data::Bool(true)), ("false", Data::Bool(false))]
+                .into_iter()
+                .find(|x| rest.starts_with(x.0))
+        {
+            self.advance(literal.0.len());
+            literal.1
+        } else if rest.starts_with('"') {
+            Data::String(self.string()?)
+        } else if rest.starts_with('[') {
+            self.advance(1);
+            let mut items = vec![];
+            self.skip_whitespace();
+            if self.rest().starts_with(']') {
+                self.advance(1);
+            } else {
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    if self.rest().starts_with(']') {
+                        self.advance(1);
+                        break;
+                    }
+                    self.expect(',')?;
+                }
+            }
+            Data::Array(items)
+        } else if rest.starts_with('{') {
+            self.advance(1);
+            let mut members = vec![];
+            self.skip_whitespace();
+            if self.rest().starts_with('}') {
+                self.advance(1);
+            } else {
+                loop {
+                    self.skip_whitespace();
+                    if !self.rest().starts_with('"') {
+                        return Err(self.err("Expected the name of a member"));
+                    }
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value()?));
+                    self.skip_whitespace();
+                    if self.rest().starts_with('}') {
+                        self.advance(1);
+                        break;
+                    }
+                    self.expect(',')?;
+                }
+            }
+            Data::Object(members)
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+                .unwrap_or(rest.len());
+            let Some(number) = rest[..len].parse::<f64>().ok().filter(|_| len > 0) else {
+                return Err(self.err("Expected a value"));
+            };
+            self.advance(len);
+            Data::Number(number)
+        };
+        Ok(Value { line, data })
+    }
+
+    /// Reads a string, starting at its opening quote
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.advance(1);
+        let mut out = String::new();
+        loop {
+            let Some(len) = self.rest().find(['"', '\\', '\n']) else {
+                return Err(self.err("Unterminated string"));
+            };
+            out.push_str(&self.rest()[..len]);
+            self.advance(len);
+            let rest = self.rest();
+            if rest.starts_with('"') {
+                self.advance(1);
+                return Ok(out);
+            } else if rest.starts_with('\n') {
+                return Err(self.err("Unterminated string"));
+            }
+            let escaped = match rest[1..].chars().next() {
+                Some('"') => '"',
+                Some('\\') => '\\',
+                Some('/') => '/',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('u') => {
+                    let code = rest.get(2..6).and_then(|x| u32::from_str_radix(x, 16).ok());
+                    // surrogate pairs are not needed for tabs, and read as invalid
+                    let Some(c) = code.and_then(char::from_u32) else {
+                        return Err(self.err("Invalid \\u escape"));
+                    };
+                    out.push(c);
+                    self.advance(6);
+                    continue;
+                }
+                _ => return Err(self.err("Invalid escape")),
+            };
+            out.push(escaped);
+            self.advance(2);
+        }
+    }
+}
+
+/// Reads the single value of a document
+pub fn parse_document(lines: &[String]) -> Result<Value, JsonError> {
+    let src = lines.join("\n");
+    let mut reader = Reader { src: &src, pos: 0, line: 0 };
+    let value = reader.value()?;
+    reader.skip_whitespace();
+    if !reader.rest().is_empty() {
+        return Err(reader.err("Expected the end of the document"));
+    }
+    Ok(value)
+}
+
+#[test]
+fn test_parse_document() {
+    let src = r#"{
+  "a": [1, -2.5, true, null],
+  "b": "x\"A\n",
+  "c": {}
+}"#;
+    let lines = src.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let root = parse_document(&lines).unwrap();
+    let a = root.get("a").unwrap();
+    assert_eq!(a.line, 1);
+    let a = a.as_array().unwrap();
+    assert_eq!(a[0].as_u64(), Some(1));
+    assert_eq!(a[1].as_u64(), None);
+    assert_eq!(a[1].data, Data::Number(-2.5));
+    assert_eq!(a[2].as_bool(), Some(true));
+    assert!(a[3].is_null());
+    assert_eq!(root.get("b").unwrap().as_str(), Some("x\"A\n"));
+    assert_eq!(root.get("c").unwrap().data, Data::Object(vec![]));
+
+    let lines = ["[1,", "2", "3]"].map(|x| x.to_string());
+    assert_eq!(parse_document(&lines).unwrap_err().line, 2);
+    let lines = ["{} {}"].map(|x| x.to_string());
+    assert!(parse_document(&lines).is_err());
+}
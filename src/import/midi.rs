@@ -0,0 +1,226 @@
+//! Reads Standard MIDI Files. One channel is turned into a tab: its note-ons are quantized to the
+//! columns of the tab, and measures follow the time signature events of the file.
+
+use std::time::Instant;
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use super::{build_parse_result, ImportSettings, ImportedMeasure, ImportedNote, ImportedTrack};
+use crate::{
+    backend::{
+        errors::{backend_error::BackendError, error_location::ErrorLocation},
+        registry::{Registry, SelectorBackend},
+        BackendResult, BackendSelector,
+    },
+    tab_writer::{write_tab, TabWriterSettings},
+    time, traceln,
+};
+
+/// Channel 10 is reserved for percussion
+const PERCUSSION_CHANNEL: u8 = 9;
+/// Event times are not limited, so a broken file could make the tab arbitrarily long. This is
+/// over 4 hours of eighths at 120 bpm.
+const MAX_COLUMNS: u64 = 1 << 16;
+
+#[derive(Clone, Debug)]
+pub struct MidiImportSettings {
+    /// Only read this track, counting from 0. Every track is read if not set.
+    pub track: Option<usize>,
+    /// Only read this channel, counting from 0. If not set, the channel of the first note is
+    /// read, skipping percussion.
+    pub channel: Option<u8>,
+    /// How many columns of the tab a quarter note takes. With 2 every column is an eighth note,
+    /// which is what the other backends expect.
+    pub columns_per_quarter: u32,
+    pub import: ImportSettings,
+}
+
+impl Default for MidiImportSettings {
+    fn default() -> Self {
+        Self { track: None, channel: None, columns_per_quarter: 2, import: Default::default() }
+    }
+}
+
+/// A note-on event, with its time from the start of the file
+struct NoteOn {
+    tick: u64,
+    track: usize,
+    channel: u8,
+    key: u8,
+}
+
+/// MIDI files are binary, so this is not a [crate::backend::Backend], which reads lines
+pub struct MidiImportBackend();
+impl MidiImportBackend {
+    pub fn process_bytes<Out: std::io::Write>(
+        input: &[u8], out: &mut Out, settings: MidiImportSettings,
+    ) -> BackendResult {
+        let mut diagnostics = vec![];
+        let (parse_time, smf) = time(|| Smf::parse(input));
+        let smf = match smf {
+            Ok(x) => x,
+            Err(e) => {
+                let err = BackendError::invalid_midi(e.to_string());
+                return BackendResult::new(diagnostics, Some(err), Some(parse_time), None);
+            }
+        };
+        let gen_start = Instant::now();
+        let track = match read_smf(&smf, &settings) {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                let err = BackendError::empty_score_err();
+                return BackendResult::new(diagnostics, Some(err), Some(parse_time), None);
+            }
+            Err(err) => return BackendResult::new(diagnostics, Some(err), Some(parse_time), None),
+        };
+        let parsed = build_parse_result(&[track], &settings.import, &mut diagnostics);
+        let mut tab = String::new();
+        let mut r = BackendResult::new(diagnostics, None, Some(parse_time), None);
+        if let Err(e) = write_tab(&parsed, &mut tab, &settings.import.writer) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(tab.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("track", "Only read this track, counting from 1"),
+        ("channel", "Only read this channel, counting from 1"),
+        ("columns-per-quarter", "How many columns of the tab a quarter note takes, 2 by default"),
+        ("max-fret", "The highest fret a note may be placed on, 24 by default"),
+        ("measures-per-line", "How many measures to write on a line, 4 by default"),
+        ("width", "The maximum number of characters on a line"),
+    ];
+    registry.register(
+        SelectorBackend::new(
+            "from-midi",
+            "Reads a MIDI file and writes a tab",
+            &["tab"],
+            options,
+            |o| {
+                let channel = o.get::<u8>("channel")?;
+                if channel.is_some_and(|x| !(1..=16).contains(&x)) {
+                    let message = "channel should be from 1 to 16".to_string();
+                    return Err(BackendError::invalid_option(message));
+                }
+                let default = MidiImportSettings::default();
+                Ok(BackendSelector::MidiImport(MidiImportSettings {
+                    track: o.get::<usize>("track")?.and_then(|x| x.checked_sub(1)),
+                    channel: channel.map(|x| x - 1),
+                    columns_per_quarter: o
+                        .get_or("columns-per-quarter", default.columns_per_quarter)?,
+                    import: ImportSettings {
+                        max_fret: o.get_or("max-fret", 24)?,
+                        writer: TabWriterSettings::from_options(o)?,
+                        ..Default::default()
+                    },
+                }))
+            },
+        )
+        .reads(&["mid", "midi", "smf"], true),
+    );
+}
+
+/// Reads the selected channel into a track, or returns [None] if it has no notes
+pub fn read_smf(
+    smf: &Smf, settings: &MidiImportSettings,
+) -> Result<Option<ImportedTrack>, BackendError> {
+    let columns_per_quarter = settings.columns_per_quarter.max(1);
+    let ticks_per_column = match smf.header.timing {
+        Timing::Metrical(ticks_per_quarter) => {
+            ticks_per_quarter.as_int() as f64 / columns_per_quarter as f64
+        }
+        // there is no tempo for timecode, so we assume 120 bpm, where a quarter is half a second
+        Timing::Timecode(fps, subframes) => {
+            fps.as_f32() as f64 * subframes as f64 / 2.0 / columns_per_quarter as f64
+        }
+    };
+    let mut notes = vec![];
+    let mut meters: Vec<(u64, u8, u8)> = vec![];
+    let mut names: Vec<Option<String>> = vec![None; smf.tracks.len()];
+    let mut programs: [Option<u8>; 16] = [None; 16];
+    for (track_idx, track) in smf.tracks.iter().enumerate() {
+        let mut tick = 0u64;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            match event.kind {
+                TrackEventKind::Midi { channel, message } => {
+                    let channel = channel.as_int();
+                    match message {
+                        MidiMessage::NoteOn { key, vel }
+                            if vel > 0
+                                && settings.track.map(|x| x == track_idx).unwrap_or(true) =>
+                        {
+                            notes.push(NoteOn {
+                                tick,
+                                track: track_idx,
+                                channel,
+                                key: key.as_int(),
+                            });
+                        }
+                        MidiMessage::ProgramChange { program } => {
+                            programs[channel as usize].get_or_insert(program.as_int());
+                        }
+                        _ => {}
+                    }
+                }
+                TrackEventKind::Meta(MetaMessage::TimeSignature(numerator, denominator, ..)) => {
+                    meters.push((tick, numerator, denominator))
+                }
+                TrackEventKind::Meta(MetaMessage::TrackName(name)) => {
+                    names[track_idx] = Some(String::from_utf8_lossy(name).trim().to_string())
+                }
+                _ => {}
+            }
+        }
+    }
+    notes.sort_by_key(|x| x.tick);
+    meters.sort_by_key(|x| x.0);
+    let channel = settings
+        .channel
+        .or_else(|| notes.iter().map(|x| x.channel).find(|x| *x != PERCUSSION_CHANNEL));
+    let Some(channel) = channel else { return Ok(None) };
+    notes.retain(|x| x.channel == channel);
+    traceln!("midi import: reading {} notes on channel {channel}", notes.len());
+    let (Some(first), Some(last)) = (notes.first(), notes.last()) else { return Ok(None) };
+    let name = names[first.track].clone().filter(|x| !x.is_empty());
+    let name = name.unwrap_or_else(|| format!("Channel {}", channel + 1));
+
+    let column = |tick: u64| (tick as f64 / ticks_per_column).round() as u32;
+    let last_column = column(last.tick);
+    // the first column of every measure
+    let mut measure_starts = vec![];
+    let mut measures = vec![];
+    let mut start = 0;
+    while start <= last_column {
+        let meter = meters.iter().rev().find(|x| column(x.0) <= start);
+        let (numerator, denominator) = meter.map(|x| (x.1, x.2)).unwrap_or((4, 2));
+        // a whole note is 4 quarters, the denominator is a power of 2
+        let whole = numerator as u64 * columns_per_quarter as u64 * 4;
+        let len = whole.checked_shr(denominator as u32).unwrap_or(0).max(1);
+        if start as u64 + len > MAX_COLUMNS {
+            let message = format!("the tab would be longer than {MAX_COLUMNS} columns");
+            return Err(BackendError::invalid_midi(message));
+        }
+        let len = len as u32;
+        measure_starts.push(start);
+        measures.push(ImportedMeasure { ticks: vec![vec![]; len as usize] });
+        start += len;
+    }
+    for note in notes {
+        let note_column = column(note.tick);
+        let measure_idx = measure_starts.partition_point(|x| *x <= note_column) - 1;
+        let tick =
+            &mut measures[measure_idx].ticks[(note_column - measure_starts[measure_idx]) as usize];
+        if tick.iter().all(|x| x.pitch != note.key) {
+            tick.push(ImportedNote::new(note.key, ErrorLocation::NoLocation));
+        }
+    }
+    Ok(Some(ImportedTrack { name, program: programs[channel as usize], measures }))
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeSet;
+
+use midly::{
+    num::{u28, u4, u7},
+    Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind,
+};
+
+use crate::backend::errors::backend_error_kind::BackendErrorKind;
+use crate::backend::{midi::MidiBackend, Backend, BackendSelector};
+use crate::import::midi::{MidiImportBackend, MidiImportSettings};
+
+fn note(delta: u32, channel: u8, key: u8, on: bool) -> TrackEvent<'static> {
+    let (key, vel) = (u7::new(key), u7::new(if on { 100 } else { 0 }));
+    TrackEvent {
+        delta: u28::new(delta),
+        kind: TrackEventKind::Midi {
+            channel: u4::new(channel),
+            message: MidiMessage::NoteOn { key, vel },
+        },
+    }
+}
+
+fn meta(delta: u32, message: MetaMessage<'static>) -> TrackEvent<'static> {
+    TrackEvent { delta: u28::new(delta), kind: TrackEventKind::Meta(message) }
+}
+
+fn to_bytes(smf: &Smf) -> Vec<u8> {
+    let mut bytes = vec![];
+    smf.write_std(&mut bytes).unwrap();
+    bytes
+}
+
+fn import(bytes: &[u8], settings: MidiImportSettings) -> String {
+    let mut out = vec![];
+    let res = MidiImportBackend::process_bytes(bytes, &mut out, settings);
+    assert!(res.err.is_none(), "{:?}", res.err);
+    String::from_utf8(out).unwrap()
+}
+
+/// The keys that start in every tick of the file
+fn note_ons(bytes: &[u8]) -> BTreeSet<(u64, u8)> {
+    let smf = Smf::parse(bytes).unwrap();
+    let mut notes = BTreeSet::new();
+    for track in &smf.tracks {
+        let mut tick = 0u64;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            if let TrackEventKind::Midi { message: MidiMessage::NoteOn { key, vel }, .. } =
+                event.kind
+            {
+                if vel > 0 {
+                    notes.insert((tick, key.as_int()));
+                }
+            }
+        }
+    }
+    notes
+}
+
+#[test]
+fn test_midi_import() {
+    // a measure of 3/4 with two eighths, an E minor chord on the third beat, and a drum hit
+    // on the percussion channel, which is skipped
+    let smf = Smf {
+        header: Header::new(Format::Parallel, Timing::Metrical(480.into())),
+        tracks: vec![
+            vec![
+                meta(0, MetaMessage::TimeSignature(3, 2, 24, 8)),
+                meta(0, MetaMessage::EndOfTrack),
+            ],
+            vec![
+                meta(0, MetaMessage::TrackName(b"Lead")),
+                note(0, 9, 36, true),
+                note(0, 0, 45, true),
+                note(240, 0, 45, false),
+                note(0, 0, 47, true),
+                // slightly late, but still on the third beat
+                note(730, 0, 47, false),
+                note(0, 0, 40, true),
+                note(0, 0, 47, true),
+                note(0, 0, 52, true),
+                note(480, 0, 40, false),
+                meta(0, MetaMessage::EndOfTrack),
+            ],
+        ],
+    };
+    let imported = import(&to_bytes(&smf), MidiImportSettings::default());
+    // the B can't stay next to the A on the same string, which would read as a single fret
+    assert_eq!(
+        imported,
+        r#"Track: Lead
+e|------|
+B|------|
+G|------|
+D|----2-|
+A|-2--2-|
+E|5---0-|
+"#
+    );
+
+    let settings = MidiImportSettings { channel: Some(9), ..Default::default() };
+    let drums = import(&to_bytes(&smf), settings);
+    assert!(drums.starts_with("Track: Lead\n"), "{drums}");
+}
+
+#[test]
+fn test_midi_round_trip() {
+    let tab = std::fs::read_to_string("input/c_major.tab").unwrap();
+    let tab = tab.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut midi = vec![];
+    assert!(MidiBackend::process(&tab, &mut midi, ()).err.is_none());
+    // the midi backend writes a column as a tick, with 4 ticks per quarter
+    let settings = MidiImportSettings { columns_per_quarter: 4, ..Default::default() };
+    let imported = import(&midi, settings);
+    let imported = imported.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let mut reexported = vec![];
+    assert!(MidiBackend::process(&imported, &mut reexported, ()).err.is_none());
+    // the midi backend tunes the strings an octave higher than the importer, which reads keys as
+    // sounding pitches
+    let shifted = note_ons(&reexported).into_iter().map(|(tick, key)| (tick, key - 12));
+    assert_eq!(shifted.collect::<BTreeSet<_>>(), note_ons(&midi));
+}
+
+#[test]
+fn test_midi_import_errors() {
+    let mut out = vec![];
+    let res = MidiImportBackend::process_bytes(b"MThd", &mut out, Default::default());
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidMidi(_)));
+
+    let smf = Smf {
+        header: Header::new(Format::SingleTrack, Timing::Metrical(480.into())),
+        tracks: vec![vec![meta(0, MetaMessage::EndOfTrack)]],
+    };
+    let res = MidiImportBackend::process_bytes(&to_bytes(&smf), &mut out, Default::default());
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::EmptyScore));
+
+    // a note a day after the start would need a huge tab
+    let smf = Smf {
+        header: Header::new(Format::SingleTrack, Timing::Metrical(480.into())),
+        tracks: vec![vec![note(0, 0, 60, true), note(0x0fff_ffff, 0, 62, true)]],
+    };
+    let res = MidiImportBackend::process_bytes(&to_bytes(&smf), &mut out, Default::default());
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidMidi(_)));
+
+    // lines can't hold a binary file
+    let res = BackendSelector::MidiImport(Default::default()).process(&[], &mut out);
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidOption(_)));
+}
@@ -0,0 +1,330 @@
+//! Converts other formats into tabs.
+//!
+//! Importers read their input into [ImportedTrack]s, which only know about pitches and rhythm.
+//! [build_parse_result] then places the notes on the fretboard with
+//! [crate::fingering::optimize], and produces the same [ParseResult] the parser would, so the tab
+//! can be written with [crate::tab_writer] or given to any backend.
+//!
+//! Like in the tab format, a tick is an eighth note.
+
+use std::collections::HashMap;
+
+use crate::{
+    backend::errors::{
+        diagnostic::Diagnostic, diagnostic_kind::DiagnosticKind, error_location::ErrorLocation,
+    },
+    fingering::{
+        hand_position,
+        optimize::{optimize, FingeringNote, FingeringSettings, FingeringTick},
+        place_chord, Position, Tuning,
+    },
+    parser::{
+        parser::{Measure, ParseResult, Track},
+        tab_element::TabElement,
+    },
+    tab_writer::TabWriterSettings,
+};
+
+pub mod ir;
+#[cfg(test)]
+mod ir_tests;
+mod json;
+pub mod midi;
+#[cfg(test)]
+mod midi_tests;
+pub mod musicxml;
+#[cfg(test)]
+mod musicxml_tests;
+mod xml;
+
+#[derive(Clone, Debug)]
+pub struct ImportSettings {
+    /// The tuning of the written tab. Notes that already have a position in the input keep it.
+    /// The strings are labeled as standard tuning if it has no string names, see [Tuning::names].
+    pub tuning: Tuning,
+    /// The highest fret a note may be placed on
+    pub max_fret: u8,
+    pub writer: TabWriterSettings,
+}
+
+impl Default for ImportSettings {
+    fn default() -> Self {
+        Self { tuning: Tuning::STANDARD, max_fret: 24, writer: TabWriterSettings::default() }
+    }
+}
+
+/// What connects a note to the next note on the same string
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Link {
+    /// A hammer-on or a pull-off, depending on the direction
+    Slur,
+    Slide,
+    Vibrato,
+}
+
+/// A note read from another format, before it is placed on the fretboard
+#[derive(Clone, Debug)]
+pub struct ImportedNote {
+    /// See [crate::fingering] for the numbering
+    pub pitch: u8,
+    /// The position given in the input, if any
+    pub position: Option<Position>,
+    pub dead: bool,
+    /// Continues the last note with the same pitch instead of starting a new one
+    pub tie_stop: bool,
+    /// The number of ticks after the first one that a tied note or a note with vibrato is held
+    /// with `~`. Other notes are just not played again.
+    pub hold: u32,
+    pub link: Option<Link>,
+    /// Where the note is in the input, for diagnostics
+    pub location: ErrorLocation,
+}
+
+impl ImportedNote {
+    pub fn new(pitch: u8, location: ErrorLocation) -> Self {
+        Self { pitch, position: None, dead: false, tie_stop: false, hold: 0, link: None, location }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportedMeasure {
+    /// The notes starting on every tick of the measure
+    pub ticks: Vec<Vec<ImportedNote>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedTrack {
+    pub name: String,
+    pub program: Option<u8>,
+    pub measures: Vec<ImportedMeasure>,
+}
+
+/// Places the notes of every track on the fretboard. Every track becomes one part of the result.
+///
+/// The positions come from [optimize] over the whole track. Ties, fixed positions and notes
+/// which have to stay on the string of a slur go first, and a note whose planned string is
+/// taken by them is placed on its own with [place_chord].
+pub fn build_parse_result(
+    tracks: &[ImportedTrack], settings: &ImportSettings, diagnostics: &mut Vec<Diagnostic>,
+) -> ParseResult {
+    let mut r = ParseResult::new();
+    let tuning = &settings.tuning;
+    for track in tracks {
+        let track_start = r.tick_stream.len();
+        r.offsets.push((0, track_start as u32));
+        r.base_notes.extend(tuning.names().unwrap_or(['e', 'B', 'G', 'D', 'A', 'E']));
+        let mut parsed_track = Track::new(track.name.clone(), track.program);
+        let mut hand = None;
+        // the pitch last played on every string, for ties
+        let mut sounding: [Option<u8>; 6] = [None; 6];
+        // (tick start, string, link) of every note with a link
+        let mut links: Vec<(usize, usize, Link)> = vec![];
+        // a slurred or slid note is followed by a note on the same string
+        let mut linked = [false; 6];
+        // (stream index, hold) of every held note
+        let mut holds: Vec<(usize, u32)> = vec![];
+        let planned = plan_fingering(track, settings);
+        let mut planned_ticks = planned.iter();
+        for measure in &track.measures {
+            let measure_start = r.tick_stream.len() as u32;
+            for (tick_idx, notes) in measure.ticks.iter().enumerate() {
+                let planned = planned_ticks.next().unwrap();
+                let tick_start = r.tick_stream.len();
+                r.tick_stream.extend([const { TabElement::Rest }; 6]);
+                let mut used = [false; 6];
+                // two frets right after each other on a string would be read as one, like `24`
+                let blocked: [bool; 6] = std::array::from_fn(|string| {
+                    tick_idx > 0
+                        && matches!(r.tick_stream[tick_start - 6 + string], TabElement::Fret(_))
+                });
+                let mut positions: Vec<Option<Position>> = vec![None; notes.len()];
+                let mut tied = vec![false; notes.len()];
+                // ties and notes with a known position first, so that the others go around them
+                for (note_idx, note) in notes.iter().enumerate() {
+                    let tie_string = (0..6).find(|x| !used[*x] && sounding[*x] == Some(note.pitch));
+                    if let Some(string) = tie_string.filter(|_| note.tie_stop) {
+                        used[string] = true;
+                        tied[note_idx] = true;
+                        // inside a measure a held note is just not played again, but over a
+                        // barline it has to be held with `~`, like `5~~~|~~`
+                        if tick_idx == 0 {
+                            if let Some(link) = note.link {
+                                links.push((tick_start, string, link));
+                            }
+                            holds.push((tick_start + string, note.hold));
+                            let mut idx = tick_start + string;
+                            r.tick_stream[idx] = TabElement::Vibrato;
+                            while idx >= track_start + 6
+                                && r.tick_stream[idx - 6] == TabElement::Rest
+                            {
+                                idx -= 6;
+                                r.tick_stream[idx] = TabElement::Vibrato;
+                            }
+                        }
+                    } else if let Some(known) = note.position.filter(|x| !used[x.string as usize]) {
+                        used[known.string as usize] = true;
+                        positions[note_idx] = Some(known);
+                    }
+                }
+                for (note, position) in notes.iter().zip(positions.iter_mut()) {
+                    if position.is_some() || note.position.is_some() {
+                        continue;
+                    }
+                    let continued = (0..6).find_map(|string| {
+                        let fret = tuning.fret(string, note.pitch, settings.max_fret)?;
+                        let free = !used[string] && !blocked[string];
+                        (linked[string] && free).then_some((string, fret))
+                    });
+                    if let Some((string, fret)) = continued {
+                        used[string] = true;
+                        *position = Some(Position { string: string as u8, fret });
+                    }
+                }
+                let to_place = (0..notes.len())
+                    .filter(|x| positions[*x].is_none() && !tied[*x])
+                    .collect::<Vec<_>>();
+                let mut avoid: [bool; 6] = std::array::from_fn(|x| used[x] || blocked[x]);
+                let mut placed = to_place
+                    .iter()
+                    .map(|x| planned[*x].filter(|p| !avoid[p.string as usize]))
+                    .collect::<Vec<_>>();
+                let mut taken = used;
+                for position in placed.iter().flatten() {
+                    avoid[position.string as usize] = true;
+                    taken[position.string as usize] = true;
+                }
+                let rest = (0..to_place.len()).filter(|x| placed[*x].is_none()).collect::<Vec<_>>();
+                let pitches = rest.iter().map(|x| notes[to_place[*x]].pitch).collect::<Vec<_>>();
+                let mut greedy = place_chord(&pitches, avoid, tuning, settings.max_fret, hand);
+                if greedy.contains(&None) {
+                    // an ambiguous tab is still better than a missing note
+                    let retry = place_chord(&pitches, taken, tuning, settings.max_fret, hand);
+                    if retry.iter().flatten().count() > greedy.iter().flatten().count() {
+                        greedy = retry;
+                    }
+                }
+                for (idx, position) in rest.iter().zip(greedy) {
+                    placed[*idx] = position;
+                }
+                for (note_idx, position) in to_place.iter().zip(placed) {
+                    match position {
+                        Some(_) => positions[*note_idx] = position,
+                        None => diagnostics.push(Diagnostic::warn(
+                            notes[*note_idx].location.clone(),
+                            DiagnosticKind::NoStringForNote,
+                        )),
+                    }
+                }
+                hand = hand_position(&positions).or(hand);
+                for (note, position) in notes.iter().zip(&positions) {
+                    let Some(Position { string, fret }) = *position else {
+                        continue;
+                    };
+                    let string = string as usize;
+                    r.tick_stream[tick_start + string] =
+                        if note.dead { TabElement::DeadNote } else { TabElement::Fret(fret) };
+                    sounding[string] = Some(note.pitch);
+                    linked[string] = matches!(note.link, Some(Link::Slur | Link::Slide));
+                    holds.push((tick_start + string, note.hold));
+                    if let Some(link) = note.link {
+                        links.push((tick_start, string, link));
+                    }
+                }
+            }
+            // an empty measure can't be written in a tab, so it gets a silent tick
+            if r.tick_stream.len() as u32 == measure_start {
+                r.tick_stream.extend([const { TabElement::Rest }; 6]);
+            }
+            parsed_track.measures.push(r.measures.len() as u32);
+            r.measures.push(Measure::from(measure_start..=r.tick_stream.len() as u32 - 1));
+        }
+        for (idx, hold) in holds {
+            let held = r.tick_stream.iter_mut().skip(idx + 6).step_by(6).take(hold as usize);
+            for elem in held.take_while(|x| **x == TabElement::Rest) {
+                *elem = TabElement::Vibrato;
+            }
+        }
+        write_links(&mut r.tick_stream[track_start..], &links, track_start);
+        r.tracks.push(parsed_track);
+    }
+    r
+}
+
+/// Places the notes of a track with [optimize]. The result has the position of every note of
+/// every tick of the track, or [None] for tied notes and the ones which can't be played.
+fn plan_fingering(track: &ImportedTrack, settings: &ImportSettings) -> Vec<Vec<Option<Position>>> {
+    let fingering = FingeringSettings {
+        max_fret: settings.max_fret,
+        // in a tab, `2` and `4` in consecutive ticks would be read as `24`
+        separate_adjacent: true,
+        ..Default::default()
+    };
+    let mut ticks: Vec<FingeringTick> = vec![];
+    // the index in `ticks` of every note, or [None] if it continues a tie
+    let mut indices: Vec<Vec<Option<usize>>> = vec![];
+    // (tick, index) of the note sounding with every pitch, which a tie continues
+    let mut sounding: HashMap<u8, (usize, usize)> = HashMap::new();
+    for notes in track.measures.iter().flat_map(|x| &x.ticks) {
+        let tick_idx = ticks.len();
+        let mut tick = FingeringTick::default();
+        let mut tick_indices = vec![];
+        for note in notes {
+            let tied = sounding.get(&note.pitch).filter(|_| note.tie_stop);
+            if let Some((source_tick, source_idx)) = tied {
+                // the string stays taken until the end of the tie
+                let length = tick_idx - source_tick + 1 + note.hold as usize;
+                ticks[*source_tick].notes[*source_idx].length = length;
+                tick_indices.push(None);
+                continue;
+            }
+            sounding.insert(note.pitch, (tick_idx, tick.notes.len()));
+            tick_indices.push(Some(tick.notes.len()));
+            let length = 1 + note.hold as usize;
+            // like in [build_parse_result], the first note given for a string keeps it
+            let taken = |x: &Position| {
+                tick.notes.iter().any(|n| n.fixed.map(|n| n.string) == Some(x.string))
+            };
+            let fixed = note.position.filter(|x| !taken(x));
+            tick.notes.push(FingeringNote { pitch: note.pitch, fixed, length });
+        }
+        ticks.push(tick);
+        indices.push(tick_indices);
+    }
+    let placed = optimize(&ticks, &settings.tuning, &fingering);
+    indices
+        .into_iter()
+        .zip(placed)
+        .map(|(tick_indices, positions)| {
+            tick_indices.into_iter().map(|x| x.and_then(|x| positions[x])).collect()
+        })
+        .collect()
+}
+
+/// Writes the technique of every link into the tick after its note, if that tick is free
+fn write_links(stream: &mut [TabElement], links: &[(usize, usize, Link)], offset: usize) {
+    for (tick_start, string, link) in links {
+        let from = tick_start - offset + string;
+        let between = from + 6;
+        if !matches!(stream.get(between), Some(TabElement::Rest)) {
+            continue;
+        }
+        let next_fret = stream[between..].iter().step_by(6).find_map(|x| match x {
+            TabElement::Fret(fret) => Some(Some(*fret)),
+            TabElement::Rest => None,
+            _ => Some(None),
+        });
+        // a tied note is written as `~`, but can still have vibrato
+        let fret = match stream[from] {
+            TabElement::Fret(fret) => Some(fret),
+            TabElement::Vibrato => None,
+            _ => continue,
+        };
+        stream[between] = match (link, fret, next_fret.flatten()) {
+            (Link::Vibrato, _, _) => TabElement::Vibrato,
+            (Link::Slide, Some(_), Some(_)) => TabElement::Slide,
+            (Link::Slur, Some(fret), Some(next)) if next > fret => TabElement::HammerOn,
+            (Link::Slur, Some(fret), Some(next)) if next < fret => TabElement::Pull,
+            _ => continue,
+        };
+    }
+}
@@ -0,0 +1,278 @@
+//! Reads `score-partwise` MusicXML, like the files written by [crate::backend::muxml].
+//!
+//! Every `<part>` becomes a track. Notes keep their `<technical><string>/<fret>` position if they
+//! have one, the others are placed by [crate::fingering]. All voices of a part end up on the same
+//! tab, but only the first staff is read, as the second one is usually a tablature staff with the
+//! same notes.
+
+use std::time::Instant;
+
+use super::{
+    build_parse_result,
+    xml::{self, Element},
+    ImportSettings, ImportedMeasure, ImportedNote, ImportedTrack, Link,
+};
+use crate::{
+    backend::{
+        errors::{
+            backend_error::BackendError, diagnostic::Diagnostic, diagnostic_kind::DiagnosticKind,
+            error_location::ErrorLocation,
+        },
+        registry::{Registry, SelectorBackend},
+        Backend, BackendResult, BackendSelector,
+    },
+    fingering::Position,
+    tab_writer::{write_tab, TabWriterSettings},
+    time, traceln,
+};
+
+pub struct MuxmlImportBackend();
+impl Backend for MuxmlImportBackend {
+    type BackendSettings = ImportSettings;
+
+    fn process<Out: std::io::Write>(
+        input: &[String], out: &mut Out, settings: Self::BackendSettings,
+    ) -> BackendResult {
+        let mut diagnostics = vec![];
+        let (parse_time, document) = time(|| xml::parse_document(input));
+        let document = match document {
+            Ok(x) => x,
+            Err(e) => {
+                let err = BackendError::invalid_xml(e.line, e.message);
+                return BackendResult::new(diagnostics, Some(err), Some(parse_time), None);
+            }
+        };
+        let gen_start = Instant::now();
+        let tracks = match read_score(&document, &mut diagnostics) {
+            Ok(x) => x,
+            Err(e) => return BackendResult::new(diagnostics, Some(e), Some(parse_time), None),
+        };
+        let parsed = build_parse_result(&tracks, &settings, &mut diagnostics);
+        let mut tab = String::new();
+        let mut r = BackendResult::new(diagnostics, None, Some(parse_time), None);
+        if let Err(e) = write_tab(&parsed, &mut tab, &settings.writer) {
+            r.err = Some(e.into());
+            return r;
+        }
+        r.timing_gen = Some(gen_start.elapsed());
+        if let Err(e) = out.write_all(tab.as_bytes()) {
+            r.err = Some(e.into());
+        }
+        r
+    }
+}
+
+pub(crate) fn register(registry: &mut Registry) {
+    let options = &[
+        ("max-fret", "The highest fret a note may be placed on, 24 by default"),
+        ("measures-per-line", "How many measures to write on a line, 4 by default"),
+        ("width", "The maximum number of characters on a line"),
+    ];
+    registry.register(
+        SelectorBackend::new(
+            "from-muxml",
+            "Reads MusicXML and writes a tab",
+            &["tab"],
+            options,
+            |o| {
+                Ok(BackendSelector::MuxmlImport(ImportSettings {
+                    max_fret: o.get_or("max-fret", 24)?,
+                    writer: TabWriterSettings::from_options(o)?,
+                    ..Default::default()
+                }))
+            },
+        )
+        .reads(&["musicxml", "xml"], false),
+    );
+}
+
+/// Reads every part of a `<score-partwise>` element
+pub fn read_score(
+    root: &Element, diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<ImportedTrack>, BackendError> {
+    if root.name != "score-partwise" {
+        let message = format!("Only score-partwise documents are supported, not {}", root.name);
+        return Err(BackendError::invalid_xml(root.line, message));
+    }
+    let score_parts = root.child("part-list").map(|x| x.children("score-part").collect());
+    let score_parts: Vec<&Element> = score_parts.unwrap_or_default();
+    let mut tracks = vec![];
+    for part in root.children("part") {
+        let id = part.attribute("id").unwrap_or_default();
+        let score_part = score_parts.iter().find(|x| x.attribute("id") == Some(id));
+        let name = score_part
+            .and_then(|x| x.child("part-name"))
+            .map(|x| x.text.clone())
+            .filter(|x| !x.is_empty())
+            .unwrap_or_else(|| id.to_string());
+        // MusicXML counts programs from 1
+        let program = score_part
+            .and_then(|x| x.parse_at::<u8>(&["midi-instrument", "midi-program"]))
+            .and_then(|x| x.checked_sub(1));
+        traceln!("musicxml import: reading part {id} ({name})");
+        let measures = read_part(part, diagnostics)?;
+        tracks.push(ImportedTrack { name, program, measures });
+    }
+    Ok(tracks)
+}
+
+/// The state carried over from the `<attributes>` of earlier measures
+struct PartState {
+    divisions: u32,
+    /// The length of a measure without notes, in eighths
+    meter_len: u32,
+    /// What has to be added to the written pitch to get the pitch of the tab.
+    /// Mirrors [crate::backend::muxml::settings::Settings::pitch_shift].
+    pitch_shift: i16,
+}
+
+fn read_part(
+    part: &Element, diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<ImportedMeasure>, BackendError> {
+    let mut state = PartState { divisions: 1, meter_len: 8, pitch_shift: 0 };
+    let mut measures = vec![];
+    for measure in part.children("measure") {
+        // the onset of every note, in divisions
+        let mut notes: Vec<(u32, ImportedNote)> = vec![];
+        let (mut pos, mut end, mut last_onset) = (0u32, 0u32, 0u32);
+        for child in &measure.children {
+            match child.name.as_str() {
+                "attributes" => read_attributes(child, &mut state)?,
+                "backup" => pos = pos.saturating_sub(child.parse_at(&["duration"]).unwrap_or(0)),
+                "forward" => pos += child.parse_at::<u32>(&["duration"]).unwrap_or(0),
+                "note" => {
+                    if child.child("grace").is_some() || child.child("cue").is_some() {
+                        continue;
+                    }
+                    let onset = if child.child("chord").is_some() { last_onset } else { pos };
+                    let duration = child.parse_at::<u32>(&["duration"]).unwrap_or(0);
+                    if child.child("chord").is_none() {
+                        pos += duration;
+                    }
+                    last_onset = onset;
+                    let staff = child.parse_at::<u32>(&["staff"]).unwrap_or(1);
+                    if staff != 1 {
+                        continue;
+                    }
+                    if let Some(note) = read_note(child, &state, duration)? {
+                        notes.push((onset, note));
+                    }
+                }
+                _ => {}
+            }
+            end = end.max(pos);
+        }
+
+        let len = match end * 2 {
+            0 => state.meter_len,
+            scaled => scaled.div_ceil(state.divisions),
+        };
+        let mut imported = ImportedMeasure { ticks: vec![vec![]; len as usize] };
+        for (onset, mut note) in notes {
+            let scaled = onset * 2;
+            if scaled % state.divisions != 0 {
+                diagnostics
+                    .push(Diagnostic::info(note.location.clone(), DiagnosticKind::NoteQuantized));
+            }
+            let tick = ((scaled + state.divisions / 2) / state.divisions).min(len - 1);
+            // a note held over the barline is continued by a tied note in the next measure
+            note.hold = note.hold.min(len - 1 - tick);
+            imported.ticks[tick as usize].push(note);
+        }
+        measures.push(imported);
+    }
+    Ok(measures)
+}
+
+fn read_attributes(attributes: &Element, state: &mut PartState) -> Result<(), BackendError> {
+    if let Some(divisions) = attributes.child("divisions") {
+        state.divisions = divisions.text.parse().ok().filter(|x| *x > 0).ok_or_else(|| {
+            BackendError::invalid_xml(
+                divisions.line,
+                format!("Invalid divisions {}", divisions.text),
+            )
+        })?;
+    }
+    if let Some(time) = attributes.child("time") {
+        let beats: Option<u32> = time.parse_at(&["beats"]);
+        let beat_type: Option<u32> = time.parse_at(&["beat-type"]);
+        if let Some((beats, beat_type)) = beats.zip(beat_type).filter(|x| x.1 > 0) {
+            state.meter_len = (beats * 8 / beat_type).max(1);
+        }
+    }
+    let clef = attributes
+        .children("clef")
+        .find(|x| x.attribute("number").map(|x| x == "1").unwrap_or(true));
+    if let Some(clef) = clef {
+        let bass = clef.find(&["sign"]).map(|x| x.text == "F").unwrap_or(false);
+        let octave_change: i16 = clef.parse_at(&["clef-octave-change"]).unwrap_or(0);
+        state.pitch_shift = if bass { 12 } else { 0 } - octave_change * 12;
+    }
+    Ok(())
+}
+
+fn read_note(
+    note: &Element, state: &PartState, duration: u32,
+) -> Result<Option<ImportedNote>, BackendError> {
+    let Some(pitch) = note.child("pitch") else {
+        // rests and unpitched notes
+        return Ok(None);
+    };
+    let invalid = || BackendError::invalid_xml(pitch.line, "Invalid pitch".to_string());
+    let pitch_class: i16 = match pitch.find(&["step"]).map(|x| x.text.as_str()) {
+        Some("C") => 0,
+        Some("D") => 2,
+        Some("E") => 4,
+        Some("F") => 5,
+        Some("G") => 7,
+        Some("A") => 9,
+        Some("B") => 11,
+        _ => return Err(invalid()),
+    };
+    // microtones are rounded to the closest semitone
+    let alter = pitch.parse_at::<f32>(&["alter"]).unwrap_or(0.0).round() as i16;
+    let octave: i16 = pitch.parse_at(&["octave"]).ok_or_else(invalid)?;
+    let step = octave * 12 + pitch_class + alter + state.pitch_shift;
+    let step = u8::try_from(step).map_err(|_| invalid())?;
+
+    let mut imported = ImportedNote::new(step, ErrorLocation::LineOnly(note.line));
+    imported.dead = note.child("notehead").map(|x| x.text == "x").unwrap_or(false);
+    let is_stop = |x: &Element| x.attribute("type") == Some("stop");
+    let is_start = |x: &Element| x.attribute("type") == Some("start");
+    imported.tie_stop = note.children("tie").any(is_stop);
+    let mut tie_start = note.children("tie").any(is_start);
+    if let Some(notations) = note.child("notations") {
+        imported.tie_stop |= notations.children("tied").any(is_stop);
+        tie_start |= notations.children("tied").any(is_start);
+        let technical = notations.child("technical");
+        if let Some(technical) = technical {
+            let string: Option<u8> = technical.parse_at(&["string"]);
+            let fret: Option<u8> = technical.parse_at(&["fret"]);
+            if let Some((string, fret)) = string.zip(fret).filter(|x| (1..=6).contains(&x.0)) {
+                imported.position = Some(Position { string: string - 1, fret });
+            }
+        }
+        let slurred = notations.children("slur").any(is_start)
+            || technical.is_some_and(|x| {
+                x.children("hammer-on").chain(x.children("pull-off")).any(is_start)
+            });
+        let slide =
+            notations.children("slide").chain(notations.children("glissando")).any(is_start);
+        let vibrato =
+            notations.child("ornaments").is_some_and(|x| x.children("wavy-line").any(is_start));
+        imported.link = if slide {
+            Some(Link::Slide)
+        } else if slurred {
+            Some(Link::Slur)
+        } else if vibrato {
+            Some(Link::Vibrato)
+        } else {
+            None
+        };
+    }
+    if tie_start || imported.tie_stop || imported.link == Some(Link::Vibrato) {
+        let ticks = (duration * 2 + state.divisions / 2) / state.divisions;
+        imported.hold = ticks.saturating_sub(1);
+    }
+    Ok(Some(imported))
+}
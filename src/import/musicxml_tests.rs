@@ -0,0 +1,112 @@
+use crate::backend::errors::backend_error_kind::BackendErrorKind;
+use crate::backend::{muxml::MuxmlBackend, Backend};
+use crate::import::{musicxml::MuxmlImportBackend, ImportSettings};
+use crate::parser::parser::parse;
+use itertools::Itertools;
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(|x| x.to_string()).collect_vec()
+}
+
+fn to_muxml(tab: &[String]) -> String {
+    let mut out = vec![];
+    let res = MuxmlBackend::process(tab, &mut out, Default::default());
+    assert!(res.err.is_none());
+    String::from_utf8(out).unwrap()
+}
+
+fn import(musicxml: &[String]) -> String {
+    let mut out = vec![];
+    let res = MuxmlImportBackend::process(musicxml, &mut out, ImportSettings::default());
+    assert!(res.err.is_none(), "{:?}", res.err);
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_muxml_round_trip() {
+    for path in
+        ["input/c_major.tab", "input/ties.tab", "input/multi_track.tab", "input/vibrato.tab"]
+    {
+        let tab = lines(&std::fs::read_to_string(path).unwrap());
+        let musicxml = to_muxml(&tab);
+        let imported = import(&lines(&musicxml));
+        let reparsed = parse(&lines(&imported));
+        assert!(reparsed.error.is_none(), "{path}:\n{imported}");
+        // the fingering can differ, but the score has to be the same
+        assert_eq!(to_muxml(&lines(&imported)), musicxml, "{path}:\n{imported}");
+    }
+}
+
+#[test]
+fn test_muxml_import() {
+    // a quarter note chord with fixed positions, then a C which is placed automatically,
+    // a grace note that is skipped, and a hammer-on over an eighth rest
+    let musicxml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="4.0">
+  <part-list>
+    <score-part id="P1"><part-name>Lead</part-name>
+      <midi-instrument id="P1-I1"><midi-program>28</midi-program></midi-instrument>
+    </score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes>
+        <divisions>4</divisions>
+        <time><beats>2</beats><beat-type>4</beat-type></time>
+        <clef><sign>G</sign><line>2</line><clef-octave-change>-1</clef-octave-change></clef>
+      </attributes>
+      <note>
+        <pitch><step>A</step><octave>2</octave></pitch><duration>4</duration>
+        <notations><technical><string>5</string><fret>0</fret></technical></notations>
+      </note>
+      <note>
+        <chord/><pitch><step>E</step><octave>3</octave></pitch><duration>4</duration>
+        <notations><technical><string>5</string><fret>7</fret></technical></notations>
+      </note>
+      <note><grace/><pitch><step>D</step><octave>3</octave></pitch></note>
+      <note>
+        <pitch><step>C</step><octave>3</octave></pitch><duration>2</duration>
+        <notations><slur type="start"/></notations>
+      </note>
+      <forward><duration>2</duration></forward>
+      <note><pitch><step>D</step><octave>3</octave></pitch><duration>2</duration></note>
+    </measure>
+    <measure number="2">
+      <note><rest measure="yes"/><duration>8</duration></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+    let imported = import(&lines(musicxml));
+    // the E can't go on the A string, which is taken, so it moves to the D string
+    assert_eq!(
+        imported,
+        r#"Track: Lead program=27
+e|-----|----|
+B|-----|----|
+G|-----|----|
+D|2----|----|
+A|0-3h5|----|
+E|-----|----|
+"#
+    );
+}
+
+#[test]
+fn test_muxml_import_errors() {
+    let mut out = vec![];
+    let res = MuxmlImportBackend::process(
+        &lines("<score-partwise>\n<part>\n</score-partwise>"),
+        &mut out,
+        ImportSettings::default(),
+    );
+    let err = res.err.unwrap();
+    assert!(matches!(err.kind, BackendErrorKind::InvalidXml(_)));
+    assert_eq!(err.main_location.get_line_idx(), Some(2));
+
+    let res = MuxmlImportBackend::process(
+        &lines("<score-timewise></score-timewise>"),
+        &mut out,
+        ImportSettings::default(),
+    );
+    assert!(matches!(res.err.unwrap().kind, BackendErrorKind::InvalidXml(_)));
+}
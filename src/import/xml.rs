@@ -0,0 +1,260 @@
+//! A small XML reader, just enough for MusicXML: elements, attributes, text and the predefined
+//! entities. Comments, processing instructions, the doctype and CDATA markers are skipped.
+
+/// An XML element with its children. Text content is stored concatenated and trimmed, which is
+/// fine for MusicXML, where text never appears next to child elements.
+#[derive(Debug, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+    /// The line index of the start tag
+    pub line: usize,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|x| x.0 == name).map(|x| x.1.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|x| x.name == name)
+    }
+
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |x| x.name == name)
+    }
+
+    /// Follows a path of child names, like `["pitch", "step"]`
+    pub fn find(&self, path: &[&str]) -> Option<&Element> {
+        path.iter().try_fold(self, |elem, name| elem.child(name))
+    }
+
+    /// The text of the element at `path`, parsed
+    pub fn parse_at<T: std::str::FromStr>(&self, path: &[&str]) -> Option<T> {
+        self.find(path)?.text.parse().ok()
+    }
+}
+
+/// A syntax error, with the line index where it happened
+#[derive(Debug)]
+pub struct XmlError {
+    pub line: usize,
+    pub message: String,
+}
+
+struct Reader<'a> {
+    src: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl Reader<'_> {
+    fn err(&self, message: impl Into<String>) -> XmlError {
+        XmlError { line: self.line, message: message.into() }
+    }
+
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.line += self.src[self.pos..self.pos + len].matches('\n').count();
+        self.pos += len;
+    }
+
+    /// Skips past the next `pattern`
+    fn skip_past(&mut self, pattern: &str) -> Result<(), XmlError> {
+        match self.rest().find(pattern) {
+            Some(x) => {
+                self.advance(x + pattern.len());
+                Ok(())
+            }
+            None => Err(self.err(format!("Expected {pattern} before the end of the file"))),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let len = self.rest().len() - self.rest().trim_start().len();
+        self.advance(len);
+    }
+
+    fn name(&mut self) -> Result<String, XmlError> {
+        let len = self
+            .rest()
+            .find(|c: char| c.is_whitespace() || matches!(c, '>' | '/' | '='))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(self.err("Expected a name"));
+        }
+        let name = self.rest()[..len].to_string();
+        self.advance(len);
+        Ok(name)
+    }
+
+    /// Reads markup that is not an element: comments, declarations and processing instructions.
+    /// Returns false if the next markup is an element.
+    fn skip_misc(&mut self) -> Result<bool, XmlError> {
+        let rest = self.rest();
+        if rest.starts_with("<!--") {
+            self.skip_past("-->")?;
+        } else if rest.starts_with("<![CDATA[") {
+            return Err(self.err("CDATA sections are not supported"));
+        } else if rest.starts_with("<?") {
+            self.skip_past("?>")?;
+        } else if rest.starts_with("<!") {
+            self.skip_past(">")?;
+        } else {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Reads an element, starting at its `<`
+    fn element(&mut self) -> Result<Element, XmlError> {
+        let line = self.line;
+        self.advance(1);
+        let name = self.name()?;
+        let mut element = Element { name, line, ..Default::default() };
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.advance(2);
+                return Ok(element);
+            } else if rest.starts_with('>') {
+                self.advance(1);
+                break;
+            }
+            let attribute = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.err(format!("Expected = after the attribute {attribute}")));
+            }
+            self.advance(1);
+            self.skip_whitespace();
+            let Some(quote) = self.rest().chars().next().filter(|x| matches!(x, '"' | '\'')) else {
+                return Err(self.err(format!("Expected a quoted value for {attribute}")));
+            };
+            self.advance(1);
+            let Some(len) = self.rest().find(quote) else {
+                return Err(self.err(format!("Unterminated value for {attribute}")));
+            };
+            let value = unescape(&self.rest()[..len]);
+            self.advance(len + 1);
+            element.attributes.push((attribute, value));
+        }
+        // content
+        loop {
+            let Some(len) = self.rest().find('<') else {
+                return Err(self.err(format!("The element {} is not closed", element.name)));
+            };
+            element.text += &unescape(&self.rest()[..len]);
+            self.advance(len);
+            if self.skip_misc()? {
+                continue;
+            }
+            if self.rest().starts_with("</") {
+                self.advance(2);
+                let closing = self.name()?;
+                if closing != element.name {
+                    return Err(
+                        self.err(format!("Expected </{}>, found </{closing}>", element.name))
+                    );
+                }
+                self.skip_past(">")?;
+                let trimmed = element.text.trim();
+                if trimmed.len() != element.text.len() {
+                    element.text = trimmed.to_string();
+                }
+                return Ok(element);
+            }
+            let child = self.element()?;
+            element.children.push(child);
+        }
+    }
+}
+
+fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|x| u32::from_str_radix(x, 16).ok())
+                .unwrap_or_else(|| entity.strip_prefix('#').and_then(|x| x.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Reads the root element of a document
+pub fn parse_document(lines: &[String]) -> Result<Element, XmlError> {
+    let src = lines.join("\n");
+    let mut reader = Reader { src: &src, pos: 0, line: 0 };
+    loop {
+        reader.skip_whitespace();
+        if reader.rest().is_empty() {
+            return Err(reader.err("The document has no root element"));
+        }
+        if !reader.rest().starts_with('<') {
+            return Err(reader.err("Expected an element"));
+        }
+        if !reader.skip_misc()? {
+            return reader.element();
+        }
+    }
+}
+
+#[test]
+fn test_parse_document() {
+    let src = r#"<?xml version="1.0"?>
+<!DOCTYPE score-partwise>
+<!-- a comment -->
+<root a="1" b='&lt;2&gt;'>
+  <child>Tom &amp; Jerry</child>
+  <empty/>
+  <child>&#65;</child>
+</root>"#;
+    let lines = src.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let root = parse_document(&lines).unwrap();
+    assert_eq!(root.name, "root");
+    assert_eq!(root.line, 3);
+    assert_eq!(root.attribute("b"), Some("<2>"));
+    assert_eq!(
+        root.children("child").map(|x| x.text.as_str()).collect::<Vec<_>>(),
+        ["Tom & Jerry", "A"]
+    );
+    assert!(root.child("empty").is_some());
+
+    let lines = ["<a>", "<b></a>"].map(|x| x.to_string());
+    assert_eq!(parse_document(&lines).unwrap_err().line, 1);
+}
@@ -19,7 +19,10 @@ use std::{
 };
 
 pub mod backend;
+pub mod fingering;
+pub mod import;
 pub mod parser;
+pub mod tab_writer;
 #[macro_export]
 macro_rules! traceln {
     (depth=$depth:literal, $($t:expr),*) => {
@@ -1,21 +1,26 @@
 use std::{
     fmt::Write,
     fs::{File, OpenOptions},
-    io::{BufRead, BufReader, StdoutLock},
+    io::{BufRead, BufReader, Read, StdoutLock},
 };
 
 use anyhow::Context;
 use clap::Parser;
 use scoreman::{
-    backend::errors::{
-        backend_error::BackendError, diagnostic::Diagnostic, error_location::ErrorLocation,
-        extend_error_range,
+    backend::{
+        errors::{
+            backend_error::BackendError, diagnostic::Diagnostic, error_location::ErrorLocation,
+            extend_error_range,
+        },
+        registry::Registry,
     },
     digit_cnt_usize,
 };
 use yansi::{Paint, Painted};
 
 mod cli_args;
+#[cfg(test)]
+mod cli_args_tests;
 use crate::cli_args::Cli;
 
 // TODO: error reporting without slurping up the whole file
@@ -37,6 +42,18 @@ fn get_lines(input_path: &str) -> anyhow::Result<Vec<String>> {
     }
 }
 
+fn get_bytes(input_path: &str) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    if input_path == "-" {
+        std::io::stdin().read_to_end(&mut bytes)?;
+    } else {
+        let mut f =
+            File::open(input_path).with_context(|| format!("Failed to open file {input_path}"))?;
+        f.read_to_end(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
 enum OutputType {
     File(File),
     Stdout(StdoutLock<'static>),
@@ -60,7 +77,14 @@ impl std::io::Write for OutputType {
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     let input_path = cli.command.input_path();
-    let lines: Vec<String> = get_lines(input_path)?;
+    let registry = Registry::with_builtins();
+    let (backend, options) = cli.command.to_backend(&registry)?;
+    // binary input has no lines to point errors at
+    let (lines, bytes) = if backend.input_is_binary() {
+        (vec![], Some(get_bytes(input_path)?))
+    } else {
+        (get_lines(input_path)?, None)
+    };
 
     let mut output_fd = if cli.command.output_path() == "-" {
         OutputType::Stdout(std::io::stdout().lock())
@@ -74,9 +98,10 @@ fn main() -> anyhow::Result<()> {
         OutputType::File(output_file)
     };
 
-    let command = &cli.command;
-    let backend = command.to_backend_selector();
-    let mut result = backend.process(&lines, &mut output_fd);
+    let mut result = match bytes {
+        Some(bytes) => backend.process_bytes(&bytes, &mut output_fd, &options),
+        None => backend.process(&lines, &mut output_fd, &options),
+    };
     match &mut result.err {
         Some(x) => handle_error(x, &mut result.diagnostics, &lines)?,
         None => {
@@ -120,6 +145,11 @@ pub fn handle_error(
 
     let mut location_explainer = String::new();
     main_location.write_location_explainer(&mut location_explainer);
+    if lines.is_empty() || *main_location == ErrorLocation::NoLocation {
+        let (short, long) = kind.desc();
+        eprintln!("\n{}\n{}", format!("Error: {short}").bold().red(), long.red());
+        return Ok(());
+    }
 
     let extended_range = extend_error_range(relevant_lines, lines.len());
     let max_digit_cnt = digit_cnt_usize(*extended_range.end());
@@ -1,6 +1,10 @@
+#[allow(clippy::module_inception)]
 pub mod parser;
 #[cfg(test)]
 mod parser_tests;
+pub mod score;
+#[cfg(test)]
+mod score_tests;
 pub(crate) mod tab_element;
 
 pub fn char(c: char) -> impl Fn(&str) -> Result<(&str, char), &str> {
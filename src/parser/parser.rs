@@ -29,6 +29,59 @@ impl Measure {
     }
 }
 
+/// An instrument in the score, started by a `Track: <name>` line. An optional `program=<n>` at
+/// the end of that line selects the General MIDI program.
+///
+/// Every part of the score belongs to the track whose directive was last seen before it. Parts
+/// before the first directive belong to a default track called `Guitar1`.
+#[derive(Debug)]
+pub struct Track {
+    pub name: String,
+    pub program: Option<u8>,
+    /// Indices into [ParseResult::measures], in order
+    pub measures: Vec<u32>,
+}
+impl Track {
+    pub fn new(name: String, program: Option<u8>) -> Self {
+        Self { name, program, measures: vec![] }
+    }
+
+    /// The explicitly set General MIDI program, or a guess based on the name of the track
+    pub fn midi_program(&self) -> u8 {
+        if let Some(program) = self.program {
+            return program;
+        }
+        let name = self.name.to_lowercase();
+        if name.contains("bass") {
+            33
+        } else if name.contains("nylon") || name.contains("classical") {
+            24
+        } else if name.contains("distortion") || name.contains("overdrive") {
+            30
+        } else if name.contains("electric") {
+            27
+        } else {
+            25
+        }
+    }
+}
+
+/// Parses lines like `Track: Bass program=33`
+pub fn track_directive(line: &str) -> Option<Track> {
+    let line = line.trim();
+    let prefix = line.get(0..6)?;
+    if !prefix.eq_ignore_ascii_case("track:") {
+        return None;
+    }
+    let rest = line[6..].trim();
+    let (name, program) = match rest.rsplit_once("program=") {
+        Some((name, program)) => (name, program.trim().parse::<u8>().ok().filter(|x| *x < 128)),
+        None => (rest, None),
+    };
+    let name = name.trim().trim_end_matches([',', ';']).trim();
+    Some(Track::new(name.to_string(), program))
+}
+
 #[derive(Debug, Default)]
 pub struct ParseResult {
     /// This is not a [Result] because we want to preserve the partial parse state, eg. for fixup or recovery
@@ -39,6 +92,8 @@ pub struct ParseResult {
     /// The line on which the n-th section begins and the index of the first tick in that section.
     /// This provides enough information to restore from where we have read an individual tick.
     pub offsets: Vec<(u32, u32)>,
+    /// The instruments in the score, see [Track]
+    pub tracks: Vec<Track>,
 }
 
 impl ParseResult {
@@ -72,11 +127,18 @@ impl ParseResult {
         bufs.iter_mut().for_each(|x| x.push('\n'));
         bufs.concat()
     }
+
+    /// The name of the string the element at `stream_idx` is on, as written in its part
+    pub fn base_note(&self, stream_idx: usize) -> char {
+        let part = self.offsets.partition_point(|x| x.1 as usize <= stream_idx).saturating_sub(1);
+        self.base_notes[part * 6 + stream_idx % 6]
+    }
 }
 
 pub fn parse(lines: &[String]) -> ParseResult {
     let mut r = ParseResult::new();
     let mut part_first_line = 0;
+    let mut current_track = 0;
     'outer: loop {
         // find a part
         loop {
@@ -87,9 +149,23 @@ pub fn parse(lines: &[String]) -> ParseResult {
             {
                 break;
             }
+            if let Some(track) = track_directive(&lines[part_first_line]) {
+                traceln!("parse3: Found track {track:?}");
+                current_track = match r.tracks.iter().position(|x| x.name == track.name) {
+                    Some(existing) => existing,
+                    None => {
+                        r.tracks.push(track);
+                        r.tracks.len() - 1
+                    }
+                };
+            }
             part_first_line += 1
         }
         traceln!("parse3: Found part {part_first_line}..={}", part_first_line + 5);
+        if r.tracks.is_empty() {
+            r.tracks.push(Track::new("Guitar1".to_string(), None));
+        }
+        let part_first_measure = r.measures.len() as u32;
         r.offsets.push((part_first_line as u32, r.tick_stream.len() as u32));
         let mut part: Vec<&str> = lines[part_first_line..=part_first_line + 5]
             .iter()
@@ -211,6 +287,7 @@ pub fn parse(lines: &[String]) -> ParseResult {
         });
         // finished parsing part
         traceln!("Finished part\n{}", r.dump_tracks());
+        r.tracks[current_track].measures.extend(part_first_measure..r.measures.len() as u32);
 
         part_first_line += 6;
     }
@@ -276,9 +353,9 @@ pub fn source_location_from_stream(r: &ParseResult, tick_location: u32) -> (u32,
     let mut measure_lines = 0;
     traceln!("{:?}", r.measures);
     traceln!("part start: {part_start}");
-    for (m_idx, measure) in r.measures[0..last_measure].iter().enumerate().rev() {
+    for (_m_idx, measure) in r.measures[0..last_measure].iter().enumerate().rev() {
         if measure.data_range.start() < &part_start {
-            traceln!("breaking at measure {m_idx}");
+            traceln!("breaking at measure {_m_idx}");
             break;
         }
         measure_lines += 1;
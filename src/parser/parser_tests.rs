@@ -153,3 +153,40 @@ E|-----9-|-----9-|
     insta::assert_snapshot!(parse3_result.dump_tracks());
     insta::assert_debug_snapshot!(parse3_result);
 }
+
+#[test]
+fn test_tracks() {
+    let input = r#"
+Track: Guitar 1
+e|-1-|---|
+B|---|---|
+G|---|---|
+D|---|---|
+A|---|---|
+E|---|---|
+
+Track: Bass program=34
+e|---|
+B|---|
+G|---|
+D|---|
+A|-3-|
+E|---|
+
+Track: Guitar 1
+e|-2-|
+B|---|
+G|---|
+D|---|
+A|---|
+E|---|
+"#;
+    let parsed = parse(&to_lines(input));
+    assert!(parsed.error.is_none());
+    let tracks = parsed
+        .tracks
+        .iter()
+        .map(|x| (x.name.as_str(), x.midi_program(), &x.measures[..]))
+        .collect::<Vec<_>>();
+    assert_eq!(tracks, [("Guitar 1", 25, &[0, 1, 3][..]), ("Bass", 34, &[2][..])]);
+}
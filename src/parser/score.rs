@@ -0,0 +1,429 @@
+//! A typed view of a [ParseResult]: tracks, Parts, measures, ticks and notes, without copying
+//! anything out of the tick stream.
+//!
+//! ```
+//! use scoreman::parser::parser::parse;
+//! let input = "
+//! e|---|
+//! B|-1-|
+//! G|---|
+//! D|-2-|
+//! A|3--|
+//! E|---|";
+//! let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+//! let parsed = parse(&lines);
+//! for measure in parsed.score().measures() {
+//!     for tick in measure.ticks() {
+//!         for note in tick.notes() {
+//!             println!("string {} fret {:?} pitch {}", note.string(), note.fret(), note.pitch());
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Strings are numbered from 0, the first line of a Part, which is usually the highest string.
+//! Pitches are MIDI keys, read from the string names of the Part like in [crate::fingering].
+
+use super::{
+    parser::{self, source_location_from_stream, ParseResult},
+    tab_element::TabElement,
+};
+use crate::{fingering::Tuning, rlen};
+
+impl ParseResult {
+    /// The typed view of the score
+    pub fn score(&self) -> Score<'_> {
+        Score { parsed: self }
+    }
+}
+
+/// How a note is connected to the next one on its string. It is written on its own tick, between
+/// the two notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Technique {
+    Bend,
+    Release,
+    HammerOn,
+    PullOff,
+    Slide,
+    /// Also holds a note over a barline, see [Track::sounding_notes]
+    Vibrato,
+}
+
+impl Technique {
+    /// The technique written as `elem`, if it is one
+    pub fn from_element(elem: &TabElement) -> Option<Technique> {
+        match elem {
+            TabElement::Bend => Some(Technique::Bend),
+            TabElement::Release => Some(Technique::Release),
+            TabElement::HammerOn => Some(Technique::HammerOn),
+            TabElement::Pull => Some(Technique::PullOff),
+            TabElement::Slide => Some(Technique::Slide),
+            TabElement::Vibrato => Some(Technique::Vibrato),
+            TabElement::Fret(_) | TabElement::Rest | TabElement::DeadNote => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Score<'a> {
+    parsed: &'a ParseResult,
+}
+
+impl<'a> Score<'a> {
+    pub fn parsed(&self) -> &'a ParseResult {
+        self.parsed
+    }
+
+    pub fn tracks(&self) -> impl ExactSizeIterator<Item = Track<'a>> + 'a {
+        let score = *self;
+        (0..self.parsed.tracks.len()).map(move |index| Track { score, index })
+    }
+
+    pub fn parts(&self) -> impl ExactSizeIterator<Item = Part<'a>> + 'a {
+        let score = *self;
+        (0..self.parsed.offsets.len()).map(move |index| Part { score, index })
+    }
+
+    /// Every measure in the order of the input. See [Track::measures] for the measures of one
+    /// instrument.
+    pub fn measures(&self) -> impl ExactSizeIterator<Item = Measure<'a>> + 'a {
+        let score = *self;
+        (0..self.parsed.measures.len()).map(move |index| Measure { score, index })
+    }
+
+    /// The measure at an index of [ParseResult::measures]
+    pub fn measure(&self, index: usize) -> Option<Measure<'a>> {
+        (index < self.parsed.measures.len()).then_some(Measure { score: *self, index })
+    }
+
+    /// The tick at `index`, counting every tick of the input
+    pub fn tick(&self, index: usize) -> Option<Tick<'a>> {
+        (index * 6 + 6 <= self.parsed.tick_stream.len()).then_some(Tick { score: *self, index })
+    }
+
+    /// The Part the element at `stream_idx` of [ParseResult::tick_stream] belongs to
+    fn part_of(&self, stream_idx: usize) -> Part<'a> {
+        let offsets = &self.parsed.offsets;
+        let index = offsets.partition_point(|x| x.1 as usize <= stream_idx).saturating_sub(1);
+        Part { score: *self, index }
+    }
+}
+
+/// An instrument, see [parser::Track]
+#[derive(Clone, Copy)]
+pub struct Track<'a> {
+    score: Score<'a>,
+    index: usize,
+}
+
+impl<'a> Track<'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    fn inner(&self) -> &'a parser::Track {
+        &self.score.parsed.tracks[self.index]
+    }
+
+    pub fn name(&self) -> &'a str {
+        &self.inner().name
+    }
+
+    pub fn program(&self) -> Option<u8> {
+        self.inner().program
+    }
+
+    pub fn midi_program(&self) -> u8 {
+        self.inner().midi_program()
+    }
+
+    /// The measures of the instrument, in order
+    pub fn measures(&self) -> impl ExactSizeIterator<Item = Measure<'a>> + 'a {
+        let score = self.score;
+        self.inner().measures.iter().map(move |x| Measure { score, index: *x as usize })
+    }
+
+    /// The number of ticks in all measures of the instrument
+    pub fn tick_count(&self) -> usize {
+        self.measures().map(|x| x.tick_count()).sum()
+    }
+
+    /// The notes of the instrument as they sound, string by string. A note is picked once and
+    /// rings until the next note or rest on its string. Hammer-ons and pull-offs change its pitch
+    /// at once, slides and bends glide during their tick, and `~` adds vibrato. A dead note lasts
+    /// one tick.
+    pub fn sounding_notes(&self) -> Vec<SoundingNote> {
+        let ticks = self.measures().flat_map(|x| x.ticks()).collect::<Vec<_>>();
+        let mut notes = vec![];
+        for string in 0..6 {
+            let mut current: Option<SoundingNote> = None;
+            let mut finish = |current: &mut Option<SoundingNote>, end: usize| {
+                if let Some(mut note) = current.take() {
+                    note.len = end - note.tick;
+                    notes.push(note);
+                }
+            };
+            for (tick_idx, tick) in ticks.iter().enumerate() {
+                let open = tick.part().tuning().strings[string];
+                let element = |x: &Tick<'a>| -> &'a TabElement { &x.elements()[string] };
+                let prev = tick_idx.checked_sub(1).map(|x| element(&ticks[x]));
+                let next = ticks.get(tick_idx + 1).map(element);
+                match element(tick) {
+                    TabElement::Fret(fret) => match (&mut current, prev) {
+                        (Some(note), Some(TabElement::HammerOn | TabElement::Pull)) => {
+                            note.glides.push(Glide { tick: tick_idx, len: 0, to: open + fret })
+                        }
+                        // the glide to this fret started on the tick before
+                        (
+                            Some(_),
+                            Some(TabElement::Slide | TabElement::Bend | TabElement::Release),
+                        ) => (),
+                        _ => {
+                            finish(&mut current, tick_idx);
+                            current = Some(SoundingNote::new(tick_idx, open + fret));
+                        }
+                    },
+                    TabElement::DeadNote => {
+                        finish(&mut current, tick_idx);
+                        current =
+                            Some(SoundingNote { dead: true, ..SoundingNote::new(tick_idx, open) });
+                        finish(&mut current, tick_idx + 1);
+                    }
+                    TabElement::Vibrato => {
+                        if let Some(note) = &mut current {
+                            note.vibrato.get_or_insert(tick_idx);
+                        }
+                    }
+                    technique @ (TabElement::Slide | TabElement::Bend | TabElement::Release) => {
+                        if let Some(note) = &mut current {
+                            let from = note.glides.last().map(|x| x.to).unwrap_or(note.pitch);
+                            let to = match (next, technique) {
+                                (Some(TabElement::Fret(fret)), _) => Some(open + fret),
+                                (_, TabElement::Bend) => Some(from.saturating_add(2)),
+                                (_, TabElement::Release) => from.checked_sub(2),
+                                _ => None,
+                            };
+                            if let Some(to) = to {
+                                note.glides.push(Glide { tick: tick_idx, len: 1, to });
+                            }
+                        }
+                    }
+                    TabElement::HammerOn | TabElement::Pull => (),
+                    TabElement::Rest => finish(&mut current, tick_idx),
+                }
+            }
+            finish(&mut current, ticks.len());
+        }
+        notes
+    }
+}
+
+/// A note as it sounds, see [Track::sounding_notes]. Ticks are counted from the start of the
+/// track.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoundingNote {
+    pub tick: usize,
+    pub len: usize,
+    /// The pitch it is picked at
+    pub pitch: u8,
+    /// Sorted by their tick
+    pub glides: Vec<Glide>,
+    /// The tick where the vibrato starts
+    pub vibrato: Option<usize>,
+    pub dead: bool,
+}
+
+impl SoundingNote {
+    fn new(tick: usize, pitch: u8) -> Self {
+        Self { tick, len: 0, pitch, glides: vec![], vibrato: None, dead: false }
+    }
+}
+
+/// A change of pitch while a note rings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glide {
+    pub tick: usize,
+    /// In ticks, 0 for a hammer-on or a pull-off
+    pub len: usize,
+    pub to: u8,
+}
+
+/// Six lines of the input, with their string names
+#[derive(Clone, Copy)]
+pub struct Part<'a> {
+    score: Score<'a>,
+    index: usize,
+}
+
+impl<'a> Part<'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The index of the line with the first string
+    pub fn line(&self) -> usize {
+        self.score.parsed.offsets[self.index].0 as usize
+    }
+
+    pub fn string_names(&self) -> &'a [char] {
+        &self.score.parsed.base_notes[self.index * 6..self.index * 6 + 6]
+    }
+
+    /// The open pitch of every string. String names which are not note names are read as
+    /// standard tuning.
+    pub fn tuning(&self) -> Tuning {
+        Tuning::from_base_notes(self.string_names()).unwrap_or_default()
+    }
+
+    pub fn measures(&self) -> impl Iterator<Item = Measure<'a>> + 'a {
+        let index = self.index;
+        self.score.measures().filter(move |x| x.part().index == index)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Measure<'a> {
+    score: Score<'a>,
+    index: usize,
+}
+
+impl<'a> Measure<'a> {
+    /// The index in [ParseResult::measures]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The index among the measures of its part, as counted in the input line
+    pub fn index_in_part(&self) -> usize {
+        let part = self.part().index;
+        self.score.measures().take(self.index).filter(|x| x.part().index == part).count()
+    }
+
+    /// The index of the first element in [ParseResult::tick_stream]
+    fn start(&self) -> usize {
+        *self.score.parsed.measures[self.index].data_range.start() as usize
+    }
+
+    pub fn tick_count(&self) -> usize {
+        rlen(&self.score.parsed.measures[self.index].data_range) as usize / 6
+    }
+
+    pub fn ticks(&self) -> impl ExactSizeIterator<Item = Tick<'a>> + 'a {
+        let (score, first) = (self.score, self.start() / 6);
+        (first..first + self.tick_count()).map(move |index| Tick { score, index })
+    }
+
+    pub fn part(&self) -> Part<'a> {
+        self.score.part_of(self.start())
+    }
+
+    /// The instrument the measure belongs to
+    pub fn track(&self) -> Option<Track<'a>> {
+        let measure = self.index as u32;
+        let index = self.score.parsed.tracks.iter().position(|x| x.measures.contains(&measure))?;
+        Some(Track { score: self.score, index })
+    }
+}
+
+/// An eighth, with an element on every string
+#[derive(Clone, Copy)]
+pub struct Tick<'a> {
+    score: Score<'a>,
+    index: usize,
+}
+
+impl<'a> Tick<'a> {
+    /// The index of the tick, counting every tick of the input
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The index of the element on the first string in [ParseResult::tick_stream]
+    pub fn stream_idx(&self) -> usize {
+        self.index * 6
+    }
+
+    /// The element on every string
+    pub fn elements(&self) -> &'a [TabElement] {
+        &self.score.parsed.tick_stream[self.index * 6..self.index * 6 + 6]
+    }
+
+    pub fn part(&self) -> Part<'a> {
+        self.score.part_of(self.stream_idx())
+    }
+
+    /// The note on `string`, if there is one
+    pub fn note(&self, string: usize) -> Option<Note<'a>> {
+        match self.elements().get(string)? {
+            TabElement::Fret(_) | TabElement::DeadNote => Some(Note { tick: *self, string }),
+            _ => None,
+        }
+    }
+
+    /// The frets and dead notes of the tick, from the first string
+    pub fn notes(&self) -> impl Iterator<Item = Note<'a>> + 'a {
+        let tick = *self;
+        (0..6).filter_map(move |string| tick.note(string))
+    }
+
+    /// The techniques written on this tick, with their string
+    pub fn techniques(&self) -> impl Iterator<Item = (usize, Technique)> + 'a {
+        let elements = self.elements().iter().enumerate();
+        elements.filter_map(|(string, elem)| Some((string, Technique::from_element(elem)?)))
+    }
+}
+
+/// A fret or a dead note
+#[derive(Clone, Copy)]
+pub struct Note<'a> {
+    tick: Tick<'a>,
+    string: usize,
+}
+
+impl<'a> Note<'a> {
+    pub fn tick(&self) -> Tick<'a> {
+        self.tick
+    }
+
+    pub fn string(&self) -> usize {
+        self.string
+    }
+
+    /// The index of the note in [ParseResult::tick_stream]
+    pub fn stream_idx(&self) -> usize {
+        self.tick.stream_idx() + self.string
+    }
+
+    /// None for a dead note
+    pub fn fret(&self) -> Option<u8> {
+        match self.tick.elements()[self.string] {
+            TabElement::Fret(fret) => Some(fret),
+            _ => None,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.fret().is_none()
+    }
+
+    /// The sounding pitch. A dead note has the pitch of the open string.
+    pub fn pitch(&self) -> u8 {
+        self.tick.part().tuning().strings[self.string] + self.fret().unwrap_or(0)
+    }
+
+    /// The technique written right after the note, which connects it to the next note on its
+    /// string
+    pub fn technique(&self) -> Option<Technique> {
+        let next = self.stream_idx() + 6;
+        if self.tick.score.part_of(next).index != self.tick.part().index {
+            return None;
+        }
+        Technique::from_element(self.tick.score.parsed.tick_stream.get(next)?)
+    }
+
+    /// The line and character index of the note in the input
+    pub fn location(&self) -> (u32, u32) {
+        source_location_from_stream(self.tick.score.parsed, self.stream_idx() as u32)
+    }
+}
@@ -0,0 +1,124 @@
+use crate::parser::{
+    parser::parse,
+    score::{Glide, Note, SoundingNote, Technique},
+};
+
+fn lines(s: &str) -> Vec<String> {
+    s.lines().map(|x| x.to_string()).collect()
+}
+
+const INPUT: &str = "Track: Lead
+e|-------|---|
+B|-------|---|
+G|-5h7/9-|---|
+D|-------|-x-|
+A|-------|---|
+E|-------|---|
+
+Track: Rhythm
+e|-----|
+B|-----|
+G|-----|
+D|-----|
+A|-----|
+D|-0~--|";
+
+fn summary(note: Note) -> (usize, usize, Option<u8>, u8, Option<Technique>, (u32, u32)) {
+    let tick = note.tick().index();
+    (tick, note.string(), note.fret(), note.pitch(), note.technique(), note.location())
+}
+
+#[test]
+fn test_score_view() {
+    let parsed = parse(&lines(INPUT));
+    assert!(parsed.error.is_none());
+    let score = parsed.score();
+    assert_eq!(score.tracks().map(|x| x.name()).collect::<Vec<_>>(), ["Lead", "Rhythm"]);
+    assert_eq!(score.measures().len(), 3);
+    assert_eq!(score.parts().map(|x| x.line()).collect::<Vec<_>>(), [1, 9]);
+
+    let lead = score.tracks().next().unwrap();
+    let notes = lead
+        .measures()
+        .flat_map(|x| x.ticks())
+        .flat_map(|x| x.notes())
+        .map(summary)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            (1, 2, Some(5), 60, Some(Technique::HammerOn), (3, 3)),
+            (3, 2, Some(7), 62, Some(Technique::Slide), (3, 5)),
+            (5, 2, Some(9), 64, None, (3, 7)),
+            // a dead note sounds like the open string
+            (8, 3, None, 50, None, (4, 11)),
+        ]
+    );
+    let techniques = lead.measures().flat_map(|x| x.ticks()).flat_map(|x| x.techniques());
+    assert_eq!(techniques.collect::<Vec<_>>(), [(2, Technique::HammerOn), (2, Technique::Slide)]);
+
+    // the lowest string is tuned down to D, and `~` holds the note
+    let rhythm = score.tracks().nth(1).unwrap();
+    let measure = rhythm.measures().next().unwrap();
+    assert_eq!(measure.index(), 2);
+    // diagnostics count the measures of every line from 0
+    assert_eq!(measure.index_in_part(), 0);
+    assert_eq!(score.measure(1).map(|x| x.index_in_part()), Some(1));
+    assert_eq!(measure.part().string_names(), ['e', 'B', 'G', 'D', 'A', 'D']);
+    assert_eq!(measure.track().map(|x| x.index()), Some(1));
+    assert_eq!(measure.tick_count(), 5);
+    let note = measure.ticks().nth(1).unwrap().note(5).unwrap();
+    assert_eq!((note.pitch(), note.technique()), (38, Some(Technique::Vibrato)));
+    // ticks are counted over the whole input
+    let last = score.tick(9).unwrap();
+    assert_eq!(last.part().index(), 0);
+    assert!(score.tick(15).is_none());
+}
+
+#[test]
+fn test_sounding_notes() {
+    let parsed = parse(&lines(INPUT));
+    let mut tracks = parsed.score().tracks();
+    let lead = tracks.next().unwrap();
+    assert_eq!(lead.tick_count(), 10);
+    assert_eq!(
+        lead.sounding_notes(),
+        [
+            // picked once, then the hammer-on changes the pitch at once and the slide glides
+            SoundingNote {
+                tick: 1,
+                len: 5,
+                pitch: 60,
+                glides: vec![Glide { tick: 3, len: 0, to: 62 }, Glide { tick: 4, len: 1, to: 64 }],
+                vibrato: None,
+                dead: false,
+            },
+            SoundingNote { tick: 8, len: 1, pitch: 50, glides: vec![], vibrato: None, dead: true },
+        ]
+    );
+    let rhythm = tracks.next().unwrap();
+    let held =
+        SoundingNote { tick: 1, len: 2, pitch: 38, glides: vec![], vibrato: Some(2), dead: false };
+    assert_eq!(rhythm.sounding_notes(), [held]);
+}
+
+#[test]
+fn test_sounding_notes_bends_and_holds() {
+    let sounding = |path: &str| {
+        let parsed = parse(&lines(&std::fs::read_to_string(path).unwrap()));
+        let track = parsed.score().tracks().next().unwrap();
+        track.sounding_notes()
+    };
+    // the bend and the release glide during their tick, and the note is only picked once
+    let glides = vec![Glide { tick: 1, len: 1, to: 72 }, Glide { tick: 3, len: 1, to: 70 }];
+    assert_eq!(
+        sounding("input/bends.tab"),
+        [SoundingNote { tick: 0, len: 5, pitch: 70, glides, vibrato: None, dead: false }]
+    );
+    // the held note rings over the barline until the rest
+    let held =
+        SoundingNote { tick: 1, len: 10, pitch: 60, glides: vec![], vibrato: Some(2), dead: false };
+    let picked =
+        SoundingNote { tick: 0, len: 1, pitch: 48, glides: vec![], vibrato: None, dead: false };
+    assert_eq!(sounding("input/ties.tab"), [held, picked]);
+}
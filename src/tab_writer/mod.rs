@@ -0,0 +1,237 @@
+//! Writes a [ParseResult] back into a tab file, which [crate::parser::parser::parse] can read.
+//!
+//! This is the last step of everything that produces tabs, like the importers.
+
+use std::fmt::Write;
+
+use crate::backend::{errors::backend_error::BackendError, registry::Options};
+use crate::parser::{
+    parser::{ParseResult, Track},
+    tab_element::TabElement,
+};
+use crate::rlen;
+
+/// The name [crate::parser::parser::parse] gives to the instrument of a file without `Track:`
+/// lines
+const DEFAULT_TRACK_NAME: &str = "Guitar1";
+
+#[derive(Clone, Debug)]
+pub struct TabWriterSettings {
+    /// How many measures go into a Part, before starting a new one
+    pub measures_per_line: usize,
+    /// The maximum number of characters on a line, including the string name and the barlines.
+    /// A measure that is wider than this on its own gets a line to itself.
+    pub max_width: Option<usize>,
+}
+
+impl TabWriterSettings {
+    /// Reads the `measures-per-line` and `width` options of the backends which write a tab
+    pub(crate) fn from_options(options: &Options) -> Result<Self, BackendError> {
+        Ok(Self {
+            measures_per_line: options
+                .get_or("measures-per-line", Self::default().measures_per_line)?,
+            max_width: options.get("width")?,
+        })
+    }
+}
+
+impl Default for TabWriterSettings {
+    fn default() -> Self {
+        Self { measures_per_line: 4, max_width: None }
+    }
+}
+
+/// Writes every track of `parsed`. A track is only introduced with a `Track:` line if the file
+/// can't be read back without it.
+pub fn write_tab(
+    parsed: &ParseResult, buf: &mut impl Write, settings: &TabWriterSettings,
+) -> std::fmt::Result {
+    let need_directives = need_directives(parsed);
+    for (track_idx, track) in parsed.tracks.iter().enumerate() {
+        if need_directives {
+            if track_idx > 0 {
+                buf.write_char('\n')?;
+            }
+            write_track_directive(buf, track)?;
+        }
+        for (line_idx, line) in wrap(parsed, &track.measures, settings).into_iter().enumerate() {
+            if line_idx > 0 {
+                buf.write_char('\n')?;
+            }
+            write_part(parsed, buf, line, measure_base_notes(parsed, line[0]))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the tracks of `parsed` can only be read back with `Track:` lines
+fn need_directives(parsed: &ParseResult) -> bool {
+    parsed.tracks.len() > 1
+        || parsed.tracks.iter().any(|x| x.name != DEFAULT_TRACK_NAME || x.program.is_some())
+}
+
+fn write_track_directive(buf: &mut impl Write, track: &Track) -> std::fmt::Result {
+    write!(buf, "Track: {}", track.name)?;
+    if let Some(program) = track.program {
+        write!(buf, " program={program}")?;
+    }
+    buf.write_char('\n')
+}
+
+/// Writes every Part of `parsed` on a line of its own, in the order of [ParseResult::offsets].
+/// A `Track:` line is written whenever the track changes, if the file can't be read back without
+/// it.
+pub fn write_parts(parsed: &ParseResult, buf: &mut impl Write) -> std::fmt::Result {
+    let need_directives = need_directives(parsed);
+    let mut measure_start = 0;
+    let mut last_track = None;
+    let mut buf_is_empty = true;
+    for (part_idx, _) in parsed.offsets.iter().enumerate() {
+        // the measures of a Part are the ones before the tick stream of the next Part
+        let stream_end = parsed.offsets.get(part_idx + 1).map_or(u32::MAX, |x| x.1);
+        let measure_end =
+            parsed.measures.partition_point(|x| *x.data_range.start() < stream_end) as u32;
+        let measures = (measure_start..measure_end).collect::<Vec<_>>();
+        measure_start = measure_end;
+        let Some(first) = measures.first() else {
+            continue;
+        };
+        if !buf_is_empty {
+            buf.write_char('\n')?;
+        }
+        buf_is_empty = false;
+        let track = parsed.tracks.iter().position(|x| x.measures.contains(first));
+        if need_directives && track != last_track {
+            if let Some(track) = track {
+                write_track_directive(buf, &parsed.tracks[track])?;
+            }
+            last_track = track;
+        }
+        let names = std::array::from_fn(|s| parsed.base_notes[part_idx * 6 + s]);
+        write_part(parsed, buf, &measures, names)?;
+    }
+    Ok(())
+}
+
+/// Splits `measures` into the lines of Parts. Measures are never split, and a Part can only have
+/// one set of string names, so a new line is started when they change.
+pub fn wrap<'a>(
+    parsed: &ParseResult, measures: &'a [u32], settings: &TabWriterSettings,
+) -> Vec<&'a [u32]> {
+    let mut lines = vec![];
+    let mut line_start = 0;
+    while line_start < measures.len() {
+        let names = measure_base_notes(parsed, measures[line_start]);
+        // the string name and the opening barline
+        let mut width = 2;
+        let line_len = measures[line_start..]
+            .iter()
+            .take(settings.measures_per_line.max(1))
+            .take_while(|x| measure_base_notes(parsed, **x) == names)
+            .enumerate()
+            .take_while(|(idx, x)| {
+                width += measure_width(parsed, **x);
+                *idx == 0 || settings.max_width.map(|max| width <= max).unwrap_or(true)
+            })
+            .count();
+        lines.push(&measures[line_start..line_start + line_len]);
+        line_start += line_len;
+    }
+    lines
+}
+
+/// The number of characters the measure takes on a line, with its closing barline
+fn measure_width(parsed: &ParseResult, measure_idx: u32) -> usize {
+    let range = &parsed.measures[measure_idx as usize].data_range;
+    let start = *range.start() as usize;
+    let ticks = (0..rlen(range) as usize / 6).map(|tick| {
+        let tick = &parsed.tick_stream[start + tick * 6..start + tick * 6 + 6];
+        tick.iter().map(|x| x.repr_len()).max().unwrap() as usize
+    });
+    ticks.sum::<usize>() + 1
+}
+
+fn measure_base_notes(parsed: &ParseResult, measure_idx: u32) -> [char; 6] {
+    let start = *parsed.measures[measure_idx as usize].data_range.start() as usize;
+    std::array::from_fn(|s| parsed.base_note(start + s))
+}
+
+/// Writes six lines with the given measures
+pub fn write_part(
+    parsed: &ParseResult, buf: &mut impl Write, measures: &[u32], names: [char; 6],
+) -> std::fmt::Result {
+    let mut lines = names.map(|name| {
+        let mut line = String::new();
+        line.push(name);
+        line.push('|');
+        line
+    });
+    for measure_idx in measures {
+        let range = &parsed.measures[*measure_idx as usize].data_range;
+        let start = *range.start() as usize;
+        for tick in 0..rlen(range) as usize / 6 {
+            let tick = &parsed.tick_stream[start + tick * 6..start + tick * 6 + 6];
+            let width = tick.iter().map(|x| x.repr_len()).max().unwrap();
+            for (line, elem) in lines.iter_mut().zip(tick) {
+                write_tab_element(line, elem)?;
+                for _ in elem.repr_len()..width {
+                    line.push('-');
+                }
+            }
+        }
+        lines.iter_mut().for_each(|x| x.push('|'));
+    }
+    for line in lines {
+        buf.write_str(&line)?;
+        buf.write_char('\n')?;
+    }
+    Ok(())
+}
+
+pub fn write_tab_element(buf: &mut impl Write, elem: &TabElement) -> std::fmt::Result {
+    match elem {
+        TabElement::Fret(x) => write!(buf, "{x}"),
+        TabElement::Rest => buf.write_char('-'),
+        TabElement::DeadNote => buf.write_char('x'),
+        TabElement::Bend => buf.write_char('b'),
+        TabElement::HammerOn => buf.write_char('h'),
+        TabElement::Pull => buf.write_char('p'),
+        TabElement::Release => buf.write_char('r'),
+        TabElement::Slide => buf.write_char('/'),
+        TabElement::Vibrato => buf.write_char('~'),
+    }
+}
+
+#[test]
+fn test_write_tab() {
+    use crate::parser::parser::parse;
+    let input = "
+e|-12-----|--------|
+B|---0h1--|--------|
+G|--------|-x---15-|
+D|--------|--------|
+A|3-------|--------|
+E|--------|--------|";
+    let lines = input.lines().map(|x| x.to_string()).collect::<Vec<_>>();
+    let parsed = parse(&lines);
+    let mut out = String::new();
+    write_tab(&parsed, &mut out, &TabWriterSettings::default()).unwrap();
+    assert_eq!(out, input.trim_start().to_string() + "\n");
+
+    let mut out = String::new();
+    let settings = TabWriterSettings { measures_per_line: 1, ..Default::default() };
+    write_tab(&parsed, &mut out, &settings).unwrap();
+    let reparsed = parse(&out.lines().map(|x| x.to_string()).collect::<Vec<_>>());
+    assert!(reparsed.error.is_none());
+    assert_eq!(reparsed.offsets.len(), 2);
+    assert_eq!(reparsed.tick_stream, parsed.tick_stream);
+
+    // "e|-12-----|" is 11 characters, the two measures together are 20
+    for (max_width, parts) in [(20, 1), (19, 2), (5, 2)] {
+        let mut out = String::new();
+        let settings = TabWriterSettings { max_width: Some(max_width), ..Default::default() };
+        write_tab(&parsed, &mut out, &settings).unwrap();
+        let reparsed = parse(&out.lines().map(|x| x.to_string()).collect::<Vec<_>>());
+        assert_eq!(reparsed.offsets.len(), parts, "{max_width}");
+    }
+}